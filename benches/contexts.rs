@@ -0,0 +1,99 @@
+//! Throughput of per call functions, which parse the key on every call, compared to reusable contexts
+//! on many small messages
+//!
+//! Run with `cargo bench`
+use std::hint::black_box;
+use std::time::Instant;
+use crypto_ext::asymmetric;
+use crypto_ext::asymmetric::signing;
+use crypto_ext::key_store::MemoryKeyStore;
+use crypto_ext::symmetric;
+
+const MESSAGE_LENGTH: usize = 64;
+
+fn measure(name: &str, messages: usize, mut operation: impl FnMut()) {
+    let started = Instant::now();
+    for _ in 0..messages {
+        operation();
+    }
+    let elapsed = started.elapsed();
+    println!("{:<48} {:>12.0} messages/s", name, messages as f64 / elapsed.as_secs_f64());
+}
+
+fn symmetric_encryption() {
+    let messages = 100_000;
+    let data = [7u8; MESSAGE_LENGTH];
+    let (encryption_params, decryption_params) = symmetric::encryption::setup_with_store(&MemoryKeyStore::new(), symmetric::encryption::Algorithm::Aes256Gcm).unwrap();
+    let encrypted_data = symmetric::encryption::encrypt(encryption_params.clone(), &data, &[]).unwrap();
+
+    measure("symmetric::encryption::encrypt", messages, || {
+        black_box(symmetric::encryption::encrypt(encryption_params.clone(), &data, &[]).unwrap());
+    });
+    let encryptor = symmetric::encryption::Encryptor::new(&encryption_params).unwrap();
+    measure("symmetric::encryption::Encryptor::encrypt", messages, || {
+        black_box(encryptor.encrypt(&data, &[]).unwrap());
+    });
+
+    measure("symmetric::encryption::decrypt", messages, || {
+        black_box(symmetric::encryption::decrypt(decryption_params.clone(), encrypted_data.as_slice(), &[]).unwrap());
+    });
+    let decryptor = symmetric::encryption::Decryptor::new(&decryption_params).unwrap();
+    measure("symmetric::encryption::Decryptor::decrypt", messages, || {
+        black_box(decryptor.decrypt(encrypted_data.as_slice(), &[]).unwrap());
+    });
+}
+
+fn asymmetric_encryption() {
+    let messages = 200;
+    let data = [7u8; MESSAGE_LENGTH];
+    let (encryption_params, decryption_params) = asymmetric::encryption::setup_with_store(&MemoryKeyStore::new()).unwrap();
+    let encrypted_data = asymmetric::encryption::encrypt(encryption_params.clone(), &data).unwrap();
+
+    measure("asymmetric::encryption::encrypt", messages, || {
+        black_box(asymmetric::encryption::encrypt(encryption_params.clone(), &data).unwrap());
+    });
+    let encryptor = asymmetric::encryption::Encryptor::new(&encryption_params).unwrap();
+    measure("asymmetric::encryption::Encryptor::encrypt", messages, || {
+        black_box(encryptor.encrypt(&data).unwrap());
+    });
+
+    measure("asymmetric::encryption::decrypt", messages, || {
+        black_box(asymmetric::encryption::decrypt(decryption_params.clone(), encrypted_data.as_slice()).unwrap());
+    });
+    let decryptor = asymmetric::encryption::Decryptor::new(&decryption_params).unwrap();
+    measure("asymmetric::encryption::Decryptor::decrypt", messages, || {
+        black_box(decryptor.decrypt(encrypted_data.as_slice()).unwrap());
+    });
+}
+
+fn signing() {
+    let messages = 10_000;
+    let data = [7u8; MESSAGE_LENGTH];
+
+    for algorithm in [signing::Algorithm::EcdsaP256, signing::Algorithm::Ed25519] {
+        let (signature_params, verification_params) = signing::setup_with_store(&MemoryKeyStore::new(), algorithm, &signing::KeyFileOptions::default()).unwrap();
+        let signature = signing::sign(signature_params.clone(), &data).unwrap();
+
+        measure(format!("signing::sign {:?}", algorithm).as_str(), messages, || {
+            black_box(signing::sign(signature_params.clone(), &data).unwrap());
+        });
+        let signer = signing::Signer::new(&signature_params).unwrap();
+        measure(format!("signing::Signer::sign {:?}", algorithm).as_str(), messages, || {
+            black_box(signer.sign(&data).unwrap());
+        });
+
+        measure(format!("signing::verify {:?}", algorithm).as_str(), messages, || {
+            assert!(black_box(signing::verify(verification_params.clone(), &data, signature.as_slice())).is_ok());
+        });
+        let verifier = signing::Verifier::new(&verification_params).unwrap();
+        measure(format!("signing::Verifier::verify {:?}", algorithm).as_str(), messages, || {
+            assert!(black_box(verifier.verify(&data, signature.as_slice())).is_ok());
+        });
+    }
+}
+
+fn main() {
+    symmetric_encryption();
+    asymmetric_encryption();
+    signing();
+}
@@ -0,0 +1,277 @@
+use crate::Error;
+use crate::asymmetric::key_agreement;
+use crate::asymmetric::key_agreement::{Algorithm, PrivateKeyParameters, PublicKeyParameters};
+use crate::kdf::hkdf;
+use crate::key_store::{FileSystemKeyStore, KeyStore};
+use crate::secret::Secret;
+use crate::symmetric;
+
+#[cfg(test)]
+mod tests;
+
+// layout: magic, format version, key agreement algorithm, symmetric algorithm, ephemeral public key, nonce, encrypted data and tag
+const MAGIC: &[u8] = b"CXEC";
+const VERSION: u8 = 1;
+const HEADER_LENGTH: usize = 7;
+
+/// EncryptionParameters is basically the key agreement algorithm and raw public key of the recipient
+///
+/// Public key is 32 bytes long u-coordinate for X25519 and SEC1 encoded point for ECDH P-256
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionParameters {
+    pub algorithm: Algorithm,
+    pub public_key: Vec<u8>,
+}
+
+/// DecryptionParameters is basically the key agreement algorithm and raw private key of the recipient,
+/// private key is wiped from memory on drop
+#[derive(Debug, Clone)]
+pub struct DecryptionParameters {
+    pub algorithm: Algorithm,
+    pub private_key: Secret<Vec<u8>>,
+}
+
+/// Will read or create X25519 EncryptionParameters and DecryptionParameters at the given location which is relative to the working directory
+///
+/// Keys are the static key pair of [key_agreement::setup], stored in the same files
+pub fn setup(path_to_encryption_parameters: Option<&str>) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    setup_with_algorithm(path_to_encryption_parameters, Algorithm::default())
+}
+
+/// Will read or create EncryptionParameters and DecryptionParameters for the given algorithm at the given location which is relative to the working directory
+pub fn setup_with_algorithm(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    setup_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Will read or create EncryptionParameters and DecryptionParameters for the given algorithm in the given key store
+pub fn setup_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    let boxed_key_pair = key_agreement::setup_with_store(store, algorithm);
+    if boxed_key_pair.is_err() {
+        return Err(boxed_key_pair.err().unwrap());
+    }
+    let (private_key_params, public_key_params) = boxed_key_pair.unwrap();
+
+    let encryption_params = EncryptionParameters {
+        algorithm,
+        public_key: public_key_params.public_key,
+    };
+
+    let decryption_params = DecryptionParameters {
+        algorithm,
+        private_key: private_key_params.private_key,
+    };
+
+    Ok((encryption_params, decryption_params))
+}
+
+/// Returns EncryptionParameters stored at the given location which is relative to the working directory
+///
+/// If location holds key pairs of several algorithms, X25519 one is returned, use [get_encryption_params_with_algorithm] to pick another
+pub fn get_encryption_params(path_to_encryption_parameters: Option<&str>) -> Result<EncryptionParameters, Error> {
+    let boxed_public_key_params = key_agreement::get_public_key_params(path_to_encryption_parameters);
+    if boxed_public_key_params.is_err() {
+        return Err(boxed_public_key_params.err().unwrap());
+    }
+    let public_key_params = boxed_public_key_params.unwrap();
+
+    Ok(EncryptionParameters { algorithm: public_key_params.algorithm, public_key: public_key_params.public_key })
+}
+
+/// Returns EncryptionParameters of the given algorithm stored at the given location which is relative to the working directory
+pub fn get_encryption_params_with_algorithm(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<EncryptionParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_encryption_params_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Returns EncryptionParameters of the given algorithm stored in the given key store
+pub fn get_encryption_params_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<EncryptionParameters, Error> {
+    let boxed_public_key_params = key_agreement::get_public_key_params_with_store(store, algorithm);
+    if boxed_public_key_params.is_err() {
+        return Err(boxed_public_key_params.err().unwrap());
+    }
+
+    Ok(EncryptionParameters { algorithm, public_key: boxed_public_key_params.unwrap().public_key })
+}
+
+/// Returns DecryptionParameters stored at the given location which is relative to the working directory
+///
+/// If location holds key pairs of several algorithms, X25519 one is returned, use [get_decryption_params_with_algorithm] to pick another
+pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Result<DecryptionParameters, Error> {
+    let boxed_private_key_params = key_agreement::get_private_key_params(path_to_encryption_parameters);
+    if boxed_private_key_params.is_err() {
+        return Err(boxed_private_key_params.err().unwrap());
+    }
+    let private_key_params = boxed_private_key_params.unwrap();
+
+    Ok(DecryptionParameters { algorithm: private_key_params.algorithm, private_key: private_key_params.private_key })
+}
+
+/// Returns DecryptionParameters of the given algorithm stored at the given location which is relative to the working directory
+pub fn get_decryption_params_with_algorithm(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<DecryptionParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_decryption_params_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Returns DecryptionParameters of the given algorithm stored in the given key store
+pub fn get_decryption_params_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<DecryptionParameters, Error> {
+    let boxed_private_key_params = key_agreement::get_private_key_params_with_store(store, algorithm);
+    if boxed_private_key_params.is_err() {
+        return Err(boxed_private_key_params.err().unwrap());
+    }
+
+    Ok(DecryptionParameters { algorithm, private_key: boxed_private_key_params.unwrap().private_key })
+}
+
+/// Encrypts given byte array of any length to the public key of the recipient
+///
+/// Ephemeral key pair is generated for each call, AES-256-GCM key is derived with HKDF-SHA256 from the shared secret
+/// of the ephemeral private key and the recipient public key. Output holds the ephemeral public key and is
+/// 67 bytes longer than the data for X25519 and 100 bytes longer for ECDH P-256.
+///
+/// # Examples
+///
+/// ```
+///    use crypto_ext::asymmetric::ecies::{decrypt, encrypt, get_decryption_params, get_encryption_params, setup};
+///
+///    #[test]
+///    fn ecies_encryption() {
+///        let data = vec![7; 1024 * 1024];
+///        let associated_data = "some unencrypted data".as_bytes();
+///
+///        // path needs to be accessible by user with write permission for initial setup
+///        let params_path = "/test/ecies_parameters/";
+///        let _ = setup(Some(params_path)).unwrap();
+///
+///        // public key is all the sender needs
+///        let encryption_params = get_encryption_params(Some(params_path)).unwrap();
+///        let encrypted = encrypt(encryption_params, data.as_slice(), associated_data).unwrap();
+///        assert_eq!(encrypted.len(), data.len() + 67);
+///
+///        let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+///        let decrypted = decrypt(decryption_params, encrypted.as_slice(), associated_data).unwrap();
+///        assert_eq!(data, decrypted);
+///    }
+/// ```
+pub fn encrypt(params: EncryptionParameters, data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let algorithm = params.algorithm;
+    let symmetric_algorithm = symmetric::encryption::Algorithm::Aes256Gcm;
+
+    let boxed_ephemeral_key_pair = key_agreement::generate_key_pair(algorithm);
+    if boxed_ephemeral_key_pair.is_err() {
+        return Err(boxed_ephemeral_key_pair.err().unwrap());
+    }
+    let (ephemeral_private_key_params, ephemeral_public_key_params) = boxed_ephemeral_key_pair.unwrap();
+
+    let mut header = Vec::with_capacity(HEADER_LENGTH + ephemeral_public_key_params.public_key.len());
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    header.push(algorithm.id());
+    header.push(symmetric_algorithm.id());
+    header.extend_from_slice(ephemeral_public_key_params.public_key.as_slice());
+
+    // compressed ECDH P-256 public key is accepted, the uncompressed one is bound to the key
+    let boxed_recipient_public_key = key_agreement::canonical_public_key(algorithm, params.public_key.as_slice());
+    if boxed_recipient_public_key.is_err() {
+        return Err(boxed_recipient_public_key.err().unwrap());
+    }
+    let recipient_public_key_params = PublicKeyParameters { algorithm, public_key: boxed_recipient_public_key.unwrap() };
+
+    let boxed_key = derive_key(&ephemeral_private_key_params, &recipient_public_key_params, header.as_slice(), recipient_public_key_params.public_key.as_slice(), symmetric_algorithm);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+
+    // header is authenticated along the associated data, so neither algorithm nor ephemeral key can be swapped
+    let encryption_associated_data = [header.as_slice(), associated_data].concat();
+
+    let symmetric_params = symmetric::encryption::EncryptionParameters { algorithm: symmetric_algorithm, key: boxed_key.unwrap() };
+    let boxed_encrypted_data = symmetric::encryption::encrypt(symmetric_params, data, encryption_associated_data.as_slice());
+    if boxed_encrypted_data.is_err() {
+        return Err(boxed_encrypted_data.err().unwrap());
+    }
+
+    header.extend_from_slice(boxed_encrypted_data.unwrap().as_slice());
+    Ok(header)
+}
+
+/// Decrypts given byte array encrypted by [encrypt] with the private key of the recipient
+///
+/// Returns `Error::KeyMismatch` if data was encrypted to a key of another algorithm and
+/// `Error::Authentication` if it was encrypted to another key or was tampered with
+pub fn decrypt(params: DecryptionParameters, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    if encrypted_data.len() < HEADER_LENGTH || !encrypted_data.starts_with(MAGIC) {
+        return Err(Error::format("unrecognized ECIES encrypted data format"))
+    }
+
+    let version = encrypted_data[MAGIC.len()];
+    if version != VERSION {
+        let message = format!("unsupported ECIES encrypted data version: {}", version);
+        return Err(Error::format(message))
+    }
+
+    let boxed_algorithm = Algorithm::from_id(encrypted_data[MAGIC.len() + 1]);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+    let algorithm = boxed_algorithm.unwrap();
+
+    if algorithm != params.algorithm {
+        let message = format!("data is encrypted to {:?} key, decryption key is for {:?}", algorithm, params.algorithm);
+        return Err(Error::key_mismatch(message))
+    }
+
+    let boxed_symmetric_algorithm = symmetric::encryption::Algorithm::from_id(encrypted_data[MAGIC.len() + 2]);
+    if boxed_symmetric_algorithm.is_err() {
+        return Err(boxed_symmetric_algorithm.err().unwrap());
+    }
+    let symmetric_algorithm = boxed_symmetric_algorithm.unwrap();
+
+    let header_length = HEADER_LENGTH + algorithm.public_key_length();
+    if encrypted_data.len() < header_length {
+        return Err(Error::format("ECIES encrypted data is shorter than its header"))
+    }
+    let header = &encrypted_data[..header_length];
+    let ephemeral_public_key_params = PublicKeyParameters { algorithm, public_key: header[HEADER_LENGTH..].to_vec() };
+
+    let boxed_recipient_public_key = key_agreement::derive_public_key(algorithm, params.private_key.as_slice());
+    if boxed_recipient_public_key.is_err() {
+        return Err(boxed_recipient_public_key.err().unwrap());
+    }
+
+    let private_key_params = PrivateKeyParameters { algorithm, private_key: params.private_key };
+    let boxed_key = derive_key(&private_key_params, &ephemeral_public_key_params, header, boxed_recipient_public_key.unwrap().as_slice(), symmetric_algorithm);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+
+    let decryption_associated_data = [header, associated_data].concat();
+
+    let symmetric_params = symmetric::encryption::DecryptionParameters { algorithm: symmetric_algorithm, key: boxed_key.unwrap() };
+    symmetric::encryption::decrypt(symmetric_params, &encrypted_data[header_length..], decryption_associated_data.as_slice())
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+// both public keys are bound to the derived key, HKDF info is the header holding the ephemeral public key
+// followed by the recipient public key
+fn derive_key(private_key_params: &PrivateKeyParameters, public_key_params: &PublicKeyParameters, header: &[u8], recipient_public_key: &[u8], symmetric_algorithm: symmetric::encryption::Algorithm) -> Result<Secret<Vec<u8>>, Error> {
+    let boxed_shared_secret = key_agreement::agree(private_key_params, public_key_params);
+    if boxed_shared_secret.is_err() {
+        return Err(boxed_shared_secret.err().unwrap());
+    }
+    let shared_secret = boxed_shared_secret.unwrap();
+
+    let info = [header, recipient_public_key].concat();
+    hkdf::derive(hkdf::Algorithm::HkdfSha256, &[], shared_secret.as_slice(), info.as_slice(), symmetric_algorithm.key_length())
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use crate::Error;
+use crate::asymmetric::ecies::{decrypt, encrypt, get_decryption_params, get_decryption_params_with_algorithm, get_encryption_params, get_encryption_params_with_algorithm, setup, setup_with_algorithm, setup_with_store, DecryptionParameters, EncryptionParameters};
+use crate::asymmetric::key_agreement;
+use crate::asymmetric::key_agreement::Algorithm;
+use crate::key_store::MemoryKeyStore;
+use crate::passphrase::generate_bytes;
+use crate::test_util::from_hex;
+
+const ALGORITHMS: [Algorithm; 2] = [Algorithm::X25519, Algorithm::EcdhP256];
+
+#[test]
+fn decryption_of_independently_encrypted_data() {
+    // encrypted by an independent implementation with fixed ephemeral keys and nonce,
+    // key pairs are the ones of RFC 7748 section 6.1 and RFC 5903 section 8.1
+    let vectors = [
+        (
+            Algorithm::X25519,
+            "5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb",
+            "435845430101028520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a000102030405060708090a0be8110a1deb17943d8c19d4929a0e93e9e0154f7871ced3ac81998eb1c148957f59de95e4",
+        ),
+        (
+            Algorithm::EcdhP256,
+            "c6ef9c5d78ae012a011164acb397ce2088685d8f06bf9be0b283ab46476bee53",
+            "4358454301020204dad0b65394221cf9b051e1feca5787d098dfe637fc90b9ef945d0c37725811805271a0461cdb8252d61f1c456fa3e59ab1f45b33accf5f58389e0577b8990bb3000102030405060708090a0bd03fc8632b493f04e32e6ee1f0cd1a9b9776e822de15ebb159821e7786ce5a2242ff9408",
+        ),
+    ];
+
+    for (algorithm, private_key, encrypted) in vectors {
+        let params = DecryptionParameters { algorithm, private_key: from_hex(private_key).into() };
+        let decrypted = decrypt(params.clone(), from_hex(encrypted).as_slice(), "some unencrypted data".as_bytes()).unwrap();
+        assert_eq!(decrypted, "some data to encrypt".as_bytes());
+
+        let error = decrypt(params, from_hex(encrypted).as_slice(), "other unencrypted data".as_bytes()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+    }
+}
+
+#[test]
+fn encryption_of_any_length() {
+    let associated_data = "some unencrypted data".as_bytes();
+
+    for algorithm in ALGORITHMS {
+        let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new(), algorithm).unwrap();
+        let overhead = 7 + algorithm.public_key_length() + 12 + 16;
+
+        for length in [0, 1, 501, 502, 1024 * 1024] {
+            let data = generate_bytes(length).unwrap();
+            let encrypted = encrypt(encryption_params.clone(), data.as_slice(), associated_data).unwrap();
+            assert_eq!(encrypted.len(), length + overhead);
+
+            let decrypted = decrypt(decryption_params.clone(), encrypted.as_slice(), associated_data).unwrap();
+            assert_eq!(data, decrypted);
+        }
+
+        // every encryption uses new ephemeral key
+        let data = "some data to encrypt".as_bytes();
+        let encrypted = encrypt(encryption_params.clone(), data, associated_data).unwrap();
+        let other_encrypted = encrypt(encryption_params.clone(), data, associated_data).unwrap();
+        assert_ne!(&encrypted[..7 + algorithm.public_key_length()], &other_encrypted[..7 + algorithm.public_key_length()]);
+    }
+
+    // compressed ECDH P-256 public key encrypts the same way
+    let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new(), Algorithm::EcdhP256).unwrap();
+    let public_key = encryption_params.public_key;
+    let mut compressed_public_key = vec![0x02 + (public_key[64] & 1)];
+    compressed_public_key.extend_from_slice(&public_key[1..33]);
+    let encryption_params = EncryptionParameters { algorithm: Algorithm::EcdhP256, public_key: compressed_public_key };
+    let encrypted = encrypt(encryption_params, "some data".as_bytes(), &[]).unwrap();
+    assert_eq!(decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap(), "some data".as_bytes());
+}
+
+#[test]
+fn tampered_data_is_rejected() {
+    let data = "some data to encrypt".as_bytes();
+
+    for algorithm in ALGORITHMS {
+        let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new(), algorithm).unwrap();
+        let encrypted = encrypt(encryption_params.clone(), data, &[]).unwrap();
+
+        // every byte after algorithm identifiers is authenticated, ephemeral key either fails to parse or derives another key
+        for index in 7..encrypted.len() {
+            let mut tampered = encrypted.clone();
+            tampered[index] ^= 1;
+            let error = decrypt(decryption_params.clone(), tampered.as_slice(), &[]).err().unwrap();
+            assert!(matches!(error, Error::Authentication { .. } | Error::Format { .. }), "byte {}", index);
+        }
+
+        let (_, other_decryption_params) = setup_with_store(&MemoryKeyStore::new(), algorithm).unwrap();
+        let error = decrypt(other_decryption_params, encrypted.as_slice(), &[]).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        for length in [0, 4, 6, 7, 7 + algorithm.public_key_length(), encrypted.len() - 1] {
+            assert!(decrypt(decryption_params.clone(), &encrypted[..length], &[]).is_err(), "{} bytes", length);
+        }
+    }
+
+    let (encryption_params, _) = setup_with_store(&MemoryKeyStore::new(), Algorithm::X25519).unwrap();
+    let (_, decryption_params) = setup_with_store(&MemoryKeyStore::new(), Algorithm::EcdhP256).unwrap();
+    let encrypted = encrypt(encryption_params, data, &[]).unwrap();
+    let error = decrypt(decryption_params.clone(), encrypted.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let mut unknown_version = encrypted.clone();
+    unknown_version[4] = 2;
+    let error = decrypt(decryption_params.clone(), unknown_version.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let mut unknown_algorithm = encrypted.clone();
+    unknown_algorithm[5] = 0xFF;
+    let error = decrypt(decryption_params.clone(), unknown_algorithm.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+
+    let error = decrypt(decryption_params, "not encrypted data".as_bytes(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let invalid_public_key = EncryptionParameters { algorithm: Algorithm::EcdhP256, public_key: vec![4; 65] };
+    let error = encrypt(invalid_public_key, data, &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn setup_and_get_params() {
+    let path_to_params = "/test/ecies_parameters_setup/";
+    let data = "some data to encrypt".as_bytes();
+
+    let (encryption_params, decryption_params) = setup(Some(path_to_params)).unwrap();
+    assert_eq!(encryption_params.algorithm, Algorithm::X25519);
+    assert_eq!(get_encryption_params(Some(path_to_params)).unwrap(), encryption_params);
+    assert_eq!(*get_decryption_params(Some(path_to_params)).unwrap().private_key, *decryption_params.private_key);
+
+    let encrypted = encrypt(get_encryption_params(Some(path_to_params)).unwrap(), data, &[]).unwrap();
+    assert_eq!(decrypt(get_decryption_params(Some(path_to_params)).unwrap(), encrypted.as_slice(), &[]).unwrap(), data);
+
+    // keys are the key agreement key pair
+    let (_, public_key_params) = key_agreement::setup(Some(path_to_params)).unwrap();
+    assert_eq!(public_key_params.public_key, encryption_params.public_key);
+
+    let (encryption_params, _) = setup_with_algorithm(Some(path_to_params), Algorithm::EcdhP256).unwrap();
+    assert_eq!(get_encryption_params_with_algorithm(Some(path_to_params), Algorithm::EcdhP256).unwrap(), encryption_params);
+    let encrypted = encrypt(encryption_params, data, &[]).unwrap();
+    let decryption_params = get_decryption_params_with_algorithm(Some(path_to_params), Algorithm::EcdhP256).unwrap();
+    assert_eq!(decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap(), data);
+
+    fs::remove_dir_all("test/ecies_parameters_setup").unwrap();
+
+    let error = get_decryption_params(Some("/test/missing_ecies_parameters/")).err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn parameters_are_shared_between_threads() {
+    assert_send_sync::<EncryptionParameters>();
+    assert_send_sync::<DecryptionParameters>();
+
+    let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new(), Algorithm::EcdhP256).unwrap();
+    let decryption_params = Arc::new(decryption_params);
+
+    let handles: Vec<_> = (0..4u8)
+        .map(|index| {
+            let encryption_params = encryption_params.clone();
+            let decryption_params = Arc::clone(&decryption_params);
+            thread::spawn(move || {
+                let data = vec![index; 1000];
+                let encrypted = encrypt(encryption_params, data.as_slice(), &[index]).unwrap();
+                assert_eq!(decrypt((*decryption_params).clone(), encrypted.as_slice(), &[index]).unwrap(), data);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
@@ -1,148 +1,176 @@
+use std::collections::BTreeMap;
+use openssl::encrypt::{Decrypter, Encrypter};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private, Public};
 use openssl::rsa::Padding;
 use openssl::rsa::Rsa;
 use openssl::symm::Cipher;
-use crate::{get_path_relative_to_working_directory, get_static_filepath, read_file, read_or_create_and_write};
-use crate::passphrase::generate_passphrase;
+use zeroize::Zeroize;
+use crate::Error;
+use crate::key_store::{read_or_create_key_pair, FileSystemKeyStore, KeyStore};
+use crate::passphrase::{generate_bytes, generate_passphrase};
+use crate::secret::Secret;
+use crate::symmetric;
 
 #[cfg(test)]
 mod tests;
 
 const RSA_SIZE: u32 = 4096;
 
-/// EncryptionParameters is basically the RSA .pem encoded public key
-///
-pub struct EncryptionParameters {
-    pub rsa_public_key_pem: String,
-}
+// names of the values in the key store, file names for the path based functions
+const PASSPHRASE_NAME: &str = ".rsa_passphrase";
+const PRIVATE_KEY_NAME: &str = ".rsa_private_key";
+const PUBLIC_KEY_NAME: &str = ".rsa_public_key";
 
-/// DecryptionParameters is basically the RSA .pem encoded private key and passphrase
-///
-pub struct DecryptionParameters {
-    pub rsa_passphrase: String,
-    pub rsa_private_key_pem: String,
-}
-
-/// Will read or create EncryptionParameters and DecryptionParameters at the given location which is relative to the working directory
-///
-pub fn setup(path_to_encryption_parameters: Option<&str>) -> Result<(EncryptionParameters, DecryptionParameters), String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".rsa_passphrase");
-    let boxed_passphrase_path = get_static_filepath(relative_path.as_str());
-    if boxed_passphrase_path.is_err() {
-        return Err(boxed_passphrase_path.err().unwrap());
-    }
-    let passphrase_path = boxed_passphrase_path.unwrap();
+// envelope layout: magic, format version, symmetric algorithm identifier, length of the wrapped key as big endian u16,
+// wrapped key, symmetrically encrypted data
+const ENVELOPE_MAGIC: &[u8] = b"CXEV";
+const ENVELOPE_VERSION: u8 = 1;
+const ENVELOPE_HEADER_LENGTH: usize = 8;
+// keyring output layout: magic, format version, key identifier as big endian u32, output of encrypt or encrypt_envelope
+const KEYRING_MAGIC: &[u8] = b"CXKR";
+const KEYRING_VERSION: u8 = 1;
+const KEYRING_HEADER_LENGTH: usize = 9;
 
+/// Hash function used by OAEP padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OaepHash {
+    /// SHA-1, use only for interoperability with systems not supporting other hash functions
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
 
-    let boxed_passphrase = get_or_create_passphrase(passphrase_path.as_str());
-    if boxed_passphrase.is_err() {
-        return Err(boxed_passphrase.err().unwrap());
+impl OaepHash {
+    /// Returns length of the digest in bytes
+    pub fn length(&self) -> usize {
+        match self {
+            OaepHash::Sha1 => 20,
+            OaepHash::Sha256 => 32,
+            OaepHash::Sha384 => 48,
+            OaepHash::Sha512 => 64,
+        }
     }
-    let passphrase = boxed_passphrase.unwrap();
 
-
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".rsa_public_key");
-    let boxed_public_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_public_key_path.is_err() {
-        return Err(boxed_public_key_path.err().unwrap());
+    fn message_digest(&self) -> MessageDigest {
+        match self {
+            OaepHash::Sha1 => MessageDigest::sha1(),
+            OaepHash::Sha256 => MessageDigest::sha256(),
+            OaepHash::Sha384 => MessageDigest::sha384(),
+            OaepHash::Sha512 => MessageDigest::sha512(),
+        }
     }
-    let public_key_path = boxed_public_key_path.unwrap();
+}
 
+/// Padding scheme applied to the data before RSA encryption
+///
+/// Default is OAEP with SHA-256 used both as a label hash and MGF1 hash and empty label.
+///
+/// Data encrypted by earlier versions used PKCS#1 v1.5 padding. To read it set `padding` of the
+/// DecryptionParameters to [RsaPadding::Pkcs1v15Legacy], decrypt the data and encrypt it again with default padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RsaPadding {
+    /// Optimal asymmetric encryption padding as defined in RFC 8017
+    Oaep {
+        hash: OaepHash,
+        mgf1_hash: OaepHash,
+        label: Vec<u8>,
+    },
+    /// PKCS#1 v1.5 padding, vulnerable to Bleichenbacher padding oracle attacks
+    #[deprecated(note = "PKCS#1 v1.5 padding is vulnerable to padding oracle attacks, use it only to read legacy data or talk to legacy systems and migrate to RsaPadding::default()")]
+    Pkcs1v15Legacy,
+}
 
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".rsa_private_key");
-    let boxed_private_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_private_key_path.is_err() {
-        return Err(boxed_private_key_path.err().unwrap());
+impl Default for RsaPadding {
+    fn default() -> Self {
+        RsaPadding::Oaep {
+            hash: OaepHash::Sha256,
+            mgf1_hash: OaepHash::Sha256,
+            label: vec![],
+        }
     }
-    let private_key_path = boxed_private_key_path.unwrap();
-
+}
 
-    let boxed_keys = get_or_create_private_public_keys(passphrase.as_str(), public_key_path.as_str(), private_key_path.as_str());
-    if boxed_keys.is_err() {
-        return Err(boxed_keys.err().unwrap());
+impl RsaPadding {
+    /// Returns maximum length in bytes of the data that can be encrypted at once with the 4096 bits long RSA key
+    #[allow(deprecated)]
+    pub fn max_data_length(&self) -> usize {
+        let key_length = (RSA_SIZE / 8) as usize;
+        match self {
+            RsaPadding::Oaep { hash, .. } => key_length - 2 * hash.length() - 2,
+            RsaPadding::Pkcs1v15Legacy => key_length - 11,
+        }
     }
+}
 
-    let (private_key, public_key) = boxed_keys.unwrap();
-
-    let encryption_params = EncryptionParameters {
-        rsa_public_key_pem: public_key,
-    };
-
-    let decryption_params = DecryptionParameters {
-        rsa_passphrase: passphrase,
-        rsa_private_key_pem: private_key
-    };
+/// EncryptionParameters is basically the RSA .pem encoded public key and padding scheme
+///
+#[derive(Debug, Clone)]
+pub struct EncryptionParameters {
+    pub rsa_public_key_pem: String,
+    pub padding: RsaPadding,
+}
 
-    Ok((encryption_params, decryption_params))
+/// DecryptionParameters is basically the RSA .pem encoded private key, passphrase and padding scheme,
+/// private key and passphrase are wiped from memory on drop
+///
+#[derive(Debug, Clone)]
+pub struct DecryptionParameters {
+    pub rsa_passphrase: Secret<String>,
+    pub rsa_private_key_pem: Secret<String>,
+    pub padding: RsaPadding,
 }
 
-/// Returns EncryptionParameters stored at the given location which is relative to the working directory
+/// Will read or create EncryptionParameters and DecryptionParameters at the given location which is relative to the working directory
 ///
-pub fn get_encryption_params(path_to_encryption_parameters: Option<&str>) -> Result<EncryptionParameters, String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".rsa_public_key");
-    let boxed_public_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_public_key_path.is_err() {
-        return Err(boxed_public_key_path.err().unwrap());
+pub fn setup(path_to_encryption_parameters: Option<&str>) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
     }
-    let public_key_path = boxed_public_key_path.unwrap();
+    setup_with_store(&boxed_store.unwrap())
+}
 
+/// Will read or create EncryptionParameters and DecryptionParameters in the given key store
+///
+pub fn setup_with_store(store: &dyn KeyStore) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    setup_key_with_store(store, &KeyNames::of(1))
+}
 
-    let boxed_public_key = read_file(public_key_path.as_str());
-    if boxed_public_key.is_err() {
-        let message = boxed_public_key.err().unwrap();
-        return Err(message)
+/// Returns EncryptionParameters stored at the given location which is relative to the working directory
+///
+pub fn get_encryption_params(path_to_encryption_parameters: Option<&str>) -> Result<EncryptionParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
     }
-    let boxed_public_key = String::from_utf8(boxed_public_key.unwrap());
-    let public_key = boxed_public_key.unwrap();
-
-    let encryption_params = EncryptionParameters {
-        rsa_public_key_pem: public_key,
-    };
+    get_encryption_params_with_store(&boxed_store.unwrap())
+}
 
-    Ok(encryption_params)
+/// Returns EncryptionParameters stored in the given key store
+///
+pub fn get_encryption_params_with_store(store: &dyn KeyStore) -> Result<EncryptionParameters, Error> {
+    read_encryption_params(store, &KeyNames::of(1))
 }
 
 /// Returns DecryptionParameters stored at the given location which is relative to the working directory
 ///
-pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Result<DecryptionParameters, String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".rsa_passphrase");
-    let boxed_passphrase_path = get_static_filepath(relative_path.as_str());
-    if boxed_passphrase_path.is_err() {
-        return Err(boxed_passphrase_path.err().unwrap());
-    }
-    let passphrase_path = boxed_passphrase_path.unwrap();
-
-
-    let boxed_passphrase = get_or_create_passphrase(passphrase_path.as_str());
-    if boxed_passphrase.is_err() {
-        return Err(boxed_passphrase.err().unwrap());
+pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Result<DecryptionParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
     }
-    let passphrase = boxed_passphrase.unwrap();
-
-
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".rsa_private_key");
-    let boxed_private_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_private_key_path.is_err() {
-        return Err(boxed_private_key_path.err().unwrap());
-    }
-    let private_key_path = boxed_private_key_path.unwrap();
-
-    let boxed_private_key = read_file(private_key_path.as_str());
-    if boxed_private_key.is_err() {
-        let message = boxed_private_key.err().unwrap();
-        return Err(message)
-    }
-    let boxed_private_key = String::from_utf8(boxed_private_key.unwrap());
-    let private_key = boxed_private_key.unwrap();
-
-    let decryption_params = DecryptionParameters {
-        rsa_passphrase: passphrase,
-        rsa_private_key_pem: private_key
-    };
+    get_decryption_params_with_store(&boxed_store.unwrap())
+}
 
-    Ok(decryption_params)
+/// Returns DecryptionParameters stored in the given key store
+///
+pub fn get_decryption_params_with_store(store: &dyn KeyStore) -> Result<DecryptionParameters, Error> {
+    read_decryption_params(store, &KeyNames::of(1))
 }
 
-/// Encrypts given byte array of maximum length up to 501 bytes
+/// Encrypts given byte array, maximum length depends on the padding: 446 bytes for default OAEP with SHA-256, see [RsaPadding::max_data_length]
 ///
 /// # Examples
 ///
@@ -151,7 +179,7 @@ pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Res
 ///
 ///    #[test]
 ///    fn encryption() {
-///        //maximum 501 bytes at once to be encrypted
+///        //maximum 446 bytes at once to be encrypted
 ///        let data_to_encrypt_as_bytes = "Some data to encrypt".as_bytes();
 ///
 ///        // path needs to be accessible by user with write permission for initial setup
@@ -169,26 +197,20 @@ pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Res
 ///        assert_eq!(data_to_encrypt_as_bytes, decrypted_bytes);
 ///    }
 /// ```
-pub fn encrypt(params: EncryptionParameters, data: &[u8]) -> Result<Vec<u8>, String> {
-    let boxed_rsa = Rsa::public_key_from_pem(params.rsa_public_key_pem.as_bytes());
-    if boxed_rsa.is_err() {
-        let message = boxed_rsa.err().unwrap().to_string();
-        return Err(message)
+pub fn encrypt(params: EncryptionParameters, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_encryptor = Encryptor::new(&params);
+    if boxed_encryptor.is_err() {
+        return Err(boxed_encryptor.err().unwrap());
     }
-    let rsa = boxed_rsa.unwrap();
-    let mut buffer : Vec<u8> = vec![0; rsa.size() as usize];
-    let boxed_encrypt = rsa.public_encrypt(data, &mut buffer, Padding::PKCS1);
-    if boxed_encrypt.is_err() {
-        let message = boxed_encrypt.err().unwrap().to_string();
-        return Err(message)
-    }
-    let _ = boxed_encrypt.unwrap();
-    Ok(buffer)
+    boxed_encryptor.unwrap().encrypt(data)
 }
 
 
 /// Decrypts given byte array
 ///
+/// Data encrypted by earlier versions of the crate used PKCS#1 v1.5 padding and can be decrypted by setting
+/// `padding` to [RsaPadding::Pkcs1v15Legacy]
+///
 /// # Examples
 ///
 /// ```
@@ -210,34 +232,856 @@ pub fn encrypt(params: EncryptionParameters, data: &[u8]) -> Result<Vec<u8>, Str
 ///        assert_eq!(data_to_encrypt_as_bytes, decrypted_bytes);
 ///    }
 /// ```
-pub fn decrypt(params: DecryptionParameters, data: &[u8]) -> Result<Vec<u8>, String> {
-    let boxed_rsa = Rsa::private_key_from_pem_passphrase(params.rsa_private_key_pem.as_bytes(), params.rsa_passphrase.as_bytes());
-    if boxed_rsa.is_err() {
-        let message = boxed_rsa.err().unwrap().to_string();
-        return Err(message)
+pub fn decrypt(params: DecryptionParameters, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_decryptor = Decryptor::new(&params);
+    if boxed_decryptor.is_err() {
+        return Err(boxed_decryptor.err().unwrap());
     }
-    let rsa = boxed_rsa.unwrap();
-    let mut buffer: Vec<u8> = vec![0; rsa.size() as usize];
-    let boxed_decrypt = rsa.private_decrypt(data, &mut buffer, Padding::PKCS1);
-    if boxed_decrypt.is_err() {
-        let message = boxed_decrypt.err().unwrap().to_string();
-        return Err(message)
+    boxed_decryptor.unwrap().decrypt(data)
+}
+
+
+/// Encrypts given byte array of any length into the envelope
+///
+/// Random data key is generated for each call and used to encrypt the data with AES-256-GCM, data key itself
+/// is encrypted with RSA public key. Envelope records algorithm and wrapped data key, so it can be
+/// decrypted with [decrypt_envelope] given only DecryptionParameters.
+///
+/// # Examples
+///
+/// ```
+///    use crypto_ext::asymmetric::encryption::{encrypt_envelope, decrypt_envelope, setup};
+///
+///    #[test]
+///    fn envelope_encryption() {
+///        let data = vec![7; 1024 * 1024];
+///        let associated_data = "some unencrypted data that needs to be sent along the envelope".as_bytes();
+///
+///        // path needs to be accessible by user with write permission for initial setup
+///        let params_path = "/test/encryption_parameters/";
+///        let (encryption_params, decryption_params) = setup(Some(params_path)).unwrap();
+///
+///        let envelope = encrypt_envelope(encryption_params, data.as_slice(), associated_data).unwrap();
+///        let decrypted = decrypt_envelope(decryption_params, envelope.as_slice(), associated_data).unwrap();
+///
+///        assert_eq!(data, decrypted);
+///    }
+/// ```
+pub fn encrypt_envelope(params: EncryptionParameters, data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_encryptor = Encryptor::new(&params);
+    if boxed_encryptor.is_err() {
+        return Err(boxed_encryptor.err().unwrap());
+    }
+    boxed_encryptor.unwrap().encrypt_envelope(data, associated_data)
+}
+
+/// Decrypts envelope created by [encrypt_envelope]
+///
+/// # Examples
+///
+/// ```
+///    use crypto_ext::asymmetric::encryption::{encrypt_envelope, decrypt_envelope, get_encryption_params, get_decryption_params, setup};
+///
+///    #[test]
+///    fn envelope_decryption() {
+///        let data = vec![7; 1024 * 1024];
+///        let associated_data = "some unencrypted data that needs to be sent along the envelope".as_bytes();
+///
+///        // path needs to be accessible by user with write permission for initial setup
+///        let params_path = "/test/encryption_parameters/";
+///        let _ = setup(Some(params_path)).unwrap();
+///
+///        let encryption_params = get_encryption_params(Some(params_path)).unwrap();
+///        let envelope = encrypt_envelope(encryption_params, data.as_slice(), associated_data).unwrap();
+///
+///        let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+///        let decrypted = decrypt_envelope(decryption_params, envelope.as_slice(), associated_data).unwrap();
+///
+///        assert_eq!(data, decrypted);
+///    }
+/// ```
+pub fn decrypt_envelope(params: DecryptionParameters, envelope: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_decryptor = Decryptor::new(&params);
+    if boxed_decryptor.is_err() {
+        return Err(boxed_decryptor.err().unwrap());
+    }
+    boxed_decryptor.unwrap().decrypt_envelope(envelope, associated_data)
+}
+
+/// Encryptor keeps parsed RSA public key, so it can encrypt any number of messages or envelopes
+///
+/// It is `Send` and `Sync`, single instance can be shared between threads
+///
+/// # Examples
+///
+/// ```
+///    use crypto_ext::asymmetric::encryption::{setup_with_store, Decryptor, Encryptor};
+///    use crypto_ext::key_store::MemoryKeyStore;
+///
+///    #[test]
+///    fn encryptor() {
+///        let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new()).unwrap();
+///
+///        // private key is decrypted with the passphrase once, not on every call
+///        let encryptor = Encryptor::new(&encryption_params).unwrap();
+///        let decryptor = Decryptor::new(&decryption_params).unwrap();
+///
+///        for message in ["first message", "second message"] {
+///            let encrypted = encryptor.encrypt(message.as_bytes()).unwrap();
+///            assert_eq!(decryptor.decrypt(encrypted.as_slice()).unwrap(), message.as_bytes());
+///
+///            let envelope = encryptor.encrypt_envelope(message.as_bytes(), &[]).unwrap();
+///            assert_eq!(decryptor.decrypt_envelope(envelope.as_slice(), &[]).unwrap(), message.as_bytes());
+///        }
+///    }
+/// ```
+pub struct Encryptor {
+    key: PKey<Public>,
+    padding: RsaPadding,
+}
+
+impl Encryptor {
+    /// Parses RSA public key of the given parameters
+    pub fn new(params: &EncryptionParameters) -> Result<Encryptor, Error> {
+        let boxed_rsa = Rsa::public_key_from_pem(params.rsa_public_key_pem.as_bytes());
+        if boxed_rsa.is_err() {
+            return Err(Error::format_from("unable to parse RSA public key", boxed_rsa.err().unwrap()))
+        }
+        let rsa = boxed_rsa.unwrap();
+
+        let boxed_key = PKey::from_rsa(rsa);
+        if boxed_key.is_err() {
+            return Err(Error::crypto_from("unable to load RSA public key", boxed_key.err().unwrap()))
+        }
+
+        Ok(Encryptor { key: boxed_key.unwrap(), padding: params.padding.clone() })
     }
-    let _ = boxed_decrypt.unwrap();
 
-    let as_string = String::from_utf8(buffer).expect("Found invalid UTF-8");
+    /// Encrypts given byte array the same way as [encrypt]
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_encrypter = Encrypter::new(&self.key);
+        if boxed_encrypter.is_err() {
+            return Err(Error::crypto_from("unable to initialize RSA encryption", boxed_encrypter.err().unwrap()))
+        }
+        let mut encrypter = boxed_encrypter.unwrap();
+
+        let boxed_padding = configure_encrypter(&mut encrypter, &self.padding);
+        if boxed_padding.is_err() {
+            return Err(Error::unsupported_algorithm_from("unable to set RSA padding", boxed_padding.err().unwrap()))
+        }
+
+        let boxed_length = encrypter.encrypt_len(data);
+        if boxed_length.is_err() {
+            return Err(Error::crypto_from("unable to calculate length of the encrypted data", boxed_length.err().unwrap()))
+        }
+        let mut buffer : Vec<u8> = vec![0; boxed_length.unwrap()];
+
+        let boxed_encrypt = encrypter.encrypt(data, &mut buffer);
+        if boxed_encrypt.is_err() {
+            return Err(Error::crypto_from("unable to encrypt data, it may exceed maximum length allowed by the padding", boxed_encrypt.err().unwrap()))
+        }
+        let encrypted_length = boxed_encrypt.unwrap();
+        buffer.truncate(encrypted_length);
+
+        Ok(buffer)
+    }
+
+    /// Encrypts given byte array into the envelope the same way as [encrypt_envelope]
+    pub fn encrypt_envelope(&self, data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let algorithm = symmetric::encryption::Algorithm::default();
+
+        let boxed_data_key = generate_bytes(algorithm.key_length());
+        if boxed_data_key.is_err() {
+            return Err(boxed_data_key.err().unwrap());
+        }
+        let data_key = Secret::new(boxed_data_key.unwrap());
+
+        let boxed_wrapped_key = self.encrypt(data_key.as_slice());
+        if boxed_wrapped_key.is_err() {
+            return Err(boxed_wrapped_key.err().unwrap());
+        }
+        let wrapped_key = boxed_wrapped_key.unwrap();
 
-    let as_filtered_string = as_string.trim_end_matches(char::from(0));
+        let mut envelope = Vec::with_capacity(ENVELOPE_HEADER_LENGTH + wrapped_key.len() + algorithm.nonce_length() + data.len() + algorithm.tag_length());
+        envelope.extend_from_slice(ENVELOPE_MAGIC);
+        envelope.push(ENVELOPE_VERSION);
+        envelope.push(algorithm.id());
+        envelope.extend_from_slice(&(wrapped_key.len() as u16).to_be_bytes());
+        envelope.extend_from_slice(wrapped_key.as_slice());
 
-    let as_vector = as_filtered_string.as_bytes().to_vec();
+        // header is authenticated along the associated data, so neither algorithm nor wrapped key can be swapped
+        let envelope_associated_data = [envelope.as_slice(), associated_data].concat();
 
-    Ok(as_vector)
+        let symmetric_params = symmetric::encryption::EncryptionParameters { algorithm, key: data_key };
+        let boxed_encrypted_data = symmetric::encryption::encrypt(symmetric_params, data, envelope_associated_data.as_slice());
+        if boxed_encrypted_data.is_err() {
+            return Err(boxed_encrypted_data.err().unwrap());
+        }
+        let encrypted_data = boxed_encrypted_data.unwrap();
+
+        envelope.extend_from_slice(encrypted_data.as_slice());
+        Ok(envelope)
+    }
+}
+
+/// Decryptor keeps RSA private key decrypted with the passphrase, so it can decrypt any number of messages or envelopes
+///
+/// It is `Send` and `Sync`, single instance can be shared between threads
+pub struct Decryptor {
+    key: PKey<Private>,
+    padding: RsaPadding,
+}
+
+impl Decryptor {
+    /// Decrypts RSA private key of the given parameters with the passphrase
+    pub fn new(params: &DecryptionParameters) -> Result<Decryptor, Error> {
+        let boxed_rsa = Rsa::private_key_from_pem_passphrase(params.rsa_private_key_pem.as_bytes(), params.rsa_passphrase.as_bytes());
+        if boxed_rsa.is_err() {
+            return Err(Error::key_mismatch_from("unable to load RSA private key, passphrase is wrong or key is malformed", boxed_rsa.err().unwrap()))
+        }
+        let rsa = boxed_rsa.unwrap();
+
+        let boxed_key = PKey::from_rsa(rsa);
+        if boxed_key.is_err() {
+            return Err(Error::crypto_from("unable to load RSA private key", boxed_key.err().unwrap()))
+        }
+
+        Ok(Decryptor { key: boxed_key.unwrap(), padding: params.padding.clone() })
+    }
+
+    /// Decrypts given byte array the same way as [decrypt]
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_decrypter = Decrypter::new(&self.key);
+        if boxed_decrypter.is_err() {
+            return Err(Error::crypto_from("unable to initialize RSA decryption", boxed_decrypter.err().unwrap()))
+        }
+        let mut decrypter = boxed_decrypter.unwrap();
+
+        let boxed_padding = configure_decrypter(&mut decrypter, &self.padding);
+        if boxed_padding.is_err() {
+            return Err(Error::unsupported_algorithm_from("unable to set RSA padding", boxed_padding.err().unwrap()))
+        }
+
+        let boxed_length = decrypter.decrypt_len(data);
+        if boxed_length.is_err() {
+            return Err(Error::crypto_from("unable to calculate length of the decrypted data", boxed_length.err().unwrap()))
+        }
+        let mut buffer: Vec<u8> = vec![0; boxed_length.unwrap()];
+
+        let boxed_decrypt = decrypter.decrypt(data, &mut buffer);
+        if boxed_decrypt.is_err() {
+            buffer.zeroize();
+            return Err(Error::authentication_from("unable to decrypt data, wrong key, padding or tampered data", boxed_decrypt.err().unwrap()))
+        }
+        let decrypted_length = boxed_decrypt.unwrap();
+        buffer.truncate(decrypted_length);
+
+        Ok(buffer)
+    }
+
+    /// Decrypts envelope created by [encrypt_envelope] or [Encryptor::encrypt_envelope]
+    pub fn decrypt_envelope(&self, envelope: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        if envelope.len() < ENVELOPE_HEADER_LENGTH || !envelope.starts_with(ENVELOPE_MAGIC) {
+            return Err(Error::format("unrecognized envelope format"))
+        }
+
+        let version = envelope[ENVELOPE_MAGIC.len()];
+        if version != ENVELOPE_VERSION {
+            let message = format!("unsupported envelope version: {}", version);
+            return Err(Error::format(message))
+        }
+
+        let boxed_algorithm = symmetric::encryption::Algorithm::from_id(envelope[ENVELOPE_MAGIC.len() + 1]);
+        if boxed_algorithm.is_err() {
+            return Err(boxed_algorithm.err().unwrap());
+        }
+        let algorithm = boxed_algorithm.unwrap();
+
+        let wrapped_key_length = u16::from_be_bytes([envelope[6], envelope[7]]) as usize;
+        let header_length = ENVELOPE_HEADER_LENGTH + wrapped_key_length;
+        if envelope.len() < header_length {
+            let message = format!("envelope is too short: {} bytes, wrapped key alone takes {} bytes", envelope.len(), wrapped_key_length);
+            return Err(Error::format(message))
+        }
+        let (header, encrypted_data) = envelope.split_at(header_length);
+        let wrapped_key = &header[ENVELOPE_HEADER_LENGTH..];
+
+        let boxed_data_key = self.decrypt(wrapped_key);
+        if boxed_data_key.is_err() {
+            return Err(boxed_data_key.err().unwrap());
+        }
+        // data key is wiped once symmetric parameters holding it are dropped
+        let data_key = Secret::new(boxed_data_key.unwrap());
+
+        let envelope_associated_data = [header, associated_data].concat();
+
+        let symmetric_params = symmetric::encryption::DecryptionParameters { algorithm, key: data_key };
+        symmetric::encryption::decrypt(symmetric_params, encrypted_data, envelope_associated_data.as_slice())
+    }
+}
+
+/// EncryptionKeyring holds versioned RSA public keys identified by key ID, data is encrypted with the primary one
+///
+/// Output is tagged with the key ID, so [DecryptionKeyring] picks the matching private key.
+/// Key ID of the plain RSA output is not authenticated, data tagged with a wrong key ID fails to decrypt,
+/// envelopes authenticate the key ID along the associated data
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::asymmetric::encryption::{reencrypt_envelope, rotate_keyring, setup_keyring, DecryptionKeyring};
+///
+///     #[test]
+///     fn rsa_key_rotation() {
+///         // path needs to be accessible by user with write permission for initial setup
+///         let params_path = "/test/rsa_keyring_parameters/";
+///         let (encryption_keyring, _) = setup_keyring(Some(params_path)).unwrap();
+///         let envelope = encryption_keyring.encrypt_envelope("some data".as_bytes(), &[]).unwrap();
+///
+///         // new primary key pair is created, previous key pairs stay in the keyrings
+///         let (encryption_keyring, decryption_keyring) = rotate_keyring(Some(params_path)).unwrap();
+///         assert_eq!(decryption_keyring.decrypt_envelope(envelope.as_slice(), &[]).unwrap(), "some data".as_bytes());
+///
+///         // envelope is migrated to the primary key pair
+///         let reencrypted = reencrypt_envelope(&decryption_keyring, &encryption_keyring, envelope.as_slice(), &[]).unwrap();
+///         assert_eq!(DecryptionKeyring::key_id_of(reencrypted.as_slice()).unwrap(), encryption_keyring.primary_key_id());
+///     }
+/// ```
+pub struct EncryptionKeyring {
+    primary_key_id: u32,
+    keys: BTreeMap<u32, Encryptor>,
+}
+
+impl EncryptionKeyring {
+    /// Creates keyring holding single public key, which is primary
+    pub fn new(key_id: u32, params: &EncryptionParameters) -> Result<EncryptionKeyring, Error> {
+        let boxed_encryptor = Encryptor::new(params);
+        if boxed_encryptor.is_err() {
+            return Err(boxed_encryptor.err().unwrap());
+        }
+
+        let mut keys = BTreeMap::new();
+        keys.insert(key_id, boxed_encryptor.unwrap());
+        Ok(EncryptionKeyring { primary_key_id: key_id, keys })
+    }
+
+    /// Returns key ID of the primary key, data is encrypted with it
+    pub fn primary_key_id(&self) -> u32 {
+        self.primary_key_id
+    }
+
+    /// Returns key IDs of all keys in ascending order
+    pub fn key_ids(&self) -> Vec<u32> {
+        self.keys.keys().copied().collect()
+    }
+
+    /// Adds public key under the given key ID, key ID can not be reused
+    pub fn add_key(&mut self, key_id: u32, params: &EncryptionParameters) -> Result<(), Error> {
+        if self.keys.contains_key(&key_id) {
+            let message = format!("keyring already holds key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+
+        let boxed_encryptor = Encryptor::new(params);
+        if boxed_encryptor.is_err() {
+            return Err(boxed_encryptor.err().unwrap());
+        }
+
+        self.keys.insert(key_id, boxed_encryptor.unwrap());
+        Ok(())
+    }
+
+    /// Makes the key with given key ID primary
+    pub fn set_primary(&mut self, key_id: u32) -> Result<(), Error> {
+        if !self.keys.contains_key(&key_id) {
+            let message = format!("keyring does not hold key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+
+        self.primary_key_id = key_id;
+        Ok(())
+    }
+
+    /// Removes key with the given key ID. Primary key can not be removed
+    pub fn remove_key(&mut self, key_id: u32) -> Result<(), Error> {
+        if key_id == self.primary_key_id {
+            let message = format!("key with ID {} is primary and can not be removed", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+
+        if self.keys.remove(&key_id).is_none() {
+            let message = format!("keyring does not hold key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+        Ok(())
+    }
+
+    /// Encrypts given byte array with the primary key the same way as [encrypt], output starts with the key ID
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut encrypted_data = encode_keyring_header(self.primary_key_id);
+
+        let boxed_encrypted_data = self.keys[&self.primary_key_id].encrypt(data);
+        if boxed_encrypted_data.is_err() {
+            return Err(boxed_encrypted_data.err().unwrap());
+        }
+
+        encrypted_data.extend_from_slice(boxed_encrypted_data.unwrap().as_slice());
+        Ok(encrypted_data)
+    }
+
+    /// Encrypts given byte array into the envelope with the primary key the same way as [encrypt_envelope], output starts with the key ID
+    pub fn encrypt_envelope(&self, data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut envelope = encode_keyring_header(self.primary_key_id);
+
+        // key ID is authenticated along the associated data
+        let keyring_associated_data = [envelope.as_slice(), associated_data].concat();
+
+        let boxed_envelope = self.keys[&self.primary_key_id].encrypt_envelope(data, keyring_associated_data.as_slice());
+        if boxed_envelope.is_err() {
+            return Err(boxed_envelope.err().unwrap());
+        }
+
+        envelope.extend_from_slice(boxed_envelope.unwrap().as_slice());
+        Ok(envelope)
+    }
+}
+
+/// DecryptionKeyring holds versioned RSA private keys identified by key ID, data is decrypted with the key it is tagged with
+pub struct DecryptionKeyring {
+    keys: BTreeMap<u32, Decryptor>,
+}
+
+impl DecryptionKeyring {
+    /// Creates keyring holding single private key
+    pub fn new(key_id: u32, params: &DecryptionParameters) -> Result<DecryptionKeyring, Error> {
+        let boxed_decryptor = Decryptor::new(params);
+        if boxed_decryptor.is_err() {
+            return Err(boxed_decryptor.err().unwrap());
+        }
+
+        let mut keys = BTreeMap::new();
+        keys.insert(key_id, boxed_decryptor.unwrap());
+        Ok(DecryptionKeyring { keys })
+    }
+
+    /// Returns key IDs of all keys in ascending order
+    pub fn key_ids(&self) -> Vec<u32> {
+        self.keys.keys().copied().collect()
+    }
+
+    /// Adds private key under the given key ID, key ID can not be reused
+    pub fn add_key(&mut self, key_id: u32, params: &DecryptionParameters) -> Result<(), Error> {
+        if self.keys.contains_key(&key_id) {
+            let message = format!("keyring already holds key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+
+        let boxed_decryptor = Decryptor::new(params);
+        if boxed_decryptor.is_err() {
+            return Err(boxed_decryptor.err().unwrap());
+        }
+
+        self.keys.insert(key_id, boxed_decryptor.unwrap());
+        Ok(())
+    }
+
+    /// Removes key with the given key ID, data encrypted with it can not be decrypted anymore
+    pub fn remove_key(&mut self, key_id: u32) -> Result<(), Error> {
+        if self.keys.remove(&key_id).is_none() {
+            let message = format!("keyring does not hold key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+        Ok(())
+    }
+
+    /// Returns key ID the encrypted data or envelope is tagged with, for example to find data not yet migrated to the primary key
+    pub fn key_id_of(encrypted_data: &[u8]) -> Result<u32, Error> {
+        if encrypted_data.len() < KEYRING_HEADER_LENGTH || !encrypted_data.starts_with(KEYRING_MAGIC) {
+            return Err(Error::format("encrypted data is not tagged with key ID"))
+        }
+
+        let version = encrypted_data[KEYRING_MAGIC.len()];
+        if version != KEYRING_VERSION {
+            let message = format!("unsupported keyring format version: {}", version);
+            return Err(Error::format(message))
+        }
+
+        let key_id = [encrypted_data[5], encrypted_data[6], encrypted_data[7], encrypted_data[8]];
+        Ok(u32::from_be_bytes(key_id))
+    }
+
+    /// Decrypts given byte array with the private key it is tagged with
+    pub fn decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_decryptor = self.decryptor_of(encrypted_data);
+        if boxed_decryptor.is_err() {
+            return Err(boxed_decryptor.err().unwrap());
+        }
+
+        boxed_decryptor.unwrap().decrypt(&encrypted_data[KEYRING_HEADER_LENGTH..])
+    }
+
+    /// Decrypts envelope with the private key it is tagged with
+    pub fn decrypt_envelope(&self, envelope: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_decryptor = self.decryptor_of(envelope);
+        if boxed_decryptor.is_err() {
+            return Err(boxed_decryptor.err().unwrap());
+        }
+
+        let (header, envelope) = envelope.split_at(KEYRING_HEADER_LENGTH);
+        let keyring_associated_data = [header, associated_data].concat();
+
+        boxed_decryptor.unwrap().decrypt_envelope(envelope, keyring_associated_data.as_slice())
+    }
+
+    fn decryptor_of(&self, encrypted_data: &[u8]) -> Result<&Decryptor, Error> {
+        let boxed_key_id = DecryptionKeyring::key_id_of(encrypted_data);
+        if boxed_key_id.is_err() {
+            return Err(boxed_key_id.err().unwrap());
+        }
+        let key_id = boxed_key_id.unwrap();
+
+        let boxed_decryptor = self.keys.get(&key_id);
+        if boxed_decryptor.is_none() {
+            let message = format!("data is encrypted with key ID {}, which is not in the keyring", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+        Ok(boxed_decryptor.unwrap())
+    }
+}
+
+/// Decrypts given byte array tagged with the key ID and encrypts it again with the primary key
+pub fn reencrypt(decryption_keyring: &DecryptionKeyring, encryption_keyring: &EncryptionKeyring, encrypted_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_data = decryption_keyring.decrypt(encrypted_data);
+    if boxed_data.is_err() {
+        return Err(boxed_data.err().unwrap());
+    }
+    let data = Secret::new(boxed_data.unwrap());
+
+    encryption_keyring.encrypt(data.as_slice())
+}
+
+/// Decrypts given envelope tagged with the key ID and encrypts it again into the envelope with the primary key
+pub fn reencrypt_envelope(decryption_keyring: &DecryptionKeyring, encryption_keyring: &EncryptionKeyring, envelope: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_data = decryption_keyring.decrypt_envelope(envelope, associated_data);
+    if boxed_data.is_err() {
+        return Err(boxed_data.err().unwrap());
+    }
+    let data = Secret::new(boxed_data.unwrap());
+
+    encryption_keyring.encrypt_envelope(data.as_slice(), associated_data)
+}
+
+/// Will read or create EncryptionKeyring and DecryptionKeyring at the given location which is relative to the working directory
+///
+/// Key pair with ID 1 is the key pair created by [setup], key pairs created by rotation are stored next to it
+/// with the key ID as a suffix. The newest key pair is primary
+pub fn setup_keyring(path_to_encryption_parameters: Option<&str>) -> Result<(EncryptionKeyring, DecryptionKeyring), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    setup_keyring_with_store(&boxed_store.unwrap())
+}
+
+/// Will read or create EncryptionKeyring and DecryptionKeyring in the given key store
+pub fn setup_keyring_with_store(store: &dyn KeyStore) -> Result<(EncryptionKeyring, DecryptionKeyring), Error> {
+    let boxed_params = setup_key_with_store(store, &KeyNames::of(1));
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
+    }
+    get_keyrings_with_store(store)
+}
+
+/// Returns EncryptionKeyring stored at the given location which is relative to the working directory
+pub fn get_encryption_keyring(path_to_encryption_parameters: Option<&str>) -> Result<EncryptionKeyring, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_encryption_keyring_with_store(&boxed_store.unwrap())
+}
+
+/// Returns EncryptionKeyring stored in the given key store, the newest key is primary
+pub fn get_encryption_keyring_with_store(store: &dyn KeyStore) -> Result<EncryptionKeyring, Error> {
+    let boxed_params = read_encryption_params(store, &KeyNames::of(1));
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
+    }
+
+    let boxed_keyring = EncryptionKeyring::new(1, &boxed_params.unwrap());
+    if boxed_keyring.is_err() {
+        return Err(boxed_keyring.err().unwrap());
+    }
+    let mut keyring = boxed_keyring.unwrap();
+
+    let mut key_id: u32 = 2;
+    loop {
+        let names = KeyNames::of(key_id);
+        let boxed_exists = store.contains(names.public_key.as_str());
+        if boxed_exists.is_err() {
+            return Err(boxed_exists.err().unwrap());
+        }
+        if !boxed_exists.unwrap() {
+            break;
+        }
+
+        let boxed_params = read_encryption_params(store, &names);
+        if boxed_params.is_err() {
+            return Err(boxed_params.err().unwrap());
+        }
+
+        let boxed_add = keyring.add_key(key_id, &boxed_params.unwrap());
+        if boxed_add.is_err() {
+            return Err(boxed_add.err().unwrap());
+        }
+        keyring.primary_key_id = key_id;
+        key_id += 1;
+    }
+
+    Ok(keyring)
+}
+
+/// Returns DecryptionKeyring stored at the given location which is relative to the working directory
+pub fn get_decryption_keyring(path_to_encryption_parameters: Option<&str>) -> Result<DecryptionKeyring, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_decryption_keyring_with_store(&boxed_store.unwrap())
+}
+
+/// Returns DecryptionKeyring stored in the given key store
+pub fn get_decryption_keyring_with_store(store: &dyn KeyStore) -> Result<DecryptionKeyring, Error> {
+    let boxed_params = read_decryption_params(store, &KeyNames::of(1));
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
+    }
+
+    let boxed_keyring = DecryptionKeyring::new(1, &boxed_params.unwrap());
+    if boxed_keyring.is_err() {
+        return Err(boxed_keyring.err().unwrap());
+    }
+    let mut keyring = boxed_keyring.unwrap();
+
+    let mut key_id: u32 = 2;
+    loop {
+        let names = KeyNames::of(key_id);
+        let boxed_exists = store.contains(names.private_key.as_str());
+        if boxed_exists.is_err() {
+            return Err(boxed_exists.err().unwrap());
+        }
+        if !boxed_exists.unwrap() {
+            break;
+        }
+
+        let boxed_params = read_decryption_params(store, &names);
+        if boxed_params.is_err() {
+            return Err(boxed_params.err().unwrap());
+        }
+
+        let boxed_add = keyring.add_key(key_id, &boxed_params.unwrap());
+        if boxed_add.is_err() {
+            return Err(boxed_add.err().unwrap());
+        }
+        key_id += 1;
+    }
+
+    Ok(keyring)
+}
+
+/// Creates new primary key pair in the keyrings stored at the given location which is relative to the working directory
+pub fn rotate_keyring(path_to_encryption_parameters: Option<&str>) -> Result<(EncryptionKeyring, DecryptionKeyring), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    rotate_keyring_with_store(&boxed_store.unwrap())
+}
+
+/// Creates new primary key pair in the keyrings stored in the given key store
+///
+/// Concurrent rotations converge on the same new key pair
+pub fn rotate_keyring_with_store(store: &dyn KeyStore) -> Result<(EncryptionKeyring, DecryptionKeyring), Error> {
+    let boxed_keyring = get_encryption_keyring_with_store(store);
+    if boxed_keyring.is_err() {
+        return Err(boxed_keyring.err().unwrap());
+    }
+    let keyring = boxed_keyring.unwrap();
+
+    let boxed_key_id = keyring.primary_key_id.checked_add(1);
+    if boxed_key_id.is_none() {
+        return Err(Error::key_mismatch("keyring has no key ID left for a new key"))
+    }
+    let key_id = boxed_key_id.unwrap();
+
+    let boxed_params = setup_key_with_store(store, &KeyNames::of(key_id));
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
+    }
+
+    get_keyrings_with_store(store)
 }
 
 
 // below are functions not exposed as an api, used for inner implementation
 
-fn get_or_create_passphrase(path: &str) -> Result<String, String> {
+#[allow(deprecated)]
+fn configure_encrypter(encrypter: &mut Encrypter, padding: &RsaPadding) -> Result<(), openssl::error::ErrorStack> {
+    match padding {
+        RsaPadding::Oaep { hash, mgf1_hash, label } => {
+            encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+            encrypter.set_rsa_oaep_md(hash.message_digest())?;
+            encrypter.set_rsa_mgf1_md(mgf1_hash.message_digest())?;
+            if !label.is_empty() {
+                encrypter.set_rsa_oaep_label(label.as_slice())?;
+            }
+            Ok(())
+        }
+        RsaPadding::Pkcs1v15Legacy => encrypter.set_rsa_padding(Padding::PKCS1),
+    }
+}
+
+#[allow(deprecated)]
+fn configure_decrypter(decrypter: &mut Decrypter, padding: &RsaPadding) -> Result<(), openssl::error::ErrorStack> {
+    match padding {
+        RsaPadding::Oaep { hash, mgf1_hash, label } => {
+            decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+            decrypter.set_rsa_oaep_md(hash.message_digest())?;
+            decrypter.set_rsa_mgf1_md(mgf1_hash.message_digest())?;
+            if !label.is_empty() {
+                decrypter.set_rsa_oaep_label(label.as_slice())?;
+            }
+            Ok(())
+        }
+        RsaPadding::Pkcs1v15Legacy => decrypter.set_rsa_padding(Padding::PKCS1),
+    }
+}
+
+fn get_keyrings_with_store(store: &dyn KeyStore) -> Result<(EncryptionKeyring, DecryptionKeyring), Error> {
+    let boxed_encryption_keyring = get_encryption_keyring_with_store(store);
+    if boxed_encryption_keyring.is_err() {
+        return Err(boxed_encryption_keyring.err().unwrap());
+    }
+
+    let boxed_decryption_keyring = get_decryption_keyring_with_store(store);
+    if boxed_decryption_keyring.is_err() {
+        return Err(boxed_decryption_keyring.err().unwrap());
+    }
+
+    Ok((boxed_encryption_keyring.unwrap(), boxed_decryption_keyring.unwrap()))
+}
+
+fn encode_keyring_header(key_id: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(KEYRING_HEADER_LENGTH);
+    header.extend_from_slice(KEYRING_MAGIC);
+    header.push(KEYRING_VERSION);
+    header.extend_from_slice(&key_id.to_be_bytes());
+    header
+}
+
+// names of the values of the key with given key ID, key with ID 1 is the one created by setup
+// so existing key pair becomes the first key of the keyring
+struct KeyNames {
+    passphrase: String,
+    private_key: String,
+    public_key: String,
+}
+
+impl KeyNames {
+    fn of(key_id: u32) -> KeyNames {
+        if key_id == 1 {
+            return KeyNames {
+                passphrase: PASSPHRASE_NAME.to_string(),
+                private_key: PRIVATE_KEY_NAME.to_string(),
+                public_key: PUBLIC_KEY_NAME.to_string(),
+            };
+        }
+
+        KeyNames {
+            passphrase: format!("{}.{}", PASSPHRASE_NAME, key_id),
+            private_key: format!("{}.{}", PRIVATE_KEY_NAME, key_id),
+            public_key: format!("{}.{}", PUBLIC_KEY_NAME, key_id),
+        }
+    }
+}
+
+fn setup_key_with_store(store: &dyn KeyStore, names: &KeyNames) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    let boxed_passphrase = get_or_create_passphrase(store, names.passphrase.as_str());
+    if boxed_passphrase.is_err() {
+        return Err(boxed_passphrase.err().unwrap());
+    }
+    let passphrase = boxed_passphrase.unwrap();
+
+
+    let boxed_keys = get_or_create_private_public_keys(store, names, passphrase.as_str());
+    if boxed_keys.is_err() {
+        return Err(boxed_keys.err().unwrap());
+    }
+
+    let (private_key, public_key) = boxed_keys.unwrap();
+
+    let encryption_params = EncryptionParameters {
+        rsa_public_key_pem: public_key,
+        padding: RsaPadding::default(),
+    };
+
+    let decryption_params = DecryptionParameters {
+        rsa_passphrase: passphrase,
+        rsa_private_key_pem: private_key,
+        padding: RsaPadding::default(),
+    };
+
+    Ok((encryption_params, decryption_params))
+}
+
+fn read_encryption_params(store: &dyn KeyStore, names: &KeyNames) -> Result<EncryptionParameters, Error> {
+    let boxed_public_key = store.read(names.public_key.as_str());
+    if boxed_public_key.is_err() {
+        let message = boxed_public_key.err().unwrap();
+        return Err(message)
+    }
+    let boxed_public_key = String::from_utf8(boxed_public_key.unwrap());
+    if boxed_public_key.is_err() {
+        return Err(Error::format_from("RSA public key is not a valid UTF-8 PEM", boxed_public_key.err().unwrap()))
+    }
+    let public_key = boxed_public_key.unwrap();
+
+    let encryption_params = EncryptionParameters {
+        rsa_public_key_pem: public_key,
+        padding: RsaPadding::default(),
+    };
+
+    Ok(encryption_params)
+}
+
+fn read_decryption_params(store: &dyn KeyStore, names: &KeyNames) -> Result<DecryptionParameters, Error> {
+    let boxed_passphrase = get_or_create_passphrase(store, names.passphrase.as_str());
+    if boxed_passphrase.is_err() {
+        return Err(boxed_passphrase.err().unwrap());
+    }
+    let passphrase = boxed_passphrase.unwrap();
+
+    let boxed_private_key = store.read(names.private_key.as_str());
+    if boxed_private_key.is_err() {
+        let message = boxed_private_key.err().unwrap();
+        return Err(message)
+    }
+    let boxed_private_key = String::from_utf8(boxed_private_key.unwrap());
+    if boxed_private_key.is_err() {
+        return Err(Error::format_from("RSA private key is not a valid UTF-8 PEM", boxed_private_key.err().unwrap()))
+    }
+    let private_key = Secret::new(boxed_private_key.unwrap());
+
+    let decryption_params = DecryptionParameters {
+        rsa_passphrase: passphrase,
+        rsa_private_key_pem: private_key,
+        padding: RsaPadding::default(),
+    };
+
+    Ok(decryption_params)
+}
+
+fn get_or_create_passphrase(store: &dyn KeyStore, name: &str) -> Result<Secret<String>, Error> {
 
     let boxed_passphrase = generate_passphrase();
     if boxed_passphrase.is_err() {
@@ -245,47 +1089,88 @@ fn get_or_create_passphrase(path: &str) -> Result<String, String> {
         return Err(message)
     }
 
-    let passphrase = boxed_passphrase.unwrap();
+    let passphrase = Secret::new(boxed_passphrase.unwrap());
 
-    let boxed_passphrase = read_or_create_and_write(path, passphrase.as_bytes());
+    let boxed_passphrase = store.read_or_create(name, passphrase.as_bytes());
     if boxed_passphrase.is_err() {
         let message = boxed_passphrase.err().unwrap();
         return Err(message)
     }
 
     let boxed_passphrase = String::from_utf8(boxed_passphrase.unwrap());
-    let passphrase = boxed_passphrase.unwrap();
+    if boxed_passphrase.is_err() {
+        return Err(Error::format_from("RSA passphrase is not a valid UTF-8 string", boxed_passphrase.err().unwrap()))
+    }
+    let passphrase = Secret::new(boxed_passphrase.unwrap());
     Ok(passphrase)
 }
 
-fn get_or_create_private_public_keys(passphrase: &str, public_key_path: &str, private_key_path: &str) -> Result<(String, String), String> {
-    let rsa = Rsa::generate(RSA_SIZE).unwrap();
+fn get_or_create_private_public_keys(store: &dyn KeyStore, names: &KeyNames, passphrase: &str) -> Result<(Secret<String>, String), Error> {
+    let boxed_rsa = Rsa::generate(RSA_SIZE);
+    if boxed_rsa.is_err() {
+        return Err(Error::crypto_from("unable to generate RSA key", boxed_rsa.err().unwrap()))
+    }
+    let rsa = boxed_rsa.unwrap();
 
     let boxed_private_key = rsa.private_key_to_pem_passphrase(Cipher::aes_128_cbc(), passphrase.as_bytes());
-    let boxed_private_key = String::from_utf8(boxed_private_key.unwrap());
-    let private_key  = boxed_private_key.unwrap();
-
-    let boxed_private_key = read_or_create_and_write(private_key_path, private_key.as_bytes());
     if boxed_private_key.is_err() {
-        let message = boxed_private_key.err().unwrap();
-        return Err(message)
+        return Err(Error::crypto_from("unable to encode RSA private key", boxed_private_key.err().unwrap()))
     }
-    let boxed_private_key = String::from_utf8(boxed_private_key.unwrap());
-    let private_key = boxed_private_key.unwrap();
+    let private_key = Secret::new(boxed_private_key.unwrap());
 
+    let boxed_key_pair = read_or_create_key_pair(
+        store,
+        names.private_key.as_str(),
+        private_key.as_slice(),
+        names.public_key.as_str(),
+        |stored_private_key| derive_public_key(stored_private_key, passphrase),
+        is_same_public_key,
+    );
+    if boxed_key_pair.is_err() {
+        return Err(boxed_key_pair.err().unwrap());
+    }
+    let (stored_private_key, stored_public_key) = boxed_key_pair.unwrap();
 
-    let boxed_public_key = rsa.public_key_to_pem();
-    let boxed_public_key = String::from_utf8(boxed_public_key.unwrap());
-    let public_key = boxed_public_key.unwrap();
+    let boxed_private_key = String::from_utf8(stored_private_key.to_vec());
+    if boxed_private_key.is_err() {
+        return Err(Error::format_from("RSA private key is not a valid UTF-8 PEM", boxed_private_key.err().unwrap()))
+    }
+    let private_key = Secret::new(boxed_private_key.unwrap());
 
-    let boxed_public_key = read_or_create_and_write(public_key_path, public_key.as_bytes());
+    let boxed_public_key = String::from_utf8(stored_public_key);
     if boxed_public_key.is_err() {
-        let message = boxed_public_key.err().unwrap();
-        return Err(message)
+        return Err(Error::format_from("RSA public key is not a valid UTF-8 PEM", boxed_public_key.err().unwrap()))
     }
-    let boxed_public_key = String::from_utf8(boxed_public_key.unwrap());
     let public_key = boxed_public_key.unwrap();
 
-    Ok((private_key.to_string(), public_key.to_string()))
+    Ok((private_key, public_key))
 }
 
+fn derive_public_key(private_key: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let boxed_rsa = Rsa::private_key_from_pem_passphrase(private_key, passphrase.as_bytes());
+    if boxed_rsa.is_err() {
+        return Err(Error::key_mismatch_from("unable to decrypt stored RSA private key with stored passphrase", boxed_rsa.err().unwrap()))
+    }
+
+    let boxed_public_key = boxed_rsa.unwrap().public_key_to_pem();
+    if boxed_public_key.is_err() {
+        return Err(Error::crypto_from("unable to encode RSA public key", boxed_public_key.err().unwrap()))
+    }
+    Ok(boxed_public_key.unwrap())
+}
+
+fn is_same_public_key(public_key: &[u8], other_public_key: &[u8]) -> Result<bool, Error> {
+    let boxed_rsa = Rsa::public_key_from_pem(public_key);
+    if boxed_rsa.is_err() {
+        return Err(Error::format_from("unable to parse stored RSA public key", boxed_rsa.err().unwrap()))
+    }
+    let rsa = boxed_rsa.unwrap();
+
+    let boxed_other_rsa = Rsa::public_key_from_pem(other_public_key);
+    if boxed_other_rsa.is_err() {
+        return Err(Error::format_from("unable to parse RSA public key", boxed_other_rsa.err().unwrap()))
+    }
+    let other_rsa = boxed_other_rsa.unwrap();
+
+    Ok(rsa.n() == other_rsa.n() && rsa.e() == other_rsa.e())
+}
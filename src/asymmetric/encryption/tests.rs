@@ -1,8 +1,14 @@
-use crate::asymmetric::encryption::{decrypt, encrypt, get_decryption_params, get_encryption_params, setup};
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use crate::asymmetric::encryption::{decrypt, decrypt_envelope, DecryptionKeyring, Decryptor, encrypt, encrypt_envelope, EncryptionKeyring, Encryptor, get_decryption_keyring, get_decryption_keyring_with_store, get_decryption_params, get_encryption_keyring, get_encryption_keyring_with_store, get_encryption_params, OaepHash, reencrypt, reencrypt_envelope, rotate_keyring_with_store, RsaPadding, setup, setup_keyring, setup_keyring_with_store, setup_with_store};
+use crate::key_store::MemoryKeyStore;
+use crate::Error;
+use crate::passphrase::generate_bytes;
 
 #[test]
 fn encryption() {
-    //maximum 501 bytes at once to be encrypted
+    //maximum 446 bytes at once to be encrypted
     let data_to_encrypt_as_bytes = "Some data to encrypt".as_bytes();
 
     // path needs to be accessible by user with write permission for initial setup
@@ -22,7 +28,7 @@ fn encryption() {
 
 #[test]
 fn encryption_alternative() {
-    // maximum 501 bytes at once to be encrypted
+    // maximum 446 bytes at once to be encrypted
     let data_to_encrypt_as_bytes = "Some data to encrypt".as_bytes();
 
     // path needs to be accessible by user with write permission for initial setup
@@ -35,3 +41,355 @@ fn encryption_alternative() {
 
     assert_eq!(data_to_encrypt_as_bytes, decrypted_bytes);
 }
+
+#[test]
+fn encryption_binary_data() {
+    let params_path = "/test/encryption_parameters/";
+    let _ = setup(Some(params_path)).unwrap();
+
+    let mut payloads: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![0],
+        vec![0; 446],
+        vec![0xFF, 0x00, 0xC3, 0x28, 0x00, 0x00],
+        "some text ending with zero bytes\0\0\0".as_bytes().to_vec(),
+    ];
+    for length in [1, 32, 255, 445, 446] {
+        payloads.push(generate_bytes(length).unwrap());
+
+        let mut ending_with_zeros = generate_bytes(length).unwrap();
+        let zeros = length / 2 + 1;
+        for byte in ending_with_zeros.iter_mut().rev().take(zeros) {
+            *byte = 0;
+        }
+        payloads.push(ending_with_zeros);
+    }
+
+    for payload in payloads {
+        let encryption_params = get_encryption_params(Some(params_path)).unwrap();
+        let encrypted_bytes = encrypt(encryption_params, payload.as_slice()).unwrap();
+
+        let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+        let decrypted_bytes = decrypt(decryption_params, encrypted_bytes.as_slice()).unwrap();
+
+        assert_eq!(payload, decrypted_bytes);
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn encryption_paddings() {
+    let params_path = "/test/encryption_parameters/";
+    let _ = setup(Some(params_path)).unwrap();
+
+    let paddings = [
+        RsaPadding::default(),
+        RsaPadding::Oaep { hash: OaepHash::Sha256, mgf1_hash: OaepHash::Sha256, label: "some label".as_bytes().to_vec() },
+        RsaPadding::Oaep { hash: OaepHash::Sha512, mgf1_hash: OaepHash::Sha256, label: vec![] },
+        RsaPadding::Oaep { hash: OaepHash::Sha1, mgf1_hash: OaepHash::Sha1, label: vec![] },
+        RsaPadding::Pkcs1v15Legacy,
+    ];
+
+    for padding in paddings.iter() {
+        let data = generate_bytes(padding.max_data_length()).unwrap();
+
+        let mut encryption_params = get_encryption_params(Some(params_path)).unwrap();
+        encryption_params.padding = padding.clone();
+        let encrypted_bytes = encrypt(encryption_params, data.as_slice()).unwrap();
+        assert_eq!(encrypted_bytes.len(), 512);
+
+        let mut decryption_params = get_decryption_params(Some(params_path)).unwrap();
+        decryption_params.padding = padding.clone();
+        let decrypted_bytes = decrypt(decryption_params, encrypted_bytes.as_slice()).unwrap();
+        assert_eq!(data, decrypted_bytes);
+
+        let mut encryption_params = get_encryption_params(Some(params_path)).unwrap();
+        encryption_params.padding = padding.clone();
+        let too_long = generate_bytes(padding.max_data_length() + 1).unwrap();
+        assert!(encrypt(encryption_params, too_long.as_slice()).is_err());
+
+        for other_padding in paddings.iter() {
+            if other_padding == padding {
+                continue;
+            }
+            let mut decryption_params = get_decryption_params(Some(params_path)).unwrap();
+            decryption_params.padding = other_padding.clone();
+            let boxed_decrypted = decrypt(decryption_params, encrypted_bytes.as_slice());
+            assert!(boxed_decrypted.is_err() || boxed_decrypted.unwrap() != data);
+        }
+    }
+}
+
+#[test]
+fn default_padding_is_oaep_sha256() {
+    let params_path = "/test/encryption_parameters/";
+    let (encryption_params, decryption_params) = setup(Some(params_path)).unwrap();
+
+    let expected = RsaPadding::Oaep { hash: OaepHash::Sha256, mgf1_hash: OaepHash::Sha256, label: vec![] };
+    assert_eq!(expected, encryption_params.padding);
+    assert_eq!(expected, decryption_params.padding);
+    assert_eq!(446, expected.max_data_length());
+}
+
+#[test]
+fn envelope_encryption() {
+    let params_path = "/test/encryption_parameters/";
+    let _ = setup(Some(params_path)).unwrap();
+    let associated_data = "some associated data".as_bytes();
+
+    for length in [0, 1, 446, 447, 501, 502, 64 * 1024, 3 * 1024 * 1024 + 7] {
+        let data = generate_bytes(length).unwrap();
+
+        let encryption_params = get_encryption_params(Some(params_path)).unwrap();
+        let envelope = encrypt_envelope(encryption_params, data.as_slice(), associated_data).unwrap();
+        // header, wrapped key, nonce, encrypted data and tag
+        assert_eq!(envelope.len(), 8 + 512 + 12 + length + 16);
+
+        let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+        let decrypted = decrypt_envelope(decryption_params, envelope.as_slice(), associated_data).unwrap();
+        assert_eq!(data, decrypted);
+    }
+}
+
+#[test]
+fn envelope_tampering() {
+    let params_path = "/test/encryption_parameters/";
+    let _ = setup(Some(params_path)).unwrap();
+    let associated_data = "some associated data".as_bytes();
+    let data = generate_bytes(1024).unwrap();
+
+    let encryption_params = get_encryption_params(Some(params_path)).unwrap();
+    let envelope = encrypt_envelope(encryption_params, data.as_slice(), associated_data).unwrap();
+
+    // magic, version, algorithm, wrapped key length, wrapped key, nonce, encrypted data and tag
+    for index in [0, 4, 5, 6, 7, 8, 300, 519, 520, 532, 1000, envelope.len() - 1] {
+        let mut tampered = envelope.to_vec();
+        tampered[index] ^= 0x01;
+        let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+        assert!(decrypt_envelope(decryption_params, tampered.as_slice(), associated_data).is_err());
+    }
+
+    let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+    assert!(decrypt_envelope(decryption_params, envelope.as_slice(), "other associated data".as_bytes()).is_err());
+
+    for length in [0, 3, 8, 519, 520, 540] {
+        let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+        assert!(decrypt_envelope(decryption_params, &envelope[..length], associated_data).is_err());
+    }
+}
+
+#[test]
+fn decryption_errors() {
+    let params_path = "/test/encryption_parameters/";
+    let _ = setup(Some(params_path)).unwrap();
+
+    let encryption_params = get_encryption_params(Some(params_path)).unwrap();
+    let encrypted_bytes = encrypt(encryption_params, "some data".as_bytes()).unwrap();
+
+    let mut decryption_params = get_decryption_params(Some(params_path)).unwrap();
+    decryption_params.rsa_passphrase = "wrong passphrase".to_string().into();
+    let error = decrypt(decryption_params, encrypted_bytes.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let mut tampered = encrypted_bytes.to_vec();
+    tampered[100] ^= 0x01;
+    let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+    let error = decrypt(decryption_params, tampered.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    let mut encryption_params = get_encryption_params(Some(params_path)).unwrap();
+    encryption_params.rsa_public_key_pem = "not a key".to_string();
+    let error = encrypt(encryption_params, "some data".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let decryption_params = get_decryption_params(Some(params_path)).unwrap();
+    let error = decrypt_envelope(decryption_params, "not an envelope".as_bytes(), "".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn concurrent_setup_converges() {
+    let path_to_params = "/test/encryption_parameters_concurrent_rsa/";
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| thread::spawn(move || setup(Some(path_to_params)).unwrap()))
+        .collect();
+    let params: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+    let (encryption_params, decryption_params) = &params[0];
+    for (other_encryption_params, other_decryption_params) in params.iter() {
+        assert_eq!(other_encryption_params.rsa_public_key_pem, encryption_params.rsa_public_key_pem);
+        assert_eq!(other_decryption_params.rsa_private_key_pem.as_str(), decryption_params.rsa_private_key_pem.as_str());
+        assert_eq!(other_decryption_params.rsa_passphrase.as_str(), decryption_params.rsa_passphrase.as_str());
+    }
+
+    // the converged key pair matches
+    let data = "some data to encrypt".as_bytes();
+    let encrypted = encrypt(get_encryption_params(Some(path_to_params)).unwrap(), data).unwrap();
+    let decrypted = decrypt(get_decryption_params(Some(path_to_params)).unwrap(), encrypted.as_slice()).unwrap();
+    assert_eq!(decrypted, data);
+
+    fs::remove_dir_all("test/encryption_parameters_concurrent_rsa").unwrap();
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn encryptor_and_decryptor_are_shared_between_threads() {
+    assert_send_sync::<Encryptor>();
+    assert_send_sync::<Decryptor>();
+
+    let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new()).unwrap();
+    let encryptor = Arc::new(Encryptor::new(&encryption_params).unwrap());
+    let decryptor = Arc::new(Decryptor::new(&decryption_params).unwrap());
+
+    let handles: Vec<_> = (0..4u8)
+        .map(|index| {
+            let encryptor = Arc::clone(&encryptor);
+            let decryptor = Arc::clone(&decryptor);
+            thread::spawn(move || {
+                let data = vec![index; 100];
+                let encrypted_data = encryptor.encrypt(data.as_slice()).unwrap();
+                assert_eq!(decryptor.decrypt(encrypted_data.as_slice()).unwrap(), data);
+
+                let envelope = encryptor.encrypt_envelope(data.as_slice(), &[index]).unwrap();
+                assert_eq!(decryptor.decrypt_envelope(envelope.as_slice(), &[index]).unwrap(), data);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // contexts and functions produce interchangeable output
+    let envelope = encryptor.encrypt_envelope("some data".as_bytes(), &[]).unwrap();
+    assert_eq!(decrypt_envelope(decryption_params.clone(), envelope.as_slice(), &[]).unwrap(), "some data".as_bytes());
+    let encrypted_data = encrypt(encryption_params, "some data".as_bytes()).unwrap();
+    assert_eq!(decryptor.decrypt(encrypted_data.as_slice()).unwrap(), "some data".as_bytes());
+
+    let mut wrong_params = decryption_params;
+    wrong_params.rsa_passphrase = "wrong passphrase".to_string().into();
+    let error = Decryptor::new(&wrong_params).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+}
+
+#[test]
+fn keyring_rotation() {
+    let store = MemoryKeyStore::new();
+    let (encryption_keyring, decryption_keyring) = setup_keyring_with_store(&store).unwrap();
+    assert_eq!(encryption_keyring.key_ids(), vec![1]);
+    assert_eq!(decryption_keyring.key_ids(), vec![1]);
+
+    let encrypted_data = encryption_keyring.encrypt("some data".as_bytes()).unwrap();
+    let envelope = encryption_keyring.encrypt_envelope("some data".as_bytes(), "associated data".as_bytes()).unwrap();
+    assert_eq!(DecryptionKeyring::key_id_of(encrypted_data.as_slice()).unwrap(), 1);
+    assert_eq!(DecryptionKeyring::key_id_of(envelope.as_slice()).unwrap(), 1);
+
+    let (encryption_keyring, decryption_keyring) = rotate_keyring_with_store(&store).unwrap();
+    assert_eq!(encryption_keyring.key_ids(), vec![1, 2]);
+    assert_eq!(encryption_keyring.primary_key_id(), 2);
+    assert_eq!(decryption_keyring.key_ids(), vec![1, 2]);
+    assert_eq!(decryption_keyring.decrypt(encrypted_data.as_slice()).unwrap(), "some data".as_bytes());
+    assert_eq!(decryption_keyring.decrypt_envelope(envelope.as_slice(), "associated data".as_bytes()).unwrap(), "some data".as_bytes());
+
+    let reencrypted_data = reencrypt(&decryption_keyring, &encryption_keyring, encrypted_data.as_slice()).unwrap();
+    assert_eq!(DecryptionKeyring::key_id_of(reencrypted_data.as_slice()).unwrap(), 2);
+    let reencrypted_envelope = reencrypt_envelope(&decryption_keyring, &encryption_keyring, envelope.as_slice(), "associated data".as_bytes()).unwrap();
+    assert_eq!(DecryptionKeyring::key_id_of(reencrypted_envelope.as_slice()).unwrap(), 2);
+
+    // keyrings are read back from the store, rotation is not repeated
+    let encryption_keyring = get_encryption_keyring_with_store(&store).unwrap();
+    assert_eq!(encryption_keyring.primary_key_id(), 2);
+    let mut decryption_keyring = get_decryption_keyring_with_store(&store).unwrap();
+    assert_eq!(decryption_keyring.decrypt(reencrypted_data.as_slice()).unwrap(), "some data".as_bytes());
+    assert_eq!(decryption_keyring.decrypt_envelope(reencrypted_envelope.as_slice(), "associated data".as_bytes()).unwrap(), "some data".as_bytes());
+
+    // key ID of the envelope is authenticated
+    let mut tampered_envelope = reencrypted_envelope.clone();
+    tampered_envelope[8] = 1;
+    let error = decryption_keyring.decrypt_envelope(tampered_envelope.as_slice(), "associated data".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    // key ID of plain RSA output is not, but data fails to decrypt with another key
+    let mut tampered_data = reencrypted_data.clone();
+    tampered_data[8] = 1;
+    let error = decryption_keyring.decrypt(tampered_data.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    tampered_data[8] = 3;
+    let error = decryption_keyring.decrypt(tampered_data.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let error = decryption_keyring.decrypt(&reencrypted_data[9..]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let error = DecryptionKeyring::key_id_of(&reencrypted_data[..8]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    decryption_keyring.remove_key(1).unwrap();
+    let error = decryption_keyring.decrypt(encrypted_data.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = decryption_keyring.remove_key(1).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+}
+
+#[test]
+fn keyring_management() {
+    let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new()).unwrap();
+    let mut encryption_keyring = EncryptionKeyring::new(10, &encryption_params).unwrap();
+    let mut decryption_keyring = DecryptionKeyring::new(10, &decryption_params).unwrap();
+
+    let error = encryption_keyring.add_key(10, &encryption_params).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = decryption_keyring.add_key(10, &decryption_params).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let mut wrong_passphrase = decryption_params.clone();
+    wrong_passphrase.rsa_passphrase = "wrong passphrase".to_string().into();
+    let error = decryption_keyring.add_key(11, &wrong_passphrase).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let mut malformed_key = encryption_params.clone();
+    malformed_key.rsa_public_key_pem = "not a key".to_string();
+    let error = encryption_keyring.add_key(11, &malformed_key).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    // same key pair may be registered under several key IDs
+    encryption_keyring.add_key(5, &encryption_params).unwrap();
+    decryption_keyring.add_key(5, &decryption_params).unwrap();
+    assert_eq!(encryption_keyring.key_ids(), vec![5, 10]);
+
+    let error = encryption_keyring.set_primary(6).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    encryption_keyring.set_primary(5).unwrap();
+    let envelope = encryption_keyring.encrypt_envelope("some data".as_bytes(), &[]).unwrap();
+    assert_eq!(DecryptionKeyring::key_id_of(envelope.as_slice()).unwrap(), 5);
+    assert_eq!(decryption_keyring.decrypt_envelope(envelope.as_slice(), &[]).unwrap(), "some data".as_bytes());
+
+    let error = encryption_keyring.remove_key(5).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    encryption_keyring.remove_key(10).unwrap();
+    assert_eq!(encryption_keyring.key_ids(), vec![5]);
+}
+
+#[test]
+fn keyring_starts_with_existing_key_pair() {
+    let params_path = "/test/rsa_keyring_existing_key/";
+    let _ = fs::remove_dir_all("test/rsa_keyring_existing_key");
+
+    let (encryption_params, decryption_params) = setup(Some(params_path)).unwrap();
+    let (encryption_keyring, _) = setup_keyring(Some(params_path)).unwrap();
+    assert_eq!(encryption_keyring.key_ids(), vec![1]);
+
+    // key pair created by setup is the first key pair of the keyring
+    let encrypted_data = encryption_keyring.encrypt("some data".as_bytes()).unwrap();
+    assert_eq!(decrypt(decryption_params, &encrypted_data[9..]).unwrap(), "some data".as_bytes());
+
+    let encrypted_data = encrypt(encryption_params, "some data".as_bytes()).unwrap();
+    let tagged_data = [&[b'C', b'X', b'K', b'R', 1, 0, 0, 0, 1], encrypted_data.as_slice()].concat();
+    let decryption_keyring = get_decryption_keyring(Some(params_path)).unwrap();
+    assert_eq!(decryption_keyring.decrypt(tagged_data.as_slice()).unwrap(), "some data".as_bytes());
+    assert_eq!(get_encryption_keyring(Some(params_path)).unwrap().primary_key_id(), 1);
+
+    fs::remove_dir_all("test/rsa_keyring_existing_key").unwrap();
+}
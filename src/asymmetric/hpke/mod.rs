@@ -0,0 +1,643 @@
+use aes_gcm::aead::Payload;
+use crate::Error;
+use crate::asymmetric::key_agreement;
+use crate::asymmetric::key_agreement::{PrivateKeyParameters, PublicKeyParameters};
+use crate::kdf::hkdf;
+use crate::secret::Secret;
+use crate::symmetric;
+use crate::symmetric::encryption::Cipher;
+
+#[cfg(test)]
+mod tests;
+
+const VERSION_LABEL: &[u8] = b"HPKE-v1";
+// HKDF-SHA256 is the only key derivation function
+const KDF_ID: u16 = 0x0001;
+const HASH_LENGTH: usize = 32;
+const MIN_PSK_LENGTH: usize = 32;
+
+/// Key encapsulation mechanism of the suite, it defines the type of the recipient key pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Kem {
+    /// DHKEM(X25519, HKDF-SHA256), encapsulated key is 32 bytes long
+    #[default]
+    DhKemX25519HkdfSha256,
+    /// DHKEM(P-256, HKDF-SHA256), encapsulated key is 65 bytes long uncompressed point
+    DhKemP256HkdfSha256,
+}
+
+impl Kem {
+    /// Returns identifier of the mechanism as registered by RFC 9180
+    pub fn id(&self) -> u16 {
+        match self {
+            Kem::DhKemX25519HkdfSha256 => 0x0020,
+            Kem::DhKemP256HkdfSha256 => 0x0010,
+        }
+    }
+
+    /// Returns key agreement algorithm of the key pairs used by the mechanism
+    pub fn algorithm(&self) -> key_agreement::Algorithm {
+        match self {
+            Kem::DhKemX25519HkdfSha256 => key_agreement::Algorithm::X25519,
+            Kem::DhKemP256HkdfSha256 => key_agreement::Algorithm::EcdhP256,
+        }
+    }
+
+    /// Returns length of the encapsulated key in bytes
+    pub fn enc_length(&self) -> usize {
+        self.algorithm().public_key_length()
+    }
+
+    fn suite_id(&self) -> Vec<u8> {
+        [b"KEM".as_slice(), &self.id().to_be_bytes()].concat()
+    }
+}
+
+/// Authenticated encryption algorithm of the suite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aead {
+    /// AES-128-GCM
+    #[default]
+    Aes128Gcm,
+    /// AES-256-GCM
+    Aes256Gcm,
+    /// ChaCha20-Poly1305 as defined in RFC 8439
+    ChaCha20Poly1305,
+    /// No encryption, context is used only to export secrets
+    ExportOnly,
+}
+
+impl Aead {
+    /// Returns identifier of the algorithm as registered by RFC 9180
+    pub fn id(&self) -> u16 {
+        match self {
+            Aead::Aes128Gcm => 0x0001,
+            Aead::Aes256Gcm => 0x0002,
+            Aead::ChaCha20Poly1305 => 0x0003,
+            Aead::ExportOnly => 0xFFFF,
+        }
+    }
+
+    fn algorithm(&self) -> Option<symmetric::encryption::Algorithm> {
+        match self {
+            Aead::Aes128Gcm => Some(symmetric::encryption::Algorithm::Aes128Gcm),
+            Aead::Aes256Gcm => Some(symmetric::encryption::Algorithm::Aes256Gcm),
+            Aead::ChaCha20Poly1305 => Some(symmetric::encryption::Algorithm::ChaCha20Poly1305),
+            Aead::ExportOnly => None,
+        }
+    }
+}
+
+/// Suite is the combination of algorithms both parties agree on, key derivation function is always HKDF-SHA256
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Suite {
+    pub kem: Kem,
+    pub aead: Aead,
+}
+
+impl Suite {
+    fn id(&self) -> Vec<u8> {
+        [b"HPKE".as_slice(), &self.kem.id().to_be_bytes(), &KDF_ID.to_be_bytes(), &self.aead.id().to_be_bytes()].concat()
+    }
+}
+
+/// SenderContext encrypts any number of messages to the recipient, they have to be opened in the same order
+///
+/// It can not be cloned, as clones would encrypt different messages with the same nonce
+pub struct SenderContext {
+    context: Context,
+}
+
+impl SenderContext {
+    /// Returns suite of the context
+    pub fn suite(&self) -> Suite {
+        self.context.suite
+    }
+
+    /// Encrypts next message, associated data is authenticated but not encrypted
+    pub fn seal(&mut self, associated_data: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.context.seal(associated_data, data)
+    }
+
+    /// Derives secret of the given length bound to the exporter context, recipient derives the same secret
+    pub fn export(&self, exporter_context: &[u8], length: usize) -> Result<Secret<Vec<u8>>, Error> {
+        self.context.export(exporter_context, length)
+    }
+}
+
+/// RecipientContext decrypts messages of the sender in the order they were sealed
+pub struct RecipientContext {
+    context: Context,
+}
+
+impl RecipientContext {
+    /// Returns suite of the context
+    pub fn suite(&self) -> Suite {
+        self.context.suite
+    }
+
+    /// Decrypts next message, failed attempt does not advance the context
+    pub fn open(&mut self, associated_data: &[u8], encrypted_data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.context.open(associated_data, encrypted_data)
+    }
+
+    /// Derives secret of the given length bound to the exporter context, sender derives the same secret
+    pub fn export(&self, exporter_context: &[u8], length: usize) -> Result<Secret<Vec<u8>>, Error> {
+        self.context.export(exporter_context, length)
+    }
+}
+
+/// Derives key pair of the mechanism deterministically from the input keying material, which has to be at least 32 bytes long
+///
+/// To generate random key pair use [key_agreement::generate_key_pair] or [key_agreement::setup]
+pub fn derive_key_pair(kem: Kem, ikm: &[u8]) -> Result<(PrivateKeyParameters, PublicKeyParameters), Error> {
+    if ikm.len() < HASH_LENGTH {
+        let message = format!("input keying material is too short: {} bytes, expected at least {} bytes", ikm.len(), HASH_LENGTH);
+        return Err(Error::key_mismatch(message))
+    }
+
+    let suite_id = kem.suite_id();
+    let prk = labeled_extract(suite_id.as_slice(), &[], b"dkp_prk", ikm);
+
+    let private_key = match kem {
+        Kem::DhKemX25519HkdfSha256 => {
+            let boxed_private_key = labeled_expand(suite_id.as_slice(), prk.as_slice(), b"sk", &[], 32);
+            if boxed_private_key.is_err() {
+                return Err(boxed_private_key.err().unwrap());
+            }
+            boxed_private_key.unwrap()
+        }
+        Kem::DhKemP256HkdfSha256 => {
+            let boxed_private_key = derive_p256_private_key(suite_id.as_slice(), prk.as_slice());
+            if boxed_private_key.is_err() {
+                return Err(boxed_private_key.err().unwrap());
+            }
+            boxed_private_key.unwrap()
+        }
+    };
+
+    let algorithm = kem.algorithm();
+    let boxed_public_key = key_agreement::derive_public_key(algorithm, private_key.as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+
+    let private_key_parameters = PrivateKeyParameters { algorithm, private_key };
+    let public_key_parameters = PublicKeyParameters { algorithm, public_key: boxed_public_key.unwrap() };
+    Ok((private_key_parameters, public_key_parameters))
+}
+
+/// Encrypts single message to the public key of the recipient in the base mode
+///
+/// Returns encapsulated key and encrypted data, both are sent to the recipient
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::asymmetric::hpke::{open, seal, Aead, Kem, Suite};
+///     use crypto_ext::asymmetric::key_agreement::setup;
+///
+///     #[test]
+///     fn hpke() {
+///         // path needs to be accessible by user with write permission for initial setup
+///         let (private_key_params, public_key_params) = setup(Some("/test/hpke_parameters/")).unwrap();
+///
+///         let suite = Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::ChaCha20Poly1305 };
+///         let info = "application name and version".as_bytes();
+///
+///         let (enc, encrypted) = seal(suite, &public_key_params, info, "header".as_bytes(), "some data".as_bytes()).unwrap();
+///         let decrypted = open(suite, enc.as_slice(), &private_key_params, info, "header".as_bytes(), encrypted.as_slice()).unwrap();
+///         assert_eq!("some data".as_bytes(), decrypted);
+///     }
+/// ```
+pub fn seal(suite: Suite, recipient_public_key: &PublicKeyParameters, info: &[u8], associated_data: &[u8], data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let boxed_context = setup_base_sender(suite, recipient_public_key, info);
+    if boxed_context.is_err() {
+        return Err(boxed_context.err().unwrap());
+    }
+    let (enc, mut context) = boxed_context.unwrap();
+
+    let boxed_encrypted_data = context.seal(associated_data, data);
+    if boxed_encrypted_data.is_err() {
+        return Err(boxed_encrypted_data.err().unwrap());
+    }
+    Ok((enc, boxed_encrypted_data.unwrap()))
+}
+
+/// Decrypts single message encrypted by [seal] with the private key of the recipient
+pub fn open(suite: Suite, enc: &[u8], recipient_private_key: &PrivateKeyParameters, info: &[u8], associated_data: &[u8], encrypted_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_context = setup_base_recipient(suite, enc, recipient_private_key, info);
+    if boxed_context.is_err() {
+        return Err(boxed_context.err().unwrap());
+    }
+    boxed_context.unwrap().open(associated_data, encrypted_data)
+}
+
+/// Creates sender context of the base mode, returns encapsulated key to be sent to the recipient
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::asymmetric::hpke::{setup_base_recipient, setup_base_sender, Suite};
+///     use crypto_ext::asymmetric::key_agreement::{generate_key_pair, Algorithm};
+///
+///     #[test]
+///     fn hpke_context() {
+///         let (private_key_params, public_key_params) = generate_key_pair(Algorithm::X25519).unwrap();
+///         let info = "session".as_bytes();
+///
+///         let (enc, mut sender) = setup_base_sender(Suite::default(), &public_key_params, info).unwrap();
+///         let mut recipient = setup_base_recipient(Suite::default(), enc.as_slice(), &private_key_params, info).unwrap();
+///
+///         for message in ["first message", "second message"] {
+///             let encrypted = sender.seal(&[], message.as_bytes()).unwrap();
+///             assert_eq!(recipient.open(&[], encrypted.as_slice()).unwrap(), message.as_bytes());
+///         }
+///
+///         // both parties derive the same secrets, for example for another protocol
+///         assert_eq!(*sender.export("resumption".as_bytes(), 32).unwrap(), *recipient.export("resumption".as_bytes(), 32).unwrap());
+///     }
+/// ```
+pub fn setup_base_sender(suite: Suite, recipient_public_key: &PublicKeyParameters, info: &[u8]) -> Result<(Vec<u8>, SenderContext), Error> {
+    setup_sender(suite, Mode::Base, recipient_public_key, info, &[], &[], None)
+}
+
+/// Creates sender context of the PSK mode, recipient has to know the same pre-shared key, which is at least 32 bytes long
+pub fn setup_psk_sender(suite: Suite, recipient_public_key: &PublicKeyParameters, info: &[u8], psk: &[u8], psk_id: &[u8]) -> Result<(Vec<u8>, SenderContext), Error> {
+    setup_sender(suite, Mode::Psk, recipient_public_key, info, psk, psk_id, None)
+}
+
+/// Creates sender context of the auth mode, recipient verifies the message comes from the holder of the sender private key
+pub fn setup_auth_sender(suite: Suite, recipient_public_key: &PublicKeyParameters, info: &[u8], sender_private_key: &PrivateKeyParameters) -> Result<(Vec<u8>, SenderContext), Error> {
+    setup_sender(suite, Mode::Auth, recipient_public_key, info, &[], &[], Some(sender_private_key))
+}
+
+/// Creates sender context of the auth PSK mode, combination of the PSK and auth modes
+pub fn setup_auth_psk_sender(suite: Suite, recipient_public_key: &PublicKeyParameters, info: &[u8], psk: &[u8], psk_id: &[u8], sender_private_key: &PrivateKeyParameters) -> Result<(Vec<u8>, SenderContext), Error> {
+    setup_sender(suite, Mode::AuthPsk, recipient_public_key, info, psk, psk_id, Some(sender_private_key))
+}
+
+/// Creates recipient context of the base mode from the encapsulated key
+pub fn setup_base_recipient(suite: Suite, enc: &[u8], recipient_private_key: &PrivateKeyParameters, info: &[u8]) -> Result<RecipientContext, Error> {
+    setup_recipient(suite, Mode::Base, enc, recipient_private_key, info, &[], &[], None)
+}
+
+/// Creates recipient context of the PSK mode from the encapsulated key
+pub fn setup_psk_recipient(suite: Suite, enc: &[u8], recipient_private_key: &PrivateKeyParameters, info: &[u8], psk: &[u8], psk_id: &[u8]) -> Result<RecipientContext, Error> {
+    setup_recipient(suite, Mode::Psk, enc, recipient_private_key, info, psk, psk_id, None)
+}
+
+/// Creates recipient context of the auth mode from the encapsulated key, messages open only if they were sealed by the holder of the sender key pair
+pub fn setup_auth_recipient(suite: Suite, enc: &[u8], recipient_private_key: &PrivateKeyParameters, info: &[u8], sender_public_key: &PublicKeyParameters) -> Result<RecipientContext, Error> {
+    setup_recipient(suite, Mode::Auth, enc, recipient_private_key, info, &[], &[], Some(sender_public_key))
+}
+
+/// Creates recipient context of the auth PSK mode from the encapsulated key
+#[allow(clippy::too_many_arguments)]
+pub fn setup_auth_psk_recipient(suite: Suite, enc: &[u8], recipient_private_key: &PrivateKeyParameters, info: &[u8], psk: &[u8], psk_id: &[u8], sender_public_key: &PublicKeyParameters) -> Result<RecipientContext, Error> {
+    setup_recipient(suite, Mode::AuthPsk, enc, recipient_private_key, info, psk, psk_id, Some(sender_public_key))
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Base,
+    Psk,
+    Auth,
+    AuthPsk,
+}
+
+impl Mode {
+    fn id(&self) -> u8 {
+        match self {
+            Mode::Base => 0x00,
+            Mode::Psk => 0x01,
+            Mode::Auth => 0x02,
+            Mode::AuthPsk => 0x03,
+        }
+    }
+
+    fn has_psk(&self) -> bool {
+        matches!(self, Mode::Psk | Mode::AuthPsk)
+    }
+}
+
+struct Context {
+    suite: Suite,
+    // export only suite has no cipher
+    cipher: Option<Cipher>,
+    base_nonce: Vec<u8>,
+    sequence: u64,
+    exporter_secret: Secret<Vec<u8>>,
+}
+
+impl Context {
+    fn seal(&mut self, associated_data: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_nonce = self.nonce();
+        if boxed_nonce.is_err() {
+            return Err(boxed_nonce.err().unwrap());
+        }
+
+        let payload = Payload { msg: data, aad: associated_data };
+        let boxed_encrypted_data = self.cipher.as_ref().unwrap().encrypt(boxed_nonce.unwrap().as_slice(), payload);
+        if boxed_encrypted_data.is_err() {
+            return Err(boxed_encrypted_data.err().unwrap());
+        }
+
+        self.sequence += 1;
+        Ok(boxed_encrypted_data.unwrap())
+    }
+
+    fn open(&mut self, associated_data: &[u8], encrypted_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_nonce = self.nonce();
+        if boxed_nonce.is_err() {
+            return Err(boxed_nonce.err().unwrap());
+        }
+
+        let payload = Payload { msg: encrypted_data, aad: associated_data };
+        let boxed_data = self.cipher.as_ref().unwrap().decrypt(boxed_nonce.unwrap().as_slice(), payload);
+        if boxed_data.is_err() {
+            return Err(boxed_data.err().unwrap());
+        }
+
+        self.sequence += 1;
+        Ok(boxed_data.unwrap())
+    }
+
+    fn export(&self, exporter_context: &[u8], length: usize) -> Result<Secret<Vec<u8>>, Error> {
+        labeled_expand(self.suite.id().as_slice(), self.exporter_secret.as_slice(), b"sec", exporter_context, length)
+    }
+
+    // nonce of the message is the base nonce xor big endian sequence number
+    fn nonce(&self) -> Result<Vec<u8>, Error> {
+        if self.cipher.is_none() {
+            return Err(Error::unsupported_algorithm("export only suite can not seal or open messages"))
+        }
+        if self.sequence == u64::MAX {
+            return Err(Error::crypto("maximum number of messages of the context is reached"))
+        }
+
+        let mut nonce = self.base_nonce.clone();
+        let sequence = self.sequence.to_be_bytes();
+        let offset = nonce.len() - sequence.len();
+        for (index, byte) in sequence.iter().enumerate() {
+            nonce[offset + index] ^= byte;
+        }
+        Ok(nonce)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup_sender(suite: Suite, mode: Mode, recipient_public_key: &PublicKeyParameters, info: &[u8], psk: &[u8], psk_id: &[u8], sender_private_key: Option<&PrivateKeyParameters>) -> Result<(Vec<u8>, SenderContext), Error> {
+    let boxed_ephemeral_key_pair = key_agreement::generate_key_pair(suite.kem.algorithm());
+    if boxed_ephemeral_key_pair.is_err() {
+        return Err(boxed_ephemeral_key_pair.err().unwrap());
+    }
+    let (ephemeral_private_key, _) = boxed_ephemeral_key_pair.unwrap();
+
+    setup_sender_with_ephemeral_key(suite, mode, recipient_public_key, info, psk, psk_id, sender_private_key, &ephemeral_private_key)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup_sender_with_ephemeral_key(suite: Suite, mode: Mode, recipient_public_key: &PublicKeyParameters, info: &[u8], psk: &[u8], psk_id: &[u8], sender_private_key: Option<&PrivateKeyParameters>, ephemeral_private_key: &PrivateKeyParameters) -> Result<(Vec<u8>, SenderContext), Error> {
+    let boxed_validate = validate_psk(mode, psk, psk_id);
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let boxed_encapsulation = encapsulate(suite.kem, recipient_public_key, ephemeral_private_key, sender_private_key);
+    if boxed_encapsulation.is_err() {
+        return Err(boxed_encapsulation.err().unwrap());
+    }
+    let (shared_secret, enc) = boxed_encapsulation.unwrap();
+
+    let boxed_context = key_schedule(suite, mode, shared_secret.as_slice(), info, psk, psk_id);
+    if boxed_context.is_err() {
+        return Err(boxed_context.err().unwrap());
+    }
+    Ok((enc, SenderContext { context: boxed_context.unwrap() }))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup_recipient(suite: Suite, mode: Mode, enc: &[u8], recipient_private_key: &PrivateKeyParameters, info: &[u8], psk: &[u8], psk_id: &[u8], sender_public_key: Option<&PublicKeyParameters>) -> Result<RecipientContext, Error> {
+    let boxed_validate = validate_psk(mode, psk, psk_id);
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let boxed_shared_secret = decapsulate(suite.kem, enc, recipient_private_key, sender_public_key);
+    if boxed_shared_secret.is_err() {
+        return Err(boxed_shared_secret.err().unwrap());
+    }
+    let shared_secret = boxed_shared_secret.unwrap();
+
+    let boxed_context = key_schedule(suite, mode, shared_secret.as_slice(), info, psk, psk_id);
+    if boxed_context.is_err() {
+        return Err(boxed_context.err().unwrap());
+    }
+    Ok(RecipientContext { context: boxed_context.unwrap() })
+}
+
+fn validate_psk(mode: Mode, psk: &[u8], psk_id: &[u8]) -> Result<(), Error> {
+    if !mode.has_psk() {
+        return Ok(())
+    }
+
+    if psk.len() < MIN_PSK_LENGTH {
+        let message = format!("pre-shared key is too short: {} bytes, expected at least {} bytes", psk.len(), MIN_PSK_LENGTH);
+        return Err(Error::key_mismatch(message))
+    }
+    if psk_id.is_empty() {
+        return Err(Error::key_mismatch("pre-shared key identifier is empty"))
+    }
+    Ok(())
+}
+
+fn validate_kem_key(kem: Kem, algorithm: key_agreement::Algorithm) -> Result<(), Error> {
+    if kem.algorithm() != algorithm {
+        let message = format!("{:?} key can not be used with {:?}", algorithm, kem);
+        return Err(Error::key_mismatch(message))
+    }
+    Ok(())
+}
+
+// returns shared secret and encapsulated key, which is the ephemeral public key
+fn encapsulate(kem: Kem, recipient_public_key: &PublicKeyParameters, ephemeral_private_key: &PrivateKeyParameters, sender_private_key: Option<&PrivateKeyParameters>) -> Result<(Secret<Vec<u8>>, Vec<u8>), Error> {
+    let boxed_validate = validate_kem_key(kem, recipient_public_key.algorithm);
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let algorithm = kem.algorithm();
+    let boxed_recipient_public_key = key_agreement::canonical_public_key(algorithm, recipient_public_key.public_key.as_slice());
+    if boxed_recipient_public_key.is_err() {
+        return Err(boxed_recipient_public_key.err().unwrap());
+    }
+    let recipient_public_key = PublicKeyParameters { algorithm, public_key: boxed_recipient_public_key.unwrap() };
+
+    let boxed_enc = key_agreement::derive_public_key(algorithm, ephemeral_private_key.private_key.as_slice());
+    if boxed_enc.is_err() {
+        return Err(boxed_enc.err().unwrap());
+    }
+    let enc = boxed_enc.unwrap();
+
+    let boxed_dh = key_agreement::agree(ephemeral_private_key, &recipient_public_key);
+    if boxed_dh.is_err() {
+        return Err(boxed_dh.err().unwrap());
+    }
+    let mut dh = boxed_dh.unwrap().to_vec();
+    let mut kem_context = [enc.as_slice(), recipient_public_key.public_key.as_slice()].concat();
+
+    if let Some(sender_private_key) = sender_private_key {
+        let boxed_validate = validate_kem_key(kem, sender_private_key.algorithm);
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        let boxed_static_dh = key_agreement::agree(sender_private_key, &recipient_public_key);
+        if boxed_static_dh.is_err() {
+            return Err(boxed_static_dh.err().unwrap());
+        }
+        dh.extend_from_slice(boxed_static_dh.unwrap().as_slice());
+
+        let boxed_sender_public_key = key_agreement::derive_public_key(algorithm, sender_private_key.private_key.as_slice());
+        if boxed_sender_public_key.is_err() {
+            return Err(boxed_sender_public_key.err().unwrap());
+        }
+        kem_context.extend_from_slice(boxed_sender_public_key.unwrap().as_slice());
+    }
+
+    let dh = Secret::new(dh);
+    let boxed_shared_secret = extract_and_expand(kem, dh.as_slice(), kem_context.as_slice());
+    if boxed_shared_secret.is_err() {
+        return Err(boxed_shared_secret.err().unwrap());
+    }
+    Ok((boxed_shared_secret.unwrap(), enc))
+}
+
+fn decapsulate(kem: Kem, enc: &[u8], recipient_private_key: &PrivateKeyParameters, sender_public_key: Option<&PublicKeyParameters>) -> Result<Secret<Vec<u8>>, Error> {
+    let boxed_validate = validate_kem_key(kem, recipient_private_key.algorithm);
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    // P-256 encapsulated key has to be uncompressed point
+    if enc.len() != kem.enc_length() {
+        let message = format!("invalid encapsulated key length: {} bytes, expected {} bytes", enc.len(), kem.enc_length());
+        return Err(Error::format(message))
+    }
+
+    let algorithm = kem.algorithm();
+    let ephemeral_public_key = PublicKeyParameters { algorithm, public_key: enc.to_vec() };
+    let boxed_dh = key_agreement::agree(recipient_private_key, &ephemeral_public_key);
+    if boxed_dh.is_err() {
+        return Err(boxed_dh.err().unwrap());
+    }
+    let mut dh = boxed_dh.unwrap().to_vec();
+
+    let boxed_recipient_public_key = key_agreement::derive_public_key(algorithm, recipient_private_key.private_key.as_slice());
+    if boxed_recipient_public_key.is_err() {
+        return Err(boxed_recipient_public_key.err().unwrap());
+    }
+    let mut kem_context = [enc, boxed_recipient_public_key.unwrap().as_slice()].concat();
+
+    if let Some(sender_public_key) = sender_public_key {
+        let boxed_validate = validate_kem_key(kem, sender_public_key.algorithm);
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        let boxed_sender_public_key = key_agreement::canonical_public_key(algorithm, sender_public_key.public_key.as_slice());
+        if boxed_sender_public_key.is_err() {
+            return Err(boxed_sender_public_key.err().unwrap());
+        }
+        let sender_public_key = PublicKeyParameters { algorithm, public_key: boxed_sender_public_key.unwrap() };
+
+        let boxed_static_dh = key_agreement::agree(recipient_private_key, &sender_public_key);
+        if boxed_static_dh.is_err() {
+            return Err(boxed_static_dh.err().unwrap());
+        }
+        dh.extend_from_slice(boxed_static_dh.unwrap().as_slice());
+        kem_context.extend_from_slice(sender_public_key.public_key.as_slice());
+    }
+
+    let dh = Secret::new(dh);
+    extract_and_expand(kem, dh.as_slice(), kem_context.as_slice())
+}
+
+fn extract_and_expand(kem: Kem, dh: &[u8], kem_context: &[u8]) -> Result<Secret<Vec<u8>>, Error> {
+    let suite_id = kem.suite_id();
+    let eae_prk = labeled_extract(suite_id.as_slice(), &[], b"eae_prk", dh);
+    labeled_expand(suite_id.as_slice(), eae_prk.as_slice(), b"shared_secret", kem_context, HASH_LENGTH)
+}
+
+fn key_schedule(suite: Suite, mode: Mode, shared_secret: &[u8], info: &[u8], psk: &[u8], psk_id: &[u8]) -> Result<Context, Error> {
+    let suite_id = suite.id();
+    let psk_id_hash = labeled_extract(suite_id.as_slice(), &[], b"psk_id_hash", psk_id);
+    let info_hash = labeled_extract(suite_id.as_slice(), &[], b"info_hash", info);
+    let key_schedule_context = [&[mode.id()], psk_id_hash.as_slice(), info_hash.as_slice()].concat();
+
+    let secret = labeled_extract(suite_id.as_slice(), shared_secret, b"secret", psk);
+
+    let boxed_exporter_secret = labeled_expand(suite_id.as_slice(), secret.as_slice(), b"exp", key_schedule_context.as_slice(), HASH_LENGTH);
+    if boxed_exporter_secret.is_err() {
+        return Err(boxed_exporter_secret.err().unwrap());
+    }
+    let exporter_secret = boxed_exporter_secret.unwrap();
+
+    let algorithm = match suite.aead.algorithm() {
+        Some(algorithm) => algorithm,
+        None => return Ok(Context { suite, cipher: None, base_nonce: vec![], sequence: 0, exporter_secret }),
+    };
+
+    let boxed_key = labeled_expand(suite_id.as_slice(), secret.as_slice(), b"key", key_schedule_context.as_slice(), algorithm.key_length());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+
+    let boxed_cipher = Cipher::new(algorithm, boxed_key.unwrap().as_slice());
+    if boxed_cipher.is_err() {
+        return Err(boxed_cipher.err().unwrap());
+    }
+
+    let boxed_base_nonce = labeled_expand(suite_id.as_slice(), secret.as_slice(), b"base_nonce", key_schedule_context.as_slice(), algorithm.nonce_length());
+    if boxed_base_nonce.is_err() {
+        return Err(boxed_base_nonce.err().unwrap());
+    }
+
+    Ok(Context { suite, cipher: Some(boxed_cipher.unwrap()), base_nonce: boxed_base_nonce.unwrap().to_vec(), sequence: 0, exporter_secret })
+}
+
+// candidates are tried until one is a valid scalar, bitmask of P-256 is 0xFF so candidates are used as is
+fn derive_p256_private_key(suite_id: &[u8], prk: &[u8]) -> Result<Secret<Vec<u8>>, Error> {
+    for counter in 0..=255u8 {
+        let boxed_candidate = labeled_expand(suite_id, prk, b"candidate", &[counter], 32);
+        if boxed_candidate.is_err() {
+            return Err(boxed_candidate.err().unwrap());
+        }
+        let candidate = boxed_candidate.unwrap();
+
+        if p256::SecretKey::from_be_bytes(candidate.as_slice()).is_ok() {
+            return Ok(candidate)
+        }
+    }
+    Err(Error::crypto("unable to derive P-256 key pair from the input keying material"))
+}
+
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> Secret<Vec<u8>> {
+    let labeled_ikm = Secret::new([VERSION_LABEL, suite_id, label, ikm].concat());
+    hkdf::extract(hkdf::Algorithm::HkdfSha256, salt, labeled_ikm.as_slice())
+}
+
+fn labeled_expand(suite_id: &[u8], prk: &[u8], label: &[u8], info: &[u8], length: usize) -> Result<Secret<Vec<u8>>, Error> {
+    let boxed_length: Result<u16, _> = length.try_into();
+    if boxed_length.is_err() {
+        let message = format!("unable to derive secret of {} bytes", length);
+        return Err(Error::crypto_from(message, boxed_length.err().unwrap()))
+    }
+
+    let labeled_info = [&boxed_length.unwrap().to_be_bytes(), VERSION_LABEL, suite_id, label, info].concat();
+    hkdf::expand(hkdf::Algorithm::HkdfSha256, prk, labeled_info.as_slice(), length)
+}
@@ -0,0 +1,351 @@
+use std::thread;
+use crate::Error;
+use crate::asymmetric::hpke::{derive_key_pair, open, seal, setup_auth_psk_recipient, setup_auth_psk_sender, setup_auth_recipient, setup_auth_sender, setup_base_recipient, setup_base_sender, setup_psk_recipient, setup_psk_sender, setup_recipient, setup_sender_with_ephemeral_key, Aead, Kem, Mode, RecipientContext, SenderContext, Suite};
+use crate::asymmetric::key_agreement::{generate_key_pair, Algorithm, PrivateKeyParameters, PublicKeyParameters};
+use crate::test_util::from_hex;
+
+const SUITES: [Suite; 6] = [
+    Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes128Gcm },
+    Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes256Gcm },
+    Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::ChaCha20Poly1305 },
+    Suite { kem: Kem::DhKemP256HkdfSha256, aead: Aead::Aes128Gcm },
+    Suite { kem: Kem::DhKemP256HkdfSha256, aead: Aead::Aes256Gcm },
+    Suite { kem: Kem::DhKemP256HkdfSha256, aead: Aead::ChaCha20Poly1305 },
+];
+
+const PSK: &str = "0247fd33b913760fa1fa51e1892d9f307fbe65eb171e8132c2af18555a738b82";
+const PSK_ID: &str = "456e6e796e20447572696e206172616e204d6f726961";
+const PLAINTEXT: &[u8] = b"Beauty is truth, truth beauty";
+
+struct Vector {
+    suite: Suite,
+    mode: Mode,
+    ikm_e: &'static str,
+    ikm_r: &'static str,
+    ikm_s: Option<&'static str>,
+    enc: &'static str,
+    ciphertext: &'static str,
+}
+
+// RFC 9180 appendix A, info of every vector is "Ode on a Grecian Urn"
+fn setup_vector(vector: &Vector) -> (Vec<u8>, SenderContext, RecipientContext) {
+    let info = from_hex("4f6465206f6e2061204772656369616e2055726e");
+    let (psk, psk_id) = match vector.mode {
+        Mode::Psk | Mode::AuthPsk => (from_hex(PSK), from_hex(PSK_ID)),
+        _ => (vec![], vec![]),
+    };
+
+    let (ephemeral_private_key, _) = derive_key_pair(vector.suite.kem, from_hex(vector.ikm_e).as_slice()).unwrap();
+    let (recipient_private_key, recipient_public_key) = derive_key_pair(vector.suite.kem, from_hex(vector.ikm_r).as_slice()).unwrap();
+    let sender_key_pair = vector.ikm_s.map(|ikm_s| derive_key_pair(vector.suite.kem, from_hex(ikm_s).as_slice()).unwrap());
+
+    let (enc, sender) = setup_sender_with_ephemeral_key(vector.suite, vector.mode, &recipient_public_key, info.as_slice(), psk.as_slice(), psk_id.as_slice(), sender_key_pair.as_ref().map(|(private_key, _)| private_key), &ephemeral_private_key).unwrap();
+    let recipient = setup_recipient(vector.suite, vector.mode, enc.as_slice(), &recipient_private_key, info.as_slice(), psk.as_slice(), psk_id.as_slice(), sender_key_pair.as_ref().map(|(_, public_key)| public_key)).unwrap();
+    (enc, sender, recipient)
+}
+
+#[test]
+fn rfc9180_vectors() {
+    let vectors = [
+        // A.1.1 base mode
+        Vector {
+            suite: Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes128Gcm },
+            mode: Mode::Base,
+            ikm_e: "7268600d403fce431561aef583ee1613527cff655c1343f29812e66706df3234",
+            ikm_r: "6db9df30aa07dd42ee5e8181afdb977e538f5e1fec8a06223f33f7013e525037",
+            ikm_s: None,
+            enc: "37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431",
+            ciphertext: "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a",
+        },
+        // A.1.2 PSK mode
+        Vector {
+            suite: Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes128Gcm },
+            mode: Mode::Psk,
+            ikm_e: "78628c354e46f3e169bd231be7b2ff1c77aa302460a26dbfa15515684c00130b",
+            ikm_r: "d4a09d09f575fef425905d2ab396c1449141463f698f8efdb7accfaff8995098",
+            ikm_s: None,
+            enc: "0ad0950d9fb9588e59690b74f1237ecdf1d775cd60be2eca57af5a4b0471c91b",
+            ciphertext: "e52c6fed7f758d0cf7145689f21bc1be6ec9ea097fef4e959440012f4feb73fb611b946199e681f4cfc34db8ea",
+        },
+        // A.1.3 auth mode
+        Vector {
+            suite: Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes128Gcm },
+            mode: Mode::Auth,
+            ikm_e: "6e6d8f200ea2fb20c30b003a8b4f433d2f4ed4c2658d5bc8ce2fef718059c9f7",
+            ikm_r: "f1d4a30a4cef8d6d4e3b016e6fd3799ea057db4f345472ed302a67ce1c20cdec",
+            ikm_s: Some("94b020ce91d73fca4649006c7e7329a67b40c55e9e93cc907d282bbbff386f58"),
+            enc: "23fb952571a14a25e3d678140cd0e5eb47a0961bb18afcf85896e5453c312e76",
+            ciphertext: "5fd92cc9d46dbf8943e72a07e42f363ed5f721212cd90bcfd072bfd9f44e06b80fd17824947496e21b680c141b",
+        },
+        // A.1.4 auth PSK mode
+        Vector {
+            suite: Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes128Gcm },
+            mode: Mode::AuthPsk,
+            ikm_e: "4303619085a20ebcf18edd22782952b8a7161e1dbae6e46e143a52a96127cf84",
+            ikm_r: "4b16221f3b269a88e207270b5e1de28cb01f847841b344b8314d6a622fe5ee90",
+            ikm_s: Some("62f77dcf5df0dd7eac54eac9f654f426d4161ec850cc65c54f8b65d2e0b4e345"),
+            enc: "820818d3c23993492cc5623ab437a48a0a7ca3e9639c140fe1e33811eb844b7c",
+            ciphertext: "a84c64df1e11d8fd11450039d4fe64ff0c8a99fca0bd72c2d4c3e0400bc14a40f27e45e141a24001697737533e",
+        },
+        // A.2.1 base mode with ChaCha20-Poly1305
+        Vector {
+            suite: Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::ChaCha20Poly1305 },
+            mode: Mode::Base,
+            ikm_e: "909a9b35d3dc4713a5e72a4da274b55d3d3821a37e5d099e74a647db583a904b",
+            ikm_r: "1ac01f181fdf9f352797655161c58b75c656a6cc2716dcb66372da835542e1df",
+            ikm_s: None,
+            enc: "1afa08d3dec047a643885163f1180476fa7ddb54c6a8029ea33f95796bf2ac4a",
+            ciphertext: "1c5250d8034ec2b784ba2cfd69dbdb8af406cfe3ff938e131f0def8c8b60b4db21993c62ce81883d2dd1b51a28",
+        },
+        // A.3.1 base mode with P-256
+        Vector {
+            suite: Suite { kem: Kem::DhKemP256HkdfSha256, aead: Aead::Aes128Gcm },
+            mode: Mode::Base,
+            ikm_e: "4270e54ffd08d79d5928020af4686d8f6b7d35dbe470265f1f5aa22816ce860e",
+            ikm_r: "668b37171f1072f3cf12ea8a236a45df23fc13b82af3609ad1e354f6ef817550",
+            ikm_s: None,
+            enc: "04a92719c6195d5085104f469a8b9814d5838ff72b60501e2c4466e5e67b325ac98536d7b61a1af4b78e5b7f951c0900be863c403ce65c9bfcb9382657222d18c4",
+            ciphertext: "5ad590bb8baa577f8619db35a36311226a896e7342a6d836d8b7bcd2f20b6c7f9076ac232e3ab2523f39513434",
+        },
+    ];
+
+    for vector in vectors {
+        let (enc, mut sender, mut recipient) = setup_vector(&vector);
+        assert_eq!(enc, from_hex(vector.enc));
+
+        let ciphertext = sender.seal(b"Count-0", PLAINTEXT).unwrap();
+        assert_eq!(ciphertext, from_hex(vector.ciphertext));
+        assert_eq!(recipient.open(b"Count-0", ciphertext.as_slice()).unwrap(), PLAINTEXT);
+    }
+}
+
+#[test]
+fn rfc9180_key_pair_derivation() {
+    let (private_key, public_key) = derive_key_pair(Kem::DhKemX25519HkdfSha256, from_hex("6db9df30aa07dd42ee5e8181afdb977e538f5e1fec8a06223f33f7013e525037").as_slice()).unwrap();
+    assert_eq!(*private_key.private_key, from_hex("4612c550263fc8ad58375df3f557aac531d26850903e55a9f23f21d8534e8ac8"));
+    assert_eq!(public_key.public_key, from_hex("3948cfe0ad1ddb695d780e59077195da6c56506b027329794ab02bca80815c4d"));
+    assert_eq!(public_key.algorithm, Algorithm::X25519);
+
+    let (private_key, public_key) = derive_key_pair(Kem::DhKemP256HkdfSha256, from_hex("668b37171f1072f3cf12ea8a236a45df23fc13b82af3609ad1e354f6ef817550").as_slice()).unwrap();
+    assert_eq!(*private_key.private_key, from_hex("f3ce7fdae57e1a310d87f1ebbde6f328be0a99cdbcadf4d6589cf29de4b8ffd2"));
+    assert_eq!(public_key.public_key, from_hex("04fe8c19ce0905191ebc298a9245792531f26f0cece2460639e8bc39cb7f706a826a779b4cf969b8a0e539c7f62fb3d30ad6aa8f80e30f1d128aafd68a2ce72ea0"));
+    assert_eq!(public_key.algorithm, Algorithm::EcdhP256);
+
+    let error = derive_key_pair(Kem::DhKemX25519HkdfSha256, &[0; 31]).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+}
+
+#[test]
+fn rfc9180_sequence_and_exports() {
+    // A.1.1 encryptions with further sequence numbers and exported values
+    let vector = Vector {
+        suite: Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes128Gcm },
+        mode: Mode::Base,
+        ikm_e: "7268600d403fce431561aef583ee1613527cff655c1343f29812e66706df3234",
+        ikm_r: "6db9df30aa07dd42ee5e8181afdb977e538f5e1fec8a06223f33f7013e525037",
+        ikm_s: None,
+        enc: "37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431",
+        ciphertext: "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a",
+    };
+    let expected = [
+        (1, "af2d7e9ac9ae7e270f46ba1f975be53c09f8d875bdc8535458c2494e8a6eab251c03d0c22a56b8ca42c2063b84"),
+        (2, "498dfcabd92e8acedc281e85af1cb4e3e31c7dc394a1ca20e173cb72516491588d96a19ad4a683518973dcc180"),
+        (4, "583bd32bc67a5994bb8ceaca813d369bca7b2a42408cddef5e22f880b631215a09fc0012bc69fccaa251c0246d"),
+        (255, "7175db9717964058640a3a11fb9007941a5d1757fda1a6935c805c21af32505bf106deefec4a49ac38d71c9e0a"),
+        (256, "957f9800542b0b8891badb026d79cc54597cb2d225b54c00c5238c25d05c30e3fbeda97d2e0e1aba483a2df9f2"),
+    ];
+
+    let (_, mut sender, mut recipient) = setup_vector(&vector);
+    for sequence in 0..=256 {
+        let associated_data = format!("Count-{}", sequence);
+        let ciphertext = sender.seal(associated_data.as_bytes(), PLAINTEXT).unwrap();
+        if let Some((_, expected_ciphertext)) = expected.iter().find(|(expected_sequence, _)| *expected_sequence == sequence) {
+            assert_eq!(ciphertext, from_hex(expected_ciphertext), "sequence {}", sequence);
+        }
+        assert_eq!(recipient.open(associated_data.as_bytes(), ciphertext.as_slice()).unwrap(), PLAINTEXT);
+    }
+
+    let exports = [
+        ("", "3853fe2b4035195a573ffc53856e77058e15d9ea064de3e59f4961d0095250ee"),
+        ("00", "2e8f0b54673c7029649d4eb9d5e33bf1872cf76d623ff164ac185da9e88c21a5"),
+        ("54657374436f6e74657874", "e9e43065102c3836401bed8c3c3c75ae46be1639869391d62c61f1ec7af54931"),
+    ];
+    for (exporter_context, exported) in exports {
+        assert_eq!(*sender.export(from_hex(exporter_context).as_slice(), 32).unwrap(), from_hex(exported));
+        assert_eq!(*recipient.export(from_hex(exporter_context).as_slice(), 32).unwrap(), from_hex(exported));
+    }
+}
+
+#[test]
+fn all_modes_and_suites() {
+    let info = "some info".as_bytes();
+    let psk = [7; 32];
+    let psk_id = "some psk id".as_bytes();
+
+    for suite in SUITES {
+        let (recipient_private_key, recipient_public_key) = generate_key_pair(suite.kem.algorithm()).unwrap();
+        let (sender_private_key, sender_public_key) = generate_key_pair(suite.kem.algorithm()).unwrap();
+
+        let contexts = [
+            {
+                let (enc, sender) = setup_base_sender(suite, &recipient_public_key, info).unwrap();
+                (sender, setup_base_recipient(suite, enc.as_slice(), &recipient_private_key, info).unwrap())
+            },
+            {
+                let (enc, sender) = setup_psk_sender(suite, &recipient_public_key, info, &psk, psk_id).unwrap();
+                (sender, setup_psk_recipient(suite, enc.as_slice(), &recipient_private_key, info, &psk, psk_id).unwrap())
+            },
+            {
+                let (enc, sender) = setup_auth_sender(suite, &recipient_public_key, info, &sender_private_key).unwrap();
+                (sender, setup_auth_recipient(suite, enc.as_slice(), &recipient_private_key, info, &sender_public_key).unwrap())
+            },
+            {
+                let (enc, sender) = setup_auth_psk_sender(suite, &recipient_public_key, info, &psk, psk_id, &sender_private_key).unwrap();
+                (sender, setup_auth_psk_recipient(suite, enc.as_slice(), &recipient_private_key, info, &psk, psk_id, &sender_public_key).unwrap())
+            },
+        ];
+
+        for (mut sender, mut recipient) in contexts {
+            assert_eq!(sender.suite(), suite);
+            assert_eq!(recipient.suite(), suite);
+
+            for length in [0, 1, 1000] {
+                let data = vec![length as u8; length];
+                let encrypted = sender.seal(&[1, 2, 3], data.as_slice()).unwrap();
+                assert_eq!(encrypted.len(), length + 16);
+                assert_eq!(recipient.open(&[1, 2, 3], encrypted.as_slice()).unwrap(), data);
+            }
+            assert_eq!(*sender.export(&[], 64).unwrap(), *recipient.export(&[], 64).unwrap());
+        }
+
+        let (enc, encrypted) = seal(suite, &recipient_public_key, info, &[], "some data".as_bytes()).unwrap();
+        assert_eq!(enc.len(), suite.kem.enc_length());
+        assert_eq!(open(suite, enc.as_slice(), &recipient_private_key, info, &[], encrypted.as_slice()).unwrap(), "some data".as_bytes());
+    }
+}
+
+#[test]
+fn wrong_keys_and_tampered_data_are_rejected() {
+    let suite = Suite::default();
+    let info = "some info".as_bytes();
+    let psk = [7; 32];
+    let psk_id = "some psk id".as_bytes();
+    let (recipient_private_key, recipient_public_key) = generate_key_pair(Algorithm::X25519).unwrap();
+    let (sender_private_key, sender_public_key) = generate_key_pair(Algorithm::X25519).unwrap();
+    let (other_private_key, other_public_key) = generate_key_pair(Algorithm::X25519).unwrap();
+
+    let (enc, encrypted) = seal(suite, &recipient_public_key, info, "header".as_bytes(), "some data".as_bytes()).unwrap();
+    let error = open(suite, enc.as_slice(), &other_private_key, info, "header".as_bytes(), encrypted.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+    let error = open(suite, enc.as_slice(), &recipient_private_key, "other info".as_bytes(), "header".as_bytes(), encrypted.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+    let error = open(suite, enc.as_slice(), &recipient_private_key, info, "other header".as_bytes(), encrypted.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+    let other_suite = Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::Aes256Gcm };
+    let error = open(other_suite, enc.as_slice(), &recipient_private_key, info, "header".as_bytes(), encrypted.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    for index in 0..encrypted.len() {
+        let mut tampered = encrypted.clone();
+        tampered[index] ^= 1;
+        let error = open(suite, enc.as_slice(), &recipient_private_key, info, "header".as_bytes(), tampered.as_slice()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }), "byte {}", index);
+    }
+
+    let error = open(suite, &enc[..31], &recipient_private_key, info, "header".as_bytes(), encrypted.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+    let error = open(suite, &[0; 32], &recipient_private_key, info, "header".as_bytes(), encrypted.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    // wrong pre-shared key or sender key derive another context
+    let (enc, mut sender) = setup_psk_sender(suite, &recipient_public_key, info, &psk, psk_id).unwrap();
+    let encrypted = sender.seal(&[], "some data".as_bytes()).unwrap();
+    let mut recipient = setup_psk_recipient(suite, enc.as_slice(), &recipient_private_key, info, &[8; 32], psk_id).unwrap();
+    assert!(matches!(recipient.open(&[], encrypted.as_slice()).err().unwrap(), Error::Authentication { .. }));
+    let mut recipient = setup_psk_recipient(suite, enc.as_slice(), &recipient_private_key, info, &psk, "other psk id".as_bytes()).unwrap();
+    assert!(matches!(recipient.open(&[], encrypted.as_slice()).err().unwrap(), Error::Authentication { .. }));
+
+    let (enc, mut sender) = setup_auth_sender(suite, &recipient_public_key, info, &sender_private_key).unwrap();
+    let encrypted = sender.seal(&[], "some data".as_bytes()).unwrap();
+    let mut recipient = setup_auth_recipient(suite, enc.as_slice(), &recipient_private_key, info, &other_public_key).unwrap();
+    assert!(matches!(recipient.open(&[], encrypted.as_slice()).err().unwrap(), Error::Authentication { .. }));
+    let mut recipient = setup_auth_recipient(suite, enc.as_slice(), &recipient_private_key, info, &sender_public_key).unwrap();
+    assert_eq!(recipient.open(&[], encrypted.as_slice()).unwrap(), "some data".as_bytes());
+
+    // pre-shared key is at least 32 bytes long and has an identifier
+    let error = setup_psk_sender(suite, &recipient_public_key, info, &[7; 31], psk_id).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = setup_psk_sender(suite, &recipient_public_key, info, &psk, &[]).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = setup_psk_recipient(suite, enc.as_slice(), &recipient_private_key, info, &[], &[]).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    // keys have to be of the suite key encapsulation mechanism
+    let (p256_private_key, p256_public_key) = generate_key_pair(Algorithm::EcdhP256).unwrap();
+    let error = setup_base_sender(suite, &p256_public_key, info).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = setup_base_recipient(suite, enc.as_slice(), &p256_private_key, info).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = setup_auth_sender(suite, &recipient_public_key, info, &p256_private_key).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let invalid_public_key = PublicKeyParameters { algorithm: Algorithm::EcdhP256, public_key: vec![4; 65] };
+    let p256_suite = Suite { kem: Kem::DhKemP256HkdfSha256, aead: Aead::Aes128Gcm };
+    let error = setup_base_sender(p256_suite, &invalid_public_key, info).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn failed_open_does_not_advance_context() {
+    let (recipient_private_key, recipient_public_key) = generate_key_pair(Algorithm::EcdhP256).unwrap();
+    let suite = Suite { kem: Kem::DhKemP256HkdfSha256, aead: Aead::ChaCha20Poly1305 };
+    let (enc, mut sender) = setup_base_sender(suite, &recipient_public_key, &[]).unwrap();
+    let mut recipient = setup_base_recipient(suite, enc.as_slice(), &recipient_private_key, &[]).unwrap();
+
+    let first = sender.seal(&[], "first".as_bytes()).unwrap();
+    let second = sender.seal(&[], "second".as_bytes()).unwrap();
+
+    // messages are opened in the order they were sealed
+    assert!(matches!(recipient.open(&[], second.as_slice()).err().unwrap(), Error::Authentication { .. }));
+    assert_eq!(recipient.open(&[], first.as_slice()).unwrap(), "first".as_bytes());
+    assert_eq!(recipient.open(&[], second.as_slice()).unwrap(), "second".as_bytes());
+
+    // the same message encrypts differently every time
+    assert_ne!(sender.seal(&[], "first".as_bytes()).unwrap(), first);
+}
+
+#[test]
+fn export_only_suite() {
+    let suite = Suite { kem: Kem::DhKemX25519HkdfSha256, aead: Aead::ExportOnly };
+    let (recipient_private_key, recipient_public_key) = generate_key_pair(Algorithm::X25519).unwrap();
+
+    let (enc, mut sender) = setup_base_sender(suite, &recipient_public_key, "some info".as_bytes()).unwrap();
+    let mut recipient = setup_base_recipient(suite, enc.as_slice(), &recipient_private_key, "some info".as_bytes()).unwrap();
+    assert_eq!(*sender.export("context".as_bytes(), 16).unwrap(), *recipient.export("context".as_bytes(), 16).unwrap());
+    assert_ne!(*sender.export("context".as_bytes(), 16).unwrap(), *sender.export("other context".as_bytes(), 16).unwrap());
+
+    let error = sender.seal(&[], "some data".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+    let error = recipient.open(&[], "some data".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+
+    let error = sender.export(&[], 255 * 32 + 1).err().unwrap();
+    assert!(matches!(error, Error::Crypto { .. }));
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn contexts_are_moved_between_threads() {
+    assert_send_sync::<SenderContext>();
+    assert_send_sync::<RecipientContext>();
+    assert_send_sync::<PrivateKeyParameters>();
+
+    let (recipient_private_key, recipient_public_key) = generate_key_pair(Algorithm::X25519).unwrap();
+    let (enc, mut sender) = setup_base_sender(Suite::default(), &recipient_public_key, &[]).unwrap();
+    let mut recipient = setup_base_recipient(Suite::default(), enc.as_slice(), &recipient_private_key, &[]).unwrap();
+
+    let encrypted = thread::spawn(move || sender.seal(&[], "some data".as_bytes()).unwrap()).join().unwrap();
+    let decrypted = thread::spawn(move || recipient.open(&[], encrypted.as_slice()).unwrap()).join().unwrap();
+    assert_eq!(decrypted, "some data".as_bytes());
+}
@@ -0,0 +1,574 @@
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use zeroize::Zeroize;
+use aes_gcm::aead::rand_core::OsRng;
+use crate::Error;
+use crate::kdf::hkdf;
+use crate::key_store::{read_or_create_key_pair, FileSystemKeyStore, KeyStore};
+use crate::secret::Secret;
+use crate::symmetric;
+
+#[cfg(test)]
+mod tests;
+
+const X25519_KEY_LENGTH: usize = 32;
+// magic of the key file, format version, algorithm identifier and raw key follow it
+const KEY_FILE_MAGIC: &[u8] = b"CXKA";
+
+/// Key agreement algorithm key pair belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// Diffie-Hellman on Curve25519 as defined in RFC 7748, keys are 32 bytes long
+    #[default]
+    X25519,
+    /// Elliptic curve Diffie-Hellman on NIST P-256 curve as defined in SP 800-56A, public keys are SEC1 encoded points
+    EcdhP256,
+}
+
+impl Algorithm {
+    /// Returns length of the raw shared secret in bytes
+    pub fn shared_secret_length(&self) -> usize {
+        32
+    }
+
+    /// Returns length of the generated public key in bytes, ECDH P-256 public keys are uncompressed points
+    pub fn public_key_length(&self) -> usize {
+        match self {
+            Algorithm::X25519 => X25519_KEY_LENGTH,
+            Algorithm::EcdhP256 => 65,
+        }
+    }
+
+    fn private_key_name(&self) -> &'static str {
+        match self {
+            Algorithm::X25519 => ".x25519_private_key",
+            Algorithm::EcdhP256 => ".ecdh_p256_private_key",
+        }
+    }
+
+    fn public_key_name(&self) -> &'static str {
+        match self {
+            Algorithm::X25519 => ".x25519_public_key",
+            Algorithm::EcdhP256 => ".ecdh_p256_public_key",
+        }
+    }
+
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Algorithm::X25519 => 1,
+            Algorithm::EcdhP256 => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Algorithm, Error> {
+        match id {
+            1 => Ok(Algorithm::X25519),
+            2 => Ok(Algorithm::EcdhP256),
+            _ => Err(Error::unsupported_algorithm(format!("unsupported key agreement algorithm identifier: {}", id))),
+        }
+    }
+}
+
+/// PrivateKeyParameters is basically the algorithm and raw private key
+///
+/// Private key is 32 bytes long scalar for both algorithms, big endian for ECDH P-256, it is wiped from memory on drop
+#[derive(Debug, Clone)]
+pub struct PrivateKeyParameters {
+    pub algorithm: Algorithm,
+    pub private_key: Secret<Vec<u8>>,
+}
+
+/// PublicKeyParameters is basically the algorithm and raw public key, it is sent to the other party
+///
+/// Public key is 32 bytes long u-coordinate for X25519 and SEC1 encoded point for ECDH P-256
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyParameters {
+    pub algorithm: Algorithm,
+    pub public_key: Vec<u8>,
+}
+
+/// Generates ephemeral key pair of the given algorithm, it is not stored anywhere and is meant to be used once
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::asymmetric::key_agreement::{derive_encryption_params, generate_key_pair, Algorithm};
+///     use crypto_ext::symmetric;
+///
+///     #[test]
+///     fn ephemeral_key_exchange() {
+///         // each party generates a key pair and sends the public key to the other one
+///         let (alice_private_params, alice_public_params) = generate_key_pair(Algorithm::X25519).unwrap();
+///         let (bob_private_params, bob_public_params) = generate_key_pair(Algorithm::X25519).unwrap();
+///
+///         let salt = "session salt".as_bytes();
+///         let info = "chat messages".as_bytes();
+///         let algorithm = symmetric::encryption::Algorithm::Aes256Gcm;
+///         let (encryption_params, _) = derive_encryption_params(&alice_private_params, &bob_public_params, salt, info, algorithm).unwrap();
+///         let (_, decryption_params) = derive_encryption_params(&bob_private_params, &alice_public_params, salt, info, algorithm).unwrap();
+///
+///         let encrypted = symmetric::encryption::encrypt(encryption_params, "hello Bob".as_bytes(), &[]).unwrap();
+///         let decrypted = symmetric::encryption::decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap();
+///         assert_eq!("hello Bob".as_bytes(), decrypted);
+///     }
+/// ```
+pub fn generate_key_pair(algorithm: Algorithm) -> Result<(PrivateKeyParameters, PublicKeyParameters), Error> {
+    let private_key = generate_private_key(algorithm);
+
+    let boxed_public_key = derive_public_key(algorithm, private_key.as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+
+    let private_key_parameters = PrivateKeyParameters {
+        algorithm,
+        private_key,
+    };
+
+    let public_key_parameters = PublicKeyParameters {
+        algorithm,
+        public_key: boxed_public_key.unwrap(),
+    };
+
+    Ok((private_key_parameters, public_key_parameters))
+}
+
+/// Will read or create static X25519 PrivateKeyParameters and PublicKeyParameters at the given location which is relative to the working directory
+pub fn setup(path_to_key_agreement_parameters: Option<&str>) -> Result<(PrivateKeyParameters, PublicKeyParameters), Error> {
+    setup_with_algorithm(path_to_key_agreement_parameters, Algorithm::default())
+}
+
+/// Will read or create static PrivateKeyParameters and PublicKeyParameters for the given algorithm at the given location which is relative to the working directory
+///
+/// Key pairs of different algorithms are stored in separate files and may share the same location
+pub fn setup_with_algorithm(path_to_key_agreement_parameters: Option<&str>, algorithm: Algorithm) -> Result<(PrivateKeyParameters, PublicKeyParameters), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_key_agreement_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    setup_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Will read or create static PrivateKeyParameters and PublicKeyParameters for the given algorithm in the given key store
+pub fn setup_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<(PrivateKeyParameters, PublicKeyParameters), Error> {
+    let generated_private_key = generate_private_key(algorithm);
+    let key_file = encode_key_file(algorithm, generated_private_key.as_slice());
+
+    let boxed_key_pair = read_or_create_key_pair(
+        store,
+        algorithm.private_key_name(),
+        key_file.as_slice(),
+        algorithm.public_key_name(),
+        |private_key_file| derive_public_key_file(algorithm, private_key_file),
+        |public_key_file, derived_public_key_file| is_same_public_key_file(algorithm, public_key_file, derived_public_key_file),
+    );
+    if boxed_key_pair.is_err() {
+        return Err(boxed_key_pair.err().unwrap());
+    }
+    let (private_key_file, public_key_file) = boxed_key_pair.unwrap();
+
+    let boxed_private_key = decode_key_file(algorithm, private_key_file.as_slice());
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
+    }
+    let private_key = boxed_private_key.unwrap();
+
+    let boxed_public_key = decode_key_file(algorithm, public_key_file.as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+    let public_key = boxed_public_key.unwrap().to_vec();
+
+    let private_key_parameters = PrivateKeyParameters {
+        algorithm,
+        private_key,
+    };
+
+    let public_key_parameters = PublicKeyParameters {
+        algorithm,
+        public_key,
+    };
+
+    Ok((private_key_parameters, public_key_parameters))
+}
+
+/// Returns PrivateKeyParameters stored at the given location which is relative to the working directory
+///
+/// If location holds key pairs of several algorithms, X25519 one is returned, use [get_private_key_params_with_algorithm] to pick another
+pub fn get_private_key_params(path_to_key_agreement_parameters: Option<&str>) -> Result<PrivateKeyParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_key_agreement_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    let store = boxed_store.unwrap();
+
+    let boxed_algorithm = detect_algorithm(&store);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+    get_private_key_params_with_store(&store, boxed_algorithm.unwrap())
+}
+
+/// Returns PrivateKeyParameters of the given algorithm stored at the given location which is relative to the working directory
+pub fn get_private_key_params_with_algorithm(path_to_key_agreement_parameters: Option<&str>, algorithm: Algorithm) -> Result<PrivateKeyParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_key_agreement_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_private_key_params_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Returns PrivateKeyParameters of the given algorithm stored in the given key store
+pub fn get_private_key_params_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<PrivateKeyParameters, Error> {
+    let boxed_key_file = store.read(algorithm.private_key_name());
+    if boxed_key_file.is_err() {
+        return Err(boxed_key_file.err().unwrap());
+    }
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    let boxed_private_key = decode_key_file(algorithm, key_file.as_slice());
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
+    }
+    let private_key = boxed_private_key.unwrap();
+
+    let boxed_validate = validate_private_key(algorithm, private_key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let params = PrivateKeyParameters {
+        algorithm,
+        private_key,
+    };
+
+    Ok(params)
+}
+
+/// Returns PublicKeyParameters stored at the given location which is relative to the working directory
+///
+/// If location holds key pairs of several algorithms, X25519 one is returned, use [get_public_key_params_with_algorithm] to pick another
+pub fn get_public_key_params(path_to_key_agreement_parameters: Option<&str>) -> Result<PublicKeyParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_key_agreement_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    let store = boxed_store.unwrap();
+
+    let boxed_algorithm = detect_algorithm(&store);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+    get_public_key_params_with_store(&store, boxed_algorithm.unwrap())
+}
+
+/// Returns PublicKeyParameters of the given algorithm stored at the given location which is relative to the working directory
+pub fn get_public_key_params_with_algorithm(path_to_key_agreement_parameters: Option<&str>, algorithm: Algorithm) -> Result<PublicKeyParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_key_agreement_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_public_key_params_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Returns PublicKeyParameters of the given algorithm stored in the given key store
+pub fn get_public_key_params_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<PublicKeyParameters, Error> {
+    let boxed_key_file = store.read(algorithm.public_key_name());
+    if boxed_key_file.is_err() {
+        return Err(boxed_key_file.err().unwrap());
+    }
+
+    let boxed_public_key = decode_key_file(algorithm, boxed_key_file.unwrap().as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+    let public_key = boxed_public_key.unwrap().to_vec();
+
+    let boxed_validate = validate_public_key(algorithm, public_key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let params = PublicKeyParameters {
+        algorithm,
+        public_key,
+    };
+
+    Ok(params)
+}
+
+/// Computes raw shared secret of own private key and public key of the other party, both parties get the same secret
+///
+/// Raw secret is not uniformly random and should not be used as a key directly, see [derive_encryption_params].
+/// Returns `Error::KeyMismatch` if keys belong to different algorithms and `Error::Format` if public key is invalid,
+/// including X25519 public keys of small order which would produce all-zero secret
+pub fn agree(private_key_params: &PrivateKeyParameters, public_key_params: &PublicKeyParameters) -> Result<Secret<Vec<u8>>, Error> {
+    if private_key_params.algorithm != public_key_params.algorithm {
+        let message = format!("private key is for {:?} algorithm, public key is for {:?}", private_key_params.algorithm, public_key_params.algorithm);
+        return Err(Error::key_mismatch(message))
+    }
+
+    let private_key = private_key_params.private_key.as_slice();
+    let public_key = public_key_params.public_key.as_slice();
+    match private_key_params.algorithm {
+        Algorithm::X25519 => agree_x25519(private_key, public_key),
+        Algorithm::EcdhP256 => agree_p256(private_key, public_key),
+    }
+}
+
+/// Derives symmetric encryption key from the shared secret of own private key and public key of the other party
+/// with HKDF-SHA256 as defined in RFC 5869
+///
+/// Both parties have to use the same salt and info. Info binds the key to its purpose, it is a good place
+/// for protocol name and identities of the parties. Salt may be empty, random salt sent along makes every derived key unique
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::asymmetric::key_agreement::{derive_encryption_params, generate_key_pair, setup, Algorithm};
+///     use crypto_ext::symmetric;
+///
+///     #[test]
+///     fn encryption_to_static_key() {
+///         // path needs to be accessible by user with write permission for initial setup
+///         let (_, server_public_params) = setup(Some("/test/key_agreement_parameters/")).unwrap();
+///
+///         // client uses ephemeral key pair and sends its public key along the encrypted data
+///         let (client_private_params, client_public_params) = generate_key_pair(Algorithm::X25519).unwrap();
+///         let algorithm = symmetric::encryption::Algorithm::ChaCha20Poly1305;
+///         let (encryption_params, _) = derive_encryption_params(&client_private_params, &server_public_params, &[], "upload".as_bytes(), algorithm).unwrap();
+///         let encrypted = symmetric::encryption::encrypt(encryption_params, "some data".as_bytes(), &[]).unwrap();
+///
+///         // server derives the same key from its static private key
+///         let (server_private_params, _) = setup(Some("/test/key_agreement_parameters/")).unwrap();
+///         let (_, decryption_params) = derive_encryption_params(&server_private_params, &client_public_params, &[], "upload".as_bytes(), algorithm).unwrap();
+///         let decrypted = symmetric::encryption::decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap();
+///         assert_eq!("some data".as_bytes(), decrypted);
+///     }
+/// ```
+pub fn derive_encryption_params(private_key_params: &PrivateKeyParameters, public_key_params: &PublicKeyParameters, salt: &[u8], info: &[u8], algorithm: symmetric::encryption::Algorithm) -> Result<(symmetric::encryption::EncryptionParameters, symmetric::encryption::DecryptionParameters), Error> {
+    let boxed_shared_secret = agree(private_key_params, public_key_params);
+    if boxed_shared_secret.is_err() {
+        return Err(boxed_shared_secret.err().unwrap());
+    }
+    let shared_secret = boxed_shared_secret.unwrap();
+
+    let boxed_key = hkdf::derive(hkdf::Algorithm::HkdfSha256, salt, shared_secret.as_slice(), info, algorithm.key_length());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let key = boxed_key.unwrap();
+
+    let encryption_params = symmetric::encryption::EncryptionParameters { algorithm, key: key.clone() };
+    let decryption_params = symmetric::encryption::DecryptionParameters { algorithm, key };
+    Ok((encryption_params, decryption_params))
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+fn agree_x25519(private_key: &[u8], public_key: &[u8]) -> Result<Secret<Vec<u8>>, Error> {
+    let boxed_secret = parse_x25519_private_key(private_key);
+    if boxed_secret.is_err() {
+        return Err(boxed_secret.err().unwrap());
+    }
+
+    let boxed_public_key = parse_x25519_public_key(public_key);
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+
+    // shared secret wipes itself on drop
+    let shared_secret = boxed_secret.unwrap().diffie_hellman(&boxed_public_key.unwrap());
+    if !shared_secret.was_contributory() {
+        return Err(Error::format("X25519 public key is a point of small order"));
+    }
+    Ok(Secret::new(shared_secret.as_bytes().to_vec()))
+}
+
+fn agree_p256(private_key: &[u8], public_key: &[u8]) -> Result<Secret<Vec<u8>>, Error> {
+    let boxed_secret_key = parse_p256_private_key(private_key);
+    if boxed_secret_key.is_err() {
+        return Err(boxed_secret_key.err().unwrap());
+    }
+
+    let boxed_public_key = parse_p256_public_key(public_key);
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+
+    let shared_secret = p256::ecdh::diffie_hellman(boxed_secret_key.unwrap().to_nonzero_scalar(), boxed_public_key.unwrap().as_affine());
+    Ok(Secret::new(shared_secret.raw_secret_bytes().to_vec()))
+}
+
+fn generate_private_key(algorithm: Algorithm) -> Secret<Vec<u8>> {
+    // secret keys wipe themselves on drop, copies of their bytes are wiped here
+    match algorithm {
+        Algorithm::X25519 => {
+            let mut bytes = x25519_dalek::StaticSecret::random_from_rng(OsRng).to_bytes();
+            let private_key = Secret::new(bytes.to_vec());
+            bytes.zeroize();
+            private_key
+        }
+        Algorithm::EcdhP256 => {
+            let mut bytes = p256::SecretKey::random(&mut OsRng).to_be_bytes();
+            let private_key = Secret::new(bytes.to_vec());
+            bytes.as_mut_slice().zeroize();
+            private_key
+        }
+    }
+}
+
+pub(crate) fn derive_public_key(algorithm: Algorithm, private_key: &[u8]) -> Result<Vec<u8>, Error> {
+    match algorithm {
+        Algorithm::X25519 => {
+            let boxed_secret = parse_x25519_private_key(private_key);
+            if boxed_secret.is_err() {
+                return Err(boxed_secret.err().unwrap());
+            }
+            Ok(x25519_dalek::PublicKey::from(&boxed_secret.unwrap()).as_bytes().to_vec())
+        }
+        Algorithm::EcdhP256 => {
+            let boxed_secret_key = parse_p256_private_key(private_key);
+            if boxed_secret_key.is_err() {
+                return Err(boxed_secret_key.err().unwrap());
+            }
+            Ok(boxed_secret_key.unwrap().public_key().to_encoded_point(false).as_bytes().to_vec())
+        }
+    }
+}
+
+// public key as generated for the algorithm, ECDH P-256 compressed point is converted to uncompressed one
+pub(crate) fn canonical_public_key(algorithm: Algorithm, public_key: &[u8]) -> Result<Vec<u8>, Error> {
+    match algorithm {
+        Algorithm::X25519 => {
+            let boxed_public_key = parse_x25519_public_key(public_key);
+            if boxed_public_key.is_err() {
+                return Err(boxed_public_key.err().unwrap());
+            }
+            Ok(boxed_public_key.unwrap().as_bytes().to_vec())
+        }
+        Algorithm::EcdhP256 => {
+            let boxed_public_key = parse_p256_public_key(public_key);
+            if boxed_public_key.is_err() {
+                return Err(boxed_public_key.err().unwrap());
+            }
+            Ok(boxed_public_key.unwrap().to_encoded_point(false).as_bytes().to_vec())
+        }
+    }
+}
+
+fn detect_algorithm(store: &dyn KeyStore) -> Result<Algorithm, Error> {
+    let algorithms = [Algorithm::X25519, Algorithm::EcdhP256];
+    for algorithm in algorithms {
+        let boxed_private_key_exists = store.contains(algorithm.private_key_name());
+        if boxed_private_key_exists.is_err() {
+            return Err(boxed_private_key_exists.err().unwrap());
+        }
+
+        let boxed_public_key_exists = store.contains(algorithm.public_key_name());
+        if boxed_public_key_exists.is_err() {
+            return Err(boxed_public_key_exists.err().unwrap());
+        }
+
+        if boxed_private_key_exists.unwrap() || boxed_public_key_exists.unwrap() {
+            return Ok(algorithm);
+        }
+    }
+
+    // nothing is stored, reading default algorithm files will report missing file
+    Ok(Algorithm::default())
+}
+
+fn encode_key_file(algorithm: Algorithm, key: &[u8]) -> Secret<Vec<u8>> {
+    crate::encode_key_file(KEY_FILE_MAGIC, algorithm.id(), key)
+}
+
+fn decode_key_file(algorithm: Algorithm, key_file: &[u8]) -> Result<Secret<Vec<u8>>, Error> {
+    let boxed_key = crate::decode_key_file(KEY_FILE_MAGIC, "key agreement", key_file, Algorithm::from_id);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (stored_algorithm, key) = boxed_key.unwrap();
+
+    if stored_algorithm != algorithm {
+        let message = format!("stored key is for {:?} algorithm, requested {:?}", stored_algorithm, algorithm);
+        return Err(Error::key_mismatch(message))
+    }
+
+    Ok(key)
+}
+
+fn derive_public_key_file(algorithm: Algorithm, private_key_file: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_private_key = decode_key_file(algorithm, private_key_file);
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
+    }
+
+    let boxed_public_key = derive_public_key(algorithm, boxed_private_key.unwrap().as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+    Ok(encode_key_file(algorithm, boxed_public_key.unwrap().as_slice()).to_vec())
+}
+
+// key files of the same algorithm are equal if their keys are
+fn is_same_public_key_file(algorithm: Algorithm, public_key_file: &[u8], other_public_key_file: &[u8]) -> Result<bool, Error> {
+    let boxed_public_key = decode_key_file(algorithm, public_key_file);
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+    Ok(public_key_file == other_public_key_file)
+}
+
+fn validate_private_key(algorithm: Algorithm, private_key: &[u8]) -> Result<(), Error> {
+    match algorithm {
+        Algorithm::X25519 => parse_x25519_private_key(private_key).map(|_| ()),
+        Algorithm::EcdhP256 => parse_p256_private_key(private_key).map(|_| ()),
+    }
+}
+
+fn validate_public_key(algorithm: Algorithm, public_key: &[u8]) -> Result<(), Error> {
+    match algorithm {
+        Algorithm::X25519 => parse_x25519_public_key(public_key).map(|_| ()),
+        Algorithm::EcdhP256 => parse_p256_public_key(public_key).map(|_| ()),
+    }
+}
+
+fn parse_x25519_private_key(private_key: &[u8]) -> Result<x25519_dalek::StaticSecret, Error> {
+    let boxed_secret: Result<[u8; X25519_KEY_LENGTH], _> = private_key.try_into();
+    if boxed_secret.is_err() {
+        let message = format!("invalid X25519 private key length: {} bytes, expected {} bytes", private_key.len(), X25519_KEY_LENGTH);
+        return Err(Error::format(message));
+    }
+    let mut secret = boxed_secret.unwrap();
+
+    let static_secret = x25519_dalek::StaticSecret::from(secret);
+    secret.zeroize();
+    Ok(static_secret)
+}
+
+fn parse_x25519_public_key(public_key: &[u8]) -> Result<x25519_dalek::PublicKey, Error> {
+    let boxed_public_key: Result<[u8; X25519_KEY_LENGTH], _> = public_key.try_into();
+    if boxed_public_key.is_err() {
+        let message = format!("invalid X25519 public key length: {} bytes, expected {} bytes", public_key.len(), X25519_KEY_LENGTH);
+        return Err(Error::format(message));
+    }
+    Ok(x25519_dalek::PublicKey::from(boxed_public_key.unwrap()))
+}
+
+fn parse_p256_private_key(private_key: &[u8]) -> Result<p256::SecretKey, Error> {
+    let boxed_secret_key = p256::SecretKey::from_be_bytes(private_key);
+    if boxed_secret_key.is_err() {
+        return Err(Error::format_from("unable to parse ECDH P-256 private key", boxed_secret_key.err().unwrap()));
+    }
+    Ok(boxed_secret_key.unwrap())
+}
+
+// compressed points are accepted as well
+fn parse_p256_public_key(public_key: &[u8]) -> Result<p256::PublicKey, Error> {
+    let boxed_public_key = p256::PublicKey::from_sec1_bytes(public_key);
+    if boxed_public_key.is_err() {
+        return Err(Error::format_from("ECDH public key is not a valid SEC1 encoded P-256 point", boxed_public_key.err().unwrap()));
+    }
+    Ok(boxed_public_key.unwrap())
+}
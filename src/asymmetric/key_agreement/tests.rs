@@ -0,0 +1,289 @@
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use crate::Error;
+use crate::asymmetric::key_agreement::{agree, decode_key_file, derive_encryption_params, encode_key_file, generate_key_pair, get_private_key_params, get_private_key_params_with_algorithm, get_public_key_params, get_public_key_params_with_algorithm, get_public_key_params_with_store, setup, setup_with_algorithm, setup_with_store, Algorithm, PrivateKeyParameters, PublicKeyParameters};
+use crate::key_store::{KeyStore, MemoryKeyStore};
+use crate::symmetric;
+use crate::test_util::from_hex;
+
+const ALGORITHMS: [Algorithm; 2] = [Algorithm::X25519, Algorithm::EcdhP256];
+
+fn x25519(scalar: &[u8], u: &[u8]) -> Vec<u8> {
+    let private_key_params = PrivateKeyParameters { algorithm: Algorithm::X25519, private_key: scalar.to_vec().into() };
+    let public_key_params = PublicKeyParameters { algorithm: Algorithm::X25519, public_key: u.to_vec() };
+    agree(&private_key_params, &public_key_params).unwrap().to_vec()
+}
+
+#[test]
+fn rfc7748_scalar_multiplication() {
+    // RFC 7748 section 5.2, scalar, input u-coordinate and output u-coordinate
+    let vectors = [
+        (
+            "a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4",
+            "e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c",
+            "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552",
+        ),
+        (
+            "4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d",
+            "e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493",
+            "95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957",
+        ),
+    ];
+
+    for (scalar, u, expected) in vectors {
+        assert_eq!(x25519(from_hex(scalar).as_slice(), from_hex(u).as_slice()), from_hex(expected));
+    }
+
+    // iterated vector, result of each step becomes the scalar and the previous scalar becomes the input
+    let mut scalar = from_hex("0900000000000000000000000000000000000000000000000000000000000000");
+    let mut u = scalar.clone();
+    for iteration in 1..=1000 {
+        let result = x25519(scalar.as_slice(), u.as_slice());
+        u = scalar;
+        scalar = result;
+
+        if iteration == 1 {
+            assert_eq!(scalar, from_hex("422c8e7a6227d7bca1350b3e2bb7279f7897b87bb6854b783c60e80311ae3079"));
+        }
+    }
+    assert_eq!(scalar, from_hex("684cf59ba83309552800ef566f2f4d3c1c3887c49360e3875f2eb94d99532c51"));
+}
+
+#[test]
+fn rfc7748_diffie_hellman() {
+    // RFC 7748 section 6.1
+    let alice_private_key = from_hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+    let alice_public_key = from_hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+    let bob_private_key = from_hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+    let bob_public_key = from_hex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+    let shared_secret = from_hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+    let store = MemoryKeyStore::new();
+    store.read_or_create(".x25519_private_key", encode_key_file(Algorithm::X25519, alice_private_key.as_slice()).as_slice()).unwrap();
+    let (alice_private_params, alice_public_params) = setup_with_store(&store, Algorithm::X25519).unwrap();
+    assert_eq!(alice_public_params.public_key, alice_public_key);
+
+    let bob_private_params = PrivateKeyParameters { algorithm: Algorithm::X25519, private_key: bob_private_key.into() };
+    let bob_public_params = PublicKeyParameters { algorithm: Algorithm::X25519, public_key: bob_public_key };
+
+    assert_eq!(*agree(&alice_private_params, &bob_public_params).unwrap(), shared_secret);
+    assert_eq!(*agree(&bob_private_params, &alice_public_params).unwrap(), shared_secret);
+
+    // key is derived with HKDF-SHA256 from the shared secret
+    let algorithm = symmetric::encryption::Algorithm::Aes256Gcm;
+    let (encryption_params, decryption_params) = derive_encryption_params(&alice_private_params, &bob_public_params, "some salt".as_bytes(), "some info".as_bytes(), algorithm).unwrap();
+    assert_eq!(encryption_params.algorithm, algorithm);
+    assert_eq!(*encryption_params.key, from_hex("a550faa48a69d7f6f539ec090855b04fdc43b269d71741208eb91c756b0cadcb"));
+    assert_eq!(*decryption_params.key, *encryption_params.key);
+
+    let algorithm = symmetric::encryption::Algorithm::Aes128Gcm;
+    let (encryption_params, _) = derive_encryption_params(&bob_private_params, &alice_public_params, "some salt".as_bytes(), "some info".as_bytes(), algorithm).unwrap();
+    assert_eq!(*encryption_params.key, from_hex("a550faa48a69d7f6f539ec090855b04f"));
+}
+
+#[test]
+fn rfc5903_ecdh_p256() {
+    // RFC 5903 section 8.1, public keys are concatenated coordinates prefixed with SEC1 uncompressed point tag
+    let initiator_private_key = from_hex("c88f01f510d9ac3f70a292daa2316de544e9aab8afe84049c62a9c57862d1433");
+    let initiator_public_key = from_hex("04dad0b65394221cf9b051e1feca5787d098dfe637fc90b9ef945d0c37725811805271a0461cdb8252d61f1c456fa3e59ab1f45b33accf5f58389e0577b8990bb3");
+    let responder_private_key = from_hex("c6ef9c5d78ae012a011164acb397ce2088685d8f06bf9be0b283ab46476bee53");
+    let responder_public_key = from_hex("04d12dfb5289c8d4f81208b70270398c342296970a0bccb74c736fc7554494bf6356fbf3ca366cc23e8157854c13c58d6aac23f046ada30f8353e74f33039872ab");
+    let shared_secret = from_hex("d6840f6b42f6edafd13116e0e12565202fef8e9ece7dce03812464d04b9442de");
+
+    let store = MemoryKeyStore::new();
+    store.read_or_create(".ecdh_p256_private_key", encode_key_file(Algorithm::EcdhP256, initiator_private_key.as_slice()).as_slice()).unwrap();
+    let (initiator_private_params, initiator_public_params) = setup_with_store(&store, Algorithm::EcdhP256).unwrap();
+    assert_eq!(initiator_public_params.public_key, initiator_public_key);
+
+    let responder_private_params = PrivateKeyParameters { algorithm: Algorithm::EcdhP256, private_key: responder_private_key.into() };
+    let responder_public_params = PublicKeyParameters { algorithm: Algorithm::EcdhP256, public_key: responder_public_key.clone() };
+
+    assert_eq!(*agree(&initiator_private_params, &responder_public_params).unwrap(), shared_secret);
+    assert_eq!(*agree(&responder_private_params, &initiator_public_params).unwrap(), shared_secret);
+
+    // compressed point of the same public key gives the same secret
+    let mut compressed_public_key = vec![0x02 + (responder_public_key[64] & 1)];
+    compressed_public_key.extend_from_slice(&responder_public_key[1..33]);
+    let compressed_public_params = PublicKeyParameters { algorithm: Algorithm::EcdhP256, public_key: compressed_public_key };
+    assert_eq!(*agree(&initiator_private_params, &compressed_public_params).unwrap(), shared_secret);
+}
+
+#[test]
+fn ephemeral_key_pairs() {
+    for algorithm in ALGORITHMS {
+        let (alice_private_params, alice_public_params) = generate_key_pair(algorithm).unwrap();
+        let (bob_private_params, bob_public_params) = generate_key_pair(algorithm).unwrap();
+        assert_eq!(alice_private_params.algorithm, algorithm);
+        assert_eq!(alice_public_params.algorithm, algorithm);
+        assert_ne!(*alice_private_params.private_key, *bob_private_params.private_key);
+
+        let alice_secret = agree(&alice_private_params, &bob_public_params).unwrap();
+        let bob_secret = agree(&bob_private_params, &alice_public_params).unwrap();
+        assert_eq!(alice_secret.len(), algorithm.shared_secret_length());
+        assert_eq!(*alice_secret, *bob_secret);
+
+        let data = "some data to encrypt".as_bytes();
+        let associated_data = "some unencrypted data".as_bytes();
+        for symmetric_algorithm in [symmetric::encryption::Algorithm::Aes256Gcm, symmetric::encryption::Algorithm::XChaCha20Poly1305] {
+            let (encryption_params, _) = derive_encryption_params(&alice_private_params, &bob_public_params, &[], "info".as_bytes(), symmetric_algorithm).unwrap();
+            let (_, decryption_params) = derive_encryption_params(&bob_private_params, &alice_public_params, &[], "info".as_bytes(), symmetric_algorithm).unwrap();
+            assert_eq!(encryption_params.key.len(), symmetric_algorithm.key_length());
+
+            let encrypted = symmetric::encryption::encrypt(encryption_params, data, associated_data).unwrap();
+            let decrypted = symmetric::encryption::decrypt(decryption_params, encrypted.as_slice(), associated_data).unwrap();
+            assert_eq!(data, decrypted);
+        }
+
+        // salt and info separate derived keys
+        let symmetric_algorithm = symmetric::encryption::Algorithm::Aes256Gcm;
+        let (key, _) = derive_encryption_params(&alice_private_params, &bob_public_params, &[], "info".as_bytes(), symmetric_algorithm).unwrap();
+        let (other_info_key, _) = derive_encryption_params(&alice_private_params, &bob_public_params, &[], "other info".as_bytes(), symmetric_algorithm).unwrap();
+        let (other_salt_key, _) = derive_encryption_params(&alice_private_params, &bob_public_params, "salt".as_bytes(), "info".as_bytes(), symmetric_algorithm).unwrap();
+        assert_ne!(*key.key, *other_info_key.key);
+        assert_ne!(*key.key, *other_salt_key.key);
+    }
+}
+
+#[test]
+fn invalid_keys_are_rejected() {
+    let (x25519_private_params, x25519_public_params) = generate_key_pair(Algorithm::X25519).unwrap();
+    let (p256_private_params, p256_public_params) = generate_key_pair(Algorithm::EcdhP256).unwrap();
+
+    let error = agree(&x25519_private_params, &p256_public_params).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = agree(&p256_private_params, &x25519_public_params).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    // points of small order on Curve25519 give all-zero secret
+    let small_order_points = [
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0100000000000000000000000000000000000000000000000000000000000000",
+        "e0eb7a7c3b41b8ae1656e3faf19fc46ada098deb9c32b1fd866205165f49b800",
+        "ecffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+    ];
+    for point in small_order_points {
+        let public_key_params = PublicKeyParameters { algorithm: Algorithm::X25519, public_key: from_hex(point) };
+        let error = agree(&x25519_private_params, &public_key_params).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }), "{}", point);
+    }
+
+    let invalid_public_keys = [
+        (Algorithm::X25519, vec![9; 31]),
+        (Algorithm::X25519, vec![9; 33]),
+        (Algorithm::EcdhP256, vec![]),
+        (Algorithm::EcdhP256, vec![0]),
+        (Algorithm::EcdhP256, [&[4], &[1; 64][..]].concat()),
+        (Algorithm::EcdhP256, x25519_public_params.public_key.clone()),
+    ];
+    for (algorithm, public_key) in invalid_public_keys {
+        let private_key_params = if algorithm == Algorithm::X25519 { &x25519_private_params } else { &p256_private_params };
+        let public_key_params = PublicKeyParameters { algorithm, public_key };
+        let error = agree(private_key_params, &public_key_params).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }));
+    }
+
+    let invalid_private_keys = [
+        (Algorithm::X25519, vec![1; 16]),
+        (Algorithm::EcdhP256, vec![0; 32]),
+        (Algorithm::EcdhP256, vec![0xff; 32]),
+    ];
+    for (algorithm, private_key) in invalid_private_keys {
+        let private_key_params = PrivateKeyParameters { algorithm, private_key: private_key.into() };
+        let public_key_params = if algorithm == Algorithm::X25519 { &x25519_public_params } else { &p256_public_params };
+        let error = agree(&private_key_params, public_key_params).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }));
+
+        let error = derive_encryption_params(&private_key_params, public_key_params, &[], &[], symmetric::encryption::Algorithm::Aes256Gcm).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }));
+    }
+}
+
+#[test]
+fn setup_and_get_params() {
+    let path_to_params = "/test/key_agreement_parameters_setup/";
+
+    let (private_key_params, public_key_params) = setup(Some(path_to_params)).unwrap();
+    assert_eq!(private_key_params.algorithm, Algorithm::X25519);
+    assert_eq!(public_key_params.public_key.len(), 32);
+
+    // stored key pair is reused
+    let (_, stored_public_key_params) = setup(Some(path_to_params)).unwrap();
+    assert_eq!(stored_public_key_params, public_key_params);
+    assert_eq!(*get_private_key_params(Some(path_to_params)).unwrap().private_key, *private_key_params.private_key);
+    assert_eq!(get_public_key_params(Some(path_to_params)).unwrap(), public_key_params);
+
+    // key pairs of different algorithms share the location
+    let (p256_private_key_params, p256_public_key_params) = setup_with_algorithm(Some(path_to_params), Algorithm::EcdhP256).unwrap();
+    assert_eq!(p256_public_key_params.public_key.len(), 65);
+    let stored_private_key_params = get_private_key_params_with_algorithm(Some(path_to_params), Algorithm::EcdhP256).unwrap();
+    assert_eq!(*stored_private_key_params.private_key, *p256_private_key_params.private_key);
+    assert_eq!(get_public_key_params_with_algorithm(Some(path_to_params), Algorithm::EcdhP256).unwrap(), p256_public_key_params);
+    assert_eq!(get_public_key_params(Some(path_to_params)).unwrap().algorithm, Algorithm::X25519);
+
+    fs::remove_dir_all("test/key_agreement_parameters_setup").unwrap();
+
+    let error = get_public_key_params(Some("/test/missing_key_agreement_parameters/")).err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+}
+
+#[test]
+fn key_file_format() {
+    for algorithm in ALGORITHMS {
+        let (private_key_params, _) = generate_key_pair(algorithm).unwrap();
+        let key_file = encode_key_file(algorithm, private_key_params.private_key.as_slice());
+        assert!(key_file.starts_with("CXKA".as_bytes()));
+        assert_eq!(*decode_key_file(algorithm, key_file.as_slice()).unwrap(), *private_key_params.private_key);
+    }
+
+    let key_file = encode_key_file(Algorithm::X25519, vec![9; 32].as_slice());
+    let error = decode_key_file(Algorithm::EcdhP256, key_file.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let mut unknown_algorithm = key_file.to_vec();
+    unknown_algorithm[5] = 0xFF;
+    let error = decode_key_file(Algorithm::X25519, unknown_algorithm.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+
+    let mut unknown_version = key_file.to_vec();
+    unknown_version[4] = 2;
+    let error = decode_key_file(Algorithm::X25519, unknown_version.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let error = decode_key_file(Algorithm::X25519, "not a key".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    // stored public key has to be valid and belong to the stored private key
+    let store = MemoryKeyStore::new();
+    store.read_or_create(".ecdh_p256_public_key", encode_key_file(Algorithm::EcdhP256, &[4; 65]).as_slice()).unwrap();
+    let error = get_public_key_params_with_store(&store, Algorithm::EcdhP256).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+    let error = setup_with_store(&store, Algorithm::EcdhP256).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn key_pairs_are_shared_between_threads() {
+    assert_send_sync::<PrivateKeyParameters>();
+    assert_send_sync::<PublicKeyParameters>();
+
+    let (private_key_params, public_key_params) = setup_with_store(&MemoryKeyStore::new(), Algorithm::X25519).unwrap();
+    let private_key_params = Arc::new(private_key_params);
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let private_key_params = Arc::clone(&private_key_params);
+            let public_key_params = public_key_params.clone();
+            thread::spawn(move || {
+                let (ephemeral_private_params, ephemeral_public_params) = generate_key_pair(Algorithm::X25519).unwrap();
+                let secret = agree(&ephemeral_private_params, &public_key_params).unwrap();
+                assert_eq!(*agree(&private_key_params, &ephemeral_public_params).unwrap(), *secret);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
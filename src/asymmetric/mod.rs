@@ -1,2 +1,5 @@
 pub mod encryption;
-pub mod signing;
\ No newline at end of file
+pub mod signing;
+pub mod key_agreement;
+pub mod ecies;
+pub mod hpke;
\ No newline at end of file
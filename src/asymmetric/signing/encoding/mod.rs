@@ -0,0 +1,284 @@
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, EcPoint, PointConversionForm};
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private, Public};
+use openssl::symm::Cipher;
+use crate::asymmetric::signing::{Algorithm, KeyEncoding};
+use crate::Error;
+use crate::secret::Secret;
+
+// keys written by earlier versions: bare 32 bytes long scalar or seed for private key,
+// SEC1 encoded point for ECDSA public key and bare 32 bytes for Ed25519 public key
+const RAW_PRIVATE_KEY_LENGTH: usize = 32;
+const RAW_ED25519_PUBLIC_KEY_LENGTH: usize = 32;
+const RAW_ECDSA_UNCOMPRESSED_PUBLIC_KEY_LENGTH: usize = 65;
+const RAW_ECDSA_COMPRESSED_PUBLIC_KEY_LENGTH: usize = 33;
+const PEM_PREFIX: &[u8] = b"-----BEGIN";
+
+/// Encodes raw private key as PKCS#8, encrypted with the passphrase if given
+pub(crate) fn encode_private_key(algorithm: Algorithm, private_key: &[u8], encoding: KeyEncoding, passphrase: Option<&str>) -> Result<Vec<u8>, Error> {
+    let boxed_key = private_key_from_raw(algorithm, private_key);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let key = boxed_key.unwrap();
+
+    // scrypt is not available for PKCS#8 encryption in all OpenSSL builds, PBES2 with AES-256-CBC is used instead
+    let boxed_encoded = match (encoding, passphrase) {
+        (KeyEncoding::Der, None) => key.private_key_to_pkcs8(),
+        (KeyEncoding::Der, Some(passphrase)) => key.private_key_to_pkcs8_passphrase(Cipher::aes_256_cbc(), passphrase.as_bytes()),
+        (KeyEncoding::Pem, None) => key.private_key_to_pem_pkcs8(),
+        (KeyEncoding::Pem, Some(passphrase)) => key.private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), passphrase.as_bytes()),
+    };
+    if boxed_encoded.is_err() {
+        return Err(Error::crypto_from("unable to encode private key as PKCS#8", boxed_encoded.err().unwrap()));
+    }
+    Ok(boxed_encoded.unwrap())
+}
+
+/// Encodes raw public key as SubjectPublicKeyInfo
+pub(crate) fn encode_public_key(algorithm: Algorithm, public_key: &[u8], encoding: KeyEncoding) -> Result<Vec<u8>, Error> {
+    let boxed_key = public_key_from_raw(algorithm, public_key);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let key = boxed_key.unwrap();
+
+    let boxed_encoded = match encoding {
+        KeyEncoding::Der => key.public_key_to_der(),
+        KeyEncoding::Pem => key.public_key_to_pem(),
+    };
+    if boxed_encoded.is_err() {
+        return Err(Error::crypto_from("unable to encode public key as SubjectPublicKeyInfo", boxed_encoded.err().unwrap()));
+    }
+    Ok(boxed_encoded.unwrap())
+}
+
+/// Decodes PKCS#8 private key in DER or PEM, optionally encrypted, or raw private key written by earlier versions
+pub(crate) fn decode_private_key(algorithm: Algorithm, encoded: &[u8], passphrase: Option<&str>) -> Result<Secret<Vec<u8>>, Error> {
+    if encoded.len() == RAW_PRIVATE_KEY_LENGTH {
+        return Ok(Secret::new(encoded.to_vec()));
+    }
+
+    let is_pem = encoded.starts_with(PEM_PREFIX);
+    let boxed_key = match (is_pem, passphrase) {
+        (false, None) => PKey::private_key_from_pkcs8(encoded),
+        (false, Some(passphrase)) => PKey::private_key_from_pkcs8_passphrase(encoded, passphrase.as_bytes()),
+        // default callback of OpenSSL would prompt for passphrase on the terminal
+        (true, None) => PKey::private_key_from_pem_callback(encoded, |_| Ok(0)),
+        (true, Some(passphrase)) => PKey::private_key_from_pem_passphrase(encoded, passphrase.as_bytes()),
+    };
+    if boxed_key.is_err() {
+        let message = if passphrase.is_some() {
+            "unable to decode PKCS#8 private key, passphrase is wrong or key is malformed"
+        } else {
+            "unable to decode PKCS#8 private key, it is malformed or encrypted with passphrase"
+        };
+        return Err(Error::format_from(message, boxed_key.err().unwrap()));
+    }
+    let key = boxed_key.unwrap();
+
+    match algorithm {
+        Algorithm::EcdsaP256 => {
+            let boxed_ec_key = ec_key_of(&key);
+            if boxed_ec_key.is_err() {
+                return Err(boxed_ec_key.err().unwrap());
+            }
+            let ec_key = boxed_ec_key.unwrap();
+
+            let boxed_scalar = ec_key.private_key().to_vec_padded(RAW_PRIVATE_KEY_LENGTH as i32);
+            if boxed_scalar.is_err() {
+                return Err(Error::format_from("ECDSA private key scalar is out of range", boxed_scalar.err().unwrap()));
+            }
+            Ok(Secret::new(boxed_scalar.unwrap()))
+        }
+        Algorithm::Ed25519 => {
+            if key.id() != Id::ED25519 {
+                return Err(Error::key_mismatch("private key is not an Ed25519 key"));
+            }
+            let boxed_raw = key.raw_private_key();
+            if boxed_raw.is_err() {
+                return Err(Error::format_from("unable to read Ed25519 private key", boxed_raw.err().unwrap()));
+            }
+            Ok(Secret::new(boxed_raw.unwrap()))
+        }
+    }
+}
+
+/// Decodes SubjectPublicKeyInfo public key in DER or PEM, or raw public key written by earlier versions
+///
+/// ECDSA public key is returned as uncompressed SEC1 point
+pub(crate) fn decode_public_key(algorithm: Algorithm, encoded: &[u8]) -> Result<Vec<u8>, Error> {
+    let is_raw = match algorithm {
+        Algorithm::EcdsaP256 => encoded.len() == RAW_ECDSA_UNCOMPRESSED_PUBLIC_KEY_LENGTH || encoded.len() == RAW_ECDSA_COMPRESSED_PUBLIC_KEY_LENGTH,
+        Algorithm::Ed25519 => encoded.len() == RAW_ED25519_PUBLIC_KEY_LENGTH,
+    };
+    if is_raw {
+        return Ok(encoded.to_vec());
+    }
+
+    let boxed_key = if encoded.starts_with(PEM_PREFIX) {
+        PKey::public_key_from_pem(encoded)
+    } else {
+        PKey::public_key_from_der(encoded)
+    };
+    if boxed_key.is_err() {
+        return Err(Error::format_from("unable to decode SubjectPublicKeyInfo public key", boxed_key.err().unwrap()));
+    }
+    let key = boxed_key.unwrap();
+
+    match algorithm {
+        Algorithm::EcdsaP256 => {
+            let boxed_ec_key = ec_key_of(&key);
+            if boxed_ec_key.is_err() {
+                return Err(boxed_ec_key.err().unwrap());
+            }
+            let ec_key = boxed_ec_key.unwrap();
+
+            let boxed_context = BigNumContext::new();
+            if boxed_context.is_err() {
+                return Err(Error::crypto_from("unable to allocate big number context", boxed_context.err().unwrap()));
+            }
+            let mut context = boxed_context.unwrap();
+
+            let boxed_point = ec_key.public_key().to_bytes(ec_key.group(), PointConversionForm::UNCOMPRESSED, &mut context);
+            if boxed_point.is_err() {
+                return Err(Error::format_from("unable to encode ECDSA public key point", boxed_point.err().unwrap()));
+            }
+            Ok(boxed_point.unwrap())
+        }
+        Algorithm::Ed25519 => {
+            if key.id() != Id::ED25519 {
+                return Err(Error::key_mismatch("public key is not an Ed25519 key"));
+            }
+            let boxed_raw = key.raw_public_key();
+            if boxed_raw.is_err() {
+                return Err(Error::format_from("unable to read Ed25519 public key", boxed_raw.err().unwrap()));
+            }
+            Ok(boxed_raw.unwrap())
+        }
+    }
+}
+
+fn ec_key_of<T: openssl::pkey::HasPublic>(key: &PKey<T>) -> Result<EcKey<T>, Error> {
+    if key.id() != Id::EC {
+        return Err(Error::key_mismatch("key is not an ECDSA key"));
+    }
+
+    let boxed_ec_key = key.ec_key();
+    if boxed_ec_key.is_err() {
+        return Err(Error::format_from("unable to read ECDSA key", boxed_ec_key.err().unwrap()));
+    }
+    let ec_key = boxed_ec_key.unwrap();
+
+    if ec_key.group().curve_name() != Some(Nid::X9_62_PRIME256V1) {
+        return Err(Error::key_mismatch("ECDSA key is not on P-256 curve"));
+    }
+    Ok(ec_key)
+}
+
+fn p256_group() -> Result<EcGroup, Error> {
+    let boxed_group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1);
+    if boxed_group.is_err() {
+        return Err(Error::crypto_from("P-256 curve is not available", boxed_group.err().unwrap()));
+    }
+    Ok(boxed_group.unwrap())
+}
+
+fn private_key_from_raw(algorithm: Algorithm, private_key: &[u8]) -> Result<PKey<Private>, Error> {
+    match algorithm {
+        Algorithm::EcdsaP256 => {
+            let boxed_group = p256_group();
+            if boxed_group.is_err() {
+                return Err(boxed_group.err().unwrap());
+            }
+            let group = boxed_group.unwrap();
+
+            let boxed_scalar = BigNum::from_slice(private_key);
+            if boxed_scalar.is_err() {
+                return Err(Error::format_from("unable to read ECDSA private key scalar", boxed_scalar.err().unwrap()));
+            }
+            let mut scalar = boxed_scalar.unwrap();
+
+            let boxed_context = BigNumContext::new();
+            if boxed_context.is_err() {
+                return Err(Error::crypto_from("unable to allocate big number context", boxed_context.err().unwrap()));
+            }
+            let mut context = boxed_context.unwrap();
+
+            let boxed_point = EcPoint::new(&group);
+            if boxed_point.is_err() {
+                return Err(Error::crypto_from("unable to allocate curve point", boxed_point.err().unwrap()));
+            }
+            let mut point = boxed_point.unwrap();
+
+            let boxed_multiplication = point.mul_generator2(&group, &scalar, &mut context);
+            if boxed_multiplication.is_err() {
+                scalar.clear();
+                return Err(Error::crypto_from("unable to derive ECDSA public key", boxed_multiplication.err().unwrap()));
+            }
+
+            // key keeps its own copy of the scalar, dropping BigNum does not wipe it
+            let boxed_ec_key = EcKey::from_private_components(&group, &scalar, &point);
+            scalar.clear();
+            if boxed_ec_key.is_err() {
+                return Err(Error::format_from("invalid ECDSA private key", boxed_ec_key.err().unwrap()));
+            }
+
+            let boxed_key = PKey::from_ec_key(boxed_ec_key.unwrap());
+            if boxed_key.is_err() {
+                return Err(Error::crypto_from("unable to load ECDSA private key", boxed_key.err().unwrap()));
+            }
+            Ok(boxed_key.unwrap())
+        }
+        Algorithm::Ed25519 => {
+            let boxed_key = PKey::private_key_from_raw_bytes(private_key, Id::ED25519);
+            if boxed_key.is_err() {
+                return Err(Error::format_from("invalid Ed25519 private key", boxed_key.err().unwrap()));
+            }
+            Ok(boxed_key.unwrap())
+        }
+    }
+}
+
+fn public_key_from_raw(algorithm: Algorithm, public_key: &[u8]) -> Result<PKey<Public>, Error> {
+    match algorithm {
+        Algorithm::EcdsaP256 => {
+            let boxed_group = p256_group();
+            if boxed_group.is_err() {
+                return Err(boxed_group.err().unwrap());
+            }
+            let group = boxed_group.unwrap();
+
+            let boxed_context = BigNumContext::new();
+            if boxed_context.is_err() {
+                return Err(Error::crypto_from("unable to allocate big number context", boxed_context.err().unwrap()));
+            }
+            let mut context = boxed_context.unwrap();
+
+            let boxed_point = EcPoint::from_bytes(&group, public_key, &mut context);
+            if boxed_point.is_err() {
+                return Err(Error::format_from("invalid ECDSA public key point", boxed_point.err().unwrap()));
+            }
+            let point = boxed_point.unwrap();
+
+            let boxed_ec_key = EcKey::from_public_key(&group, &point);
+            if boxed_ec_key.is_err() {
+                return Err(Error::format_from("invalid ECDSA public key", boxed_ec_key.err().unwrap()));
+            }
+
+            let boxed_key = PKey::from_ec_key(boxed_ec_key.unwrap());
+            if boxed_key.is_err() {
+                return Err(Error::crypto_from("unable to load ECDSA public key", boxed_key.err().unwrap()));
+            }
+            Ok(boxed_key.unwrap())
+        }
+        Algorithm::Ed25519 => {
+            let boxed_key = PKey::public_key_from_raw_bytes(public_key, Id::ED25519);
+            if boxed_key.is_err() {
+                return Err(Error::format_from("invalid Ed25519 public key", boxed_key.err().unwrap()));
+            }
+            Ok(boxed_key.unwrap())
+        }
+    }
+}
@@ -1,62 +1,393 @@
-use crate::{get_path_relative_to_working_directory, get_static_filepath, read_file, read_or_create_and_write};
-use p256::{ecdsa::{SigningKey, Signature, signature::Signer, VerifyingKey, signature::Verifier}, EncodedPoint};
+use zeroize::Zeroize;
+use crate::Error;
+use crate::key_store::{read_or_create_key_pair, FileSystemKeyStore, KeyStore};
+use crate::secret::Secret;
+use p256::{ecdsa::{SigningKey, Signature, signature::Signer as _, VerifyingKey, signature::Verifier as _}, EncodedPoint};
+use ed25519_dalek::{Signature as Ed25519Signature, SigningKey as Ed25519SigningKey, VerifyingKey as Ed25519VerifyingKey};
+use ed25519_dalek::Signer as _;
 use aes_gcm::aead::rand_core::OsRng;
 
 #[cfg(test)]
 mod tests;
 
-/// SignatureParameters is basically the .der encoded ECDSA private key
+mod encoding;
+
+const ED25519_KEY_LENGTH: usize = 32;
+
+/// Digital signature algorithm key pair belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// ECDSA on NIST P-256 curve with SHA-256, signatures are DER encoded
+    #[default]
+    EcdsaP256,
+    /// EdDSA on edwards25519 curve as defined in RFC 8032, signatures are 64 bytes long
+    Ed25519,
+}
+
+impl Algorithm {
+    fn private_key_name(&self, encoding: KeyEncoding) -> &'static str {
+        match (self, encoding) {
+            (Algorithm::EcdsaP256, KeyEncoding::Der) => ".ecdsa_private_key.der",
+            (Algorithm::EcdsaP256, KeyEncoding::Pem) => ".ecdsa_private_key.pem",
+            (Algorithm::Ed25519, KeyEncoding::Der) => ".ed25519_private_key.der",
+            (Algorithm::Ed25519, KeyEncoding::Pem) => ".ed25519_private_key.pem",
+        }
+    }
+
+    fn public_key_name(&self, encoding: KeyEncoding) -> &'static str {
+        match (self, encoding) {
+            (Algorithm::EcdsaP256, KeyEncoding::Der) => ".ecdsa_public_key.der",
+            (Algorithm::EcdsaP256, KeyEncoding::Pem) => ".ecdsa_public_key.pem",
+            (Algorithm::Ed25519, KeyEncoding::Der) => ".ed25519_public_key.der",
+            (Algorithm::Ed25519, KeyEncoding::Pem) => ".ed25519_public_key.pem",
+        }
+    }
+}
+
+/// Encoding of the key files, private key is stored as PKCS#8 and public key as SubjectPublicKeyInfo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyEncoding {
+    /// binary ASN.1 DER, files have `.der` extension
+    #[default]
+    Der,
+    /// base64 armored DER, files have `.pem` extension
+    Pem,
+}
+
+/// KeyFileOptions control how key files are written and read
+///
+/// When passphrase is set, private key is stored as encrypted PKCS#8 (PBES2 with AES-256-CBC)
+/// and the same passphrase is required to read it back
+#[derive(Debug, Clone, Default)]
+pub struct KeyFileOptions {
+    pub encoding: KeyEncoding,
+    pub passphrase: Option<Secret<String>>,
+}
+
+/// SignatureParameters is basically the algorithm and raw private key
+///
+/// Private key is 32 bytes long scalar for ECDSA P-256 and 32 bytes long seed for Ed25519, it is wiped from memory on drop
+#[derive(Debug, Clone)]
 pub struct SignatureParameters {
-    pub ecdsa_private_key: Vec<u8>,
+    pub algorithm: Algorithm,
+    pub private_key: Secret<Vec<u8>>,
 }
 
-/// VerificationParameters is basically the .der encoded ECDSA public key
+/// VerificationParameters is basically the algorithm and raw public key
+///
+/// Public key is SEC1 encoded point for ECDSA P-256 and 32 bytes long compressed point for Ed25519
+#[derive(Debug, Clone)]
 pub struct VerificationParameters {
-    pub ecdsa_public_key: Vec<u8>,
+    pub algorithm: Algorithm,
+    pub public_key: Vec<u8>,
+}
+
+/// Will read or create ECDSA P-256 SignatureParameters and VerificationParameters at the given location which is relative to the working directory
+pub fn setup(path_to_encryption_parameters: Option<&str>) -> Result<(SignatureParameters, VerificationParameters), Error> {
+    setup_with_algorithm(path_to_encryption_parameters, Algorithm::default())
+}
+
+/// Will read or create SignatureParameters and VerificationParameters for the given algorithm at the given location which is relative to the working directory
+///
+/// Key pairs of different algorithms are stored in separate files and may share the same location
+pub fn setup_with_algorithm(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<(SignatureParameters, VerificationParameters), Error> {
+    setup_with_options(path_to_encryption_parameters, algorithm, &KeyFileOptions::default())
+}
+
+/// Will read or create SignatureParameters and VerificationParameters for the given algorithm at the given location which is relative to the working directory,
+/// key files are written and read according to the given options
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::asymmetric::signing::{Algorithm, KeyEncoding, KeyFileOptions, setup_with_options, sign, verify};
+///     use crypto_ext::secret::Secret;
+///     #[test]
+///     fn signing_with_encrypted_pem_keys() {
+///         let data = "some data to sign".as_bytes();
+///         let path_to_params = "/test/signature_parameters_pem/";
+///
+///         let options = KeyFileOptions {
+///             encoding: KeyEncoding::Pem,
+///             passphrase: Some(Secret::new("passphrase protecting the private key".to_string())),
+///         };
+///         let (signature_params, verification_params) = setup_with_options(Some(path_to_params), Algorithm::EcdsaP256, &options).unwrap();
+///
+///         let signature = sign(signature_params, data).unwrap();
+///         assert!(verify(verification_params, data, signature.as_slice()).is_ok());
+///     }
+/// ```
+pub fn setup_with_options(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm, options: &KeyFileOptions) -> Result<(SignatureParameters, VerificationParameters), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    setup_with_store(&boxed_store.unwrap(), algorithm, options)
 }
 
-/// Will read or create SignatureParameters and VerificationParameters at the given location which is relative to the working directory
-pub fn setup(path_to_encryption_parameters: Option<&str>) -> Result<(SignatureParameters, VerificationParameters), String> {
-    let signing_key = SigningKey::random(&mut OsRng);
-    let verifying_key = VerifyingKey::from(&signing_key);
+/// Will read or create SignatureParameters and VerificationParameters for the given algorithm in the given key store,
+/// keys are encoded according to the given options
+///
+/// Key pair already stored in the other encoding is written in the requested one as well, [Error::KeyMismatch] is returned
+/// if files of both encodings exist and hold different keys
+pub fn setup_with_store(store: &dyn KeyStore, algorithm: Algorithm, options: &KeyFileOptions) -> Result<(SignatureParameters, VerificationParameters), Error> {
+    // key pair of the algorithm stored in the other encoding is re-encoded rather than replaced by an unrelated one,
+    // so files of both encodings hold the same key
+    let boxed_other_private_key = read_private_key_of_other_encoding(store, algorithm, options);
+    if boxed_other_private_key.is_err() {
+        return Err(boxed_other_private_key.err().unwrap());
+    }
+    let other_private_key = boxed_other_private_key.unwrap();
+    let generated_private_key = match &other_private_key {
+        Some(private_key) => private_key.clone(),
+        None => generate_private_key(algorithm),
+    };
+    let passphrase = options.passphrase.as_ref().map(|passphrase| passphrase.as_str());
+
+    let boxed_encoded_private_key = encoding::encode_private_key(algorithm, generated_private_key.as_slice(), options.encoding, passphrase);
+    if boxed_encoded_private_key.is_err() {
+        return Err(boxed_encoded_private_key.err().unwrap());
+    }
+    let encoded_private_key = Secret::new(boxed_encoded_private_key.unwrap());
+
+    let boxed_key_pair = read_or_create_key_pair(
+        store,
+        algorithm.private_key_name(options.encoding),
+        encoded_private_key.as_slice(),
+        algorithm.public_key_name(options.encoding),
+        |stored_private_key| derive_encoded_public_key(algorithm, stored_private_key, options),
+        |stored_public_key, derived_public_key| is_same_encoded_public_key(algorithm, stored_public_key, derived_public_key),
+    );
+    if boxed_key_pair.is_err() {
+        return Err(boxed_key_pair.err().unwrap());
+    }
+    let (stored_private_key, stored_public_key) = boxed_key_pair.unwrap();
 
-    let signing_key_as_bytes = Vec::from(signing_key.to_bytes().as_slice());
-    let verifying_key_as_bytes = Vec::from(verifying_key.to_encoded_point(false).as_bytes());
+    let boxed_private_key = encoding::decode_private_key(algorithm, stored_private_key.as_slice(), passphrase);
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
+    }
+    let private_key = boxed_private_key.unwrap();
+    if other_private_key.is_some() && other_private_key.unwrap().as_slice() != private_key.as_slice() {
+        return Err(Error::key_mismatch("key pairs stored in different encodings hold different keys"));
+    }
 
-    let ecdsa_private_key = setup_private_key(signing_key_as_bytes.as_slice(), path_to_encryption_parameters).unwrap();
-    let ecdsa_public_key = setup_public_key(verifying_key_as_bytes.as_slice(), path_to_encryption_parameters).unwrap();
+    let boxed_public_key = encoding::decode_public_key(algorithm, stored_public_key.as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+    let public_key = boxed_public_key.unwrap();
 
     let signature_parameters = SignatureParameters {
-        ecdsa_private_key
+        algorithm,
+        private_key,
     };
 
     let verification_parameters = VerificationParameters {
-        ecdsa_public_key
+        algorithm,
+        public_key,
     };
 
     Ok((signature_parameters, verification_parameters))
 }
 
 /// Returns SignatureParameters stored at the given location which is relative to the working directory
-pub fn get_signature_params(path_to_encryption_parameters: Option<&str>) -> Result<SignatureParameters, String> {
-    let ecdsa_private_key = get_private_key(path_to_encryption_parameters).unwrap();
-        let params = SignatureParameters {
-        ecdsa_private_key,
+///
+/// If location holds key pairs of several algorithms, ECDSA P-256 one is returned, use [get_signature_params_with_algorithm] to pick another
+pub fn get_signature_params(path_to_encryption_parameters: Option<&str>) -> Result<SignatureParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    let store = boxed_store.unwrap();
+
+    let boxed_algorithm = detect_algorithm(&store);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+    let algorithm = boxed_algorithm.unwrap();
+
+    let boxed_encoding = detect_encoding(&store, algorithm);
+    if boxed_encoding.is_err() {
+        return Err(boxed_encoding.err().unwrap());
+    }
+
+    let options = KeyFileOptions {
+        encoding: boxed_encoding.unwrap(),
+        passphrase: None,
+    };
+    get_signature_params_with_store(&store, algorithm, &options)
+}
+
+/// Returns SignatureParameters of the given algorithm stored at the given location which is relative to the working directory
+///
+/// DER encoded key file is read if present, PEM encoded one otherwise. Encrypted private key requires [get_signature_params_with_options]
+pub fn get_signature_params_with_algorithm(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<SignatureParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    let store = boxed_store.unwrap();
+
+    let boxed_encoding = detect_encoding(&store, algorithm);
+    if boxed_encoding.is_err() {
+        return Err(boxed_encoding.err().unwrap());
+    }
+
+    let options = KeyFileOptions {
+        encoding: boxed_encoding.unwrap(),
+        passphrase: None,
+    };
+    get_signature_params_with_store(&store, algorithm, &options)
+}
+
+/// Returns SignatureParameters of the given algorithm stored at the given location which is relative to the working directory,
+/// key file is read according to the given options
+pub fn get_signature_params_with_options(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm, options: &KeyFileOptions) -> Result<SignatureParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_signature_params_with_store(&boxed_store.unwrap(), algorithm, options)
+}
+
+/// Returns SignatureParameters of the given algorithm stored in the given key store, key is decoded according to the given options
+pub fn get_signature_params_with_store(store: &dyn KeyStore, algorithm: Algorithm, options: &KeyFileOptions) -> Result<SignatureParameters, Error> {
+    let boxed_private_key = store.read(algorithm.private_key_name(options.encoding));
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
+    }
+
+    let stored_private_key = Secret::new(boxed_private_key.unwrap());
+    let boxed_private_key = encoding::decode_private_key(algorithm, stored_private_key.as_slice(), options.passphrase.as_ref().map(|passphrase| passphrase.as_str()));
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
+    }
+    let private_key = boxed_private_key.unwrap();
+
+    let boxed_validate = validate_private_key(algorithm, private_key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let params = SignatureParameters {
+        algorithm,
+        private_key,
     };
 
     Ok(params)
 }
 
 /// Returns VerificationParameters stored at the given location which is relative to the working directory
-pub fn get_verification_params(path_to_encryption_parameters: Option<&str>) -> Result<VerificationParameters, String> {
-    let ecdsa_public_key = get_public_key(path_to_encryption_parameters).unwrap();
+///
+/// If location holds key pairs of several algorithms, ECDSA P-256 one is returned, use [get_verification_params_with_algorithm] to pick another
+pub fn get_verification_params(path_to_encryption_parameters: Option<&str>) -> Result<VerificationParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    let store = boxed_store.unwrap();
+
+    let boxed_algorithm = detect_algorithm(&store);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+    let algorithm = boxed_algorithm.unwrap();
+
+    let boxed_encoding = detect_encoding(&store, algorithm);
+    if boxed_encoding.is_err() {
+        return Err(boxed_encoding.err().unwrap());
+    }
+
+    let options = KeyFileOptions {
+        encoding: boxed_encoding.unwrap(),
+        passphrase: None,
+    };
+    get_verification_params_with_store(&store, algorithm, &options)
+}
+
+/// Returns VerificationParameters of the given algorithm stored at the given location which is relative to the working directory
+///
+/// DER encoded key file is read if present, PEM encoded one otherwise
+pub fn get_verification_params_with_algorithm(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<VerificationParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    let store = boxed_store.unwrap();
+
+    let boxed_encoding = detect_encoding(&store, algorithm);
+    if boxed_encoding.is_err() {
+        return Err(boxed_encoding.err().unwrap());
+    }
+
+    let options = KeyFileOptions {
+        encoding: boxed_encoding.unwrap(),
+        passphrase: None,
+    };
+    get_verification_params_with_store(&store, algorithm, &options)
+}
+
+/// Returns VerificationParameters of the given algorithm stored at the given location which is relative to the working directory,
+/// key file is read according to the given options
+pub fn get_verification_params_with_options(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm, options: &KeyFileOptions) -> Result<VerificationParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_verification_params_with_store(&boxed_store.unwrap(), algorithm, options)
+}
+
+/// Returns VerificationParameters of the given algorithm stored in the given key store, key is decoded according to the given options
+pub fn get_verification_params_with_store(store: &dyn KeyStore, algorithm: Algorithm, options: &KeyFileOptions) -> Result<VerificationParameters, Error> {
+    let boxed_public_key = store.read(algorithm.public_key_name(options.encoding));
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+
+    let boxed_public_key = encoding::decode_public_key(algorithm, boxed_public_key.unwrap().as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+    let public_key = boxed_public_key.unwrap();
+
+    let boxed_validate = validate_public_key(algorithm, public_key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
     let params = VerificationParameters {
-        ecdsa_public_key
+        algorithm,
+        public_key,
     };
 
     Ok(params)
 }
 
+/// Encodes private key of the given SignatureParameters as PKCS#8, encrypted with the passphrase if given
+///
+/// Output can be read by OpenSSL and other tools, for example `openssl pkey -in key.pem`
+pub fn export_private_key(params: &SignatureParameters, encoding: KeyEncoding, passphrase: Option<&str>) -> Result<Vec<u8>, Error> {
+    let boxed_validate = validate_private_key(params.algorithm, params.private_key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    encoding::encode_private_key(params.algorithm, params.private_key.as_slice(), encoding, passphrase)
+}
+
+/// Encodes public key of the given VerificationParameters as SubjectPublicKeyInfo
+///
+/// Output can be read by OpenSSL and other tools, for example `openssl pkey -pubin -in key.pem`
+pub fn export_public_key(params: &VerificationParameters, encoding: KeyEncoding) -> Result<Vec<u8>, Error> {
+    let boxed_validate = validate_public_key(params.algorithm, params.public_key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    encoding::encode_public_key(params.algorithm, params.public_key.as_slice(), encoding)
+}
+
 /// Signs given byte array
 ///
 /// # Examples
@@ -80,10 +411,12 @@ pub fn get_verification_params(path_to_encryption_parameters: Option<&str>) -> R
 ///         assert!(verified.is_ok());
 ///     }
 /// ```
-pub fn sign(params: SignatureParameters, data: &[u8]) -> Result<Vec<u8>, String> {
-    let signing_key = SigningKey::from_bytes(params.ecdsa_private_key.as_slice()).unwrap();
-    let signature = signing_key.sign(data);
-    Ok(signature.to_der().as_bytes().to_vec())
+pub fn sign(params: SignatureParameters, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_signer = Signer::new(&params);
+    if boxed_signer.is_err() {
+        return Err(boxed_signer.err().unwrap());
+    }
+    boxed_signer.unwrap().sign(data)
 }
 
 
@@ -113,99 +446,393 @@ pub fn sign(params: SignatureParameters, data: &[u8]) -> Result<Vec<u8>, String>
 ///         assert!(verified.is_ok());
 ///     }
 /// ```
-pub fn verify(params: VerificationParameters, data: &[u8], signature: &[u8]) -> Result<(), String> {
-    let point = EncodedPoint::from_bytes(params.ecdsa_public_key.as_slice()).unwrap();
-    let verifying_key = VerifyingKey::from_encoded_point(&point).unwrap();
+pub fn verify(params: VerificationParameters, data: &[u8], signature: &[u8]) -> Result<(), Error> {
+    let boxed_verifier = Verifier::new(&params);
+    if boxed_verifier.is_err() {
+        return Err(boxed_verifier.err().unwrap());
+    }
+    boxed_verifier.unwrap().verify(data, signature)
+}
+
+/// Signer keeps parsed private key, so it can sign any number of messages
+///
+/// It is `Send` and `Sync`, single instance can be shared between threads, private key is wiped on drop
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::asymmetric::signing::{setup_with_store, Algorithm, KeyFileOptions, Signer, Verifier};
+///     use crypto_ext::key_store::MemoryKeyStore;
+///
+///     #[test]
+///     fn signer() {
+///         let store = MemoryKeyStore::new();
+///         let (signature_params, verification_params) = setup_with_store(&store, Algorithm::Ed25519, &KeyFileOptions::default()).unwrap();
+///         let signer = Signer::new(&signature_params).unwrap();
+///         let verifier = Verifier::new(&verification_params).unwrap();
+///
+///         for message in ["first message", "second message"] {
+///             let signature = signer.sign(message.as_bytes()).unwrap();
+///             assert!(verifier.verify(message.as_bytes(), signature.as_slice()).is_ok());
+///         }
+///     }
+/// ```
+pub struct Signer {
+    key: SignerKey,
+}
+
+enum SignerKey {
+    EcdsaP256(SigningKey),
+    Ed25519(Ed25519SigningKey),
+}
+
+impl Signer {
+    /// Parses private key of the given parameters
+    pub fn new(params: &SignatureParameters) -> Result<Signer, Error> {
+        let key = match params.algorithm {
+            Algorithm::EcdsaP256 => {
+                let boxed_signing_key = parse_signing_key(params.private_key.as_slice());
+                if boxed_signing_key.is_err() {
+                    return Err(boxed_signing_key.err().unwrap());
+                }
+                SignerKey::EcdsaP256(boxed_signing_key.unwrap())
+            }
+            Algorithm::Ed25519 => {
+                let boxed_signing_key = parse_ed25519_signing_key(params.private_key.as_slice());
+                if boxed_signing_key.is_err() {
+                    return Err(boxed_signing_key.err().unwrap());
+                }
+                SignerKey::Ed25519(boxed_signing_key.unwrap())
+            }
+        };
+        Ok(Signer { key })
+    }
+
+    /// Returns algorithm of the private key
+    pub fn algorithm(&self) -> Algorithm {
+        match self.key {
+            SignerKey::EcdsaP256(_) => Algorithm::EcdsaP256,
+            SignerKey::Ed25519(_) => Algorithm::Ed25519,
+        }
+    }
+
+    /// Signs given byte array the same way as [sign], DER encoded signature for ECDSA P-256 and 64 bytes long one for Ed25519
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match &self.key {
+            SignerKey::EcdsaP256(signing_key) => {
+                let signature: Signature = signing_key.sign(data);
+                Ok(signature.to_der().as_bytes().to_vec())
+            }
+            SignerKey::Ed25519(signing_key) => {
+                let signature = signing_key.sign(data);
+                Ok(signature.to_bytes().to_vec())
+            }
+        }
+    }
+}
+
+/// Verifier keeps parsed public key, so it can verify any number of signatures
+///
+/// It is `Send` and `Sync`, single instance can be shared between threads
+pub struct Verifier {
+    key: VerifierKey,
+}
+
+enum VerifierKey {
+    EcdsaP256(VerifyingKey),
+    Ed25519(Ed25519VerifyingKey),
+}
+
+impl Verifier {
+    /// Parses public key of the given parameters
+    pub fn new(params: &VerificationParameters) -> Result<Verifier, Error> {
+        let key = match params.algorithm {
+            Algorithm::EcdsaP256 => {
+                let boxed_verifying_key = parse_verifying_key(params.public_key.as_slice());
+                if boxed_verifying_key.is_err() {
+                    return Err(boxed_verifying_key.err().unwrap());
+                }
+                VerifierKey::EcdsaP256(boxed_verifying_key.unwrap())
+            }
+            Algorithm::Ed25519 => {
+                let boxed_verifying_key = parse_ed25519_verifying_key(params.public_key.as_slice());
+                if boxed_verifying_key.is_err() {
+                    return Err(boxed_verifying_key.err().unwrap());
+                }
+                VerifierKey::Ed25519(boxed_verifying_key.unwrap())
+            }
+        };
+        Ok(Verifier { key })
+    }
 
-    let signature = Signature::from_der(signature).unwrap();
-    let verified = verifying_key.verify(data, &signature);
+    /// Returns algorithm of the public key
+    pub fn algorithm(&self) -> Algorithm {
+        match self.key {
+            VerifierKey::EcdsaP256(_) => Algorithm::EcdsaP256,
+            VerifierKey::Ed25519(_) => Algorithm::Ed25519,
+        }
+    }
 
-    return if verified.is_ok() {
-        Ok(())
-    } else {
-        let message = verified.err().unwrap().to_string();
-        Err(message)
+    /// Verifies given signature the same way as [verify]
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), Error> {
+        match &self.key {
+            VerifierKey::EcdsaP256(verifying_key) => {
+                let boxed_signature = Signature::from_der(signature);
+                if boxed_signature.is_err() {
+                    return Err(Error::format_from("unable to parse DER encoded ECDSA signature", boxed_signature.err().unwrap()));
+                }
+                let signature = boxed_signature.unwrap();
+
+                let verified = verifying_key.verify(data, &signature);
+
+                if verified.is_ok() {
+                    Ok(())
+                } else {
+                    let error = Error::authentication_from("signature does not match the data and public key", verified.err().unwrap());
+                    Err(error)
+                }
+            }
+            VerifierKey::Ed25519(verifying_key) => {
+                let boxed_signature = Ed25519Signature::from_slice(signature);
+                if boxed_signature.is_err() {
+                    return Err(Error::format_from("unable to parse Ed25519 signature", boxed_signature.err().unwrap()));
+                }
+                let signature = boxed_signature.unwrap();
+
+                // strict verification rejects malleable signatures and weak public keys
+                let verified = verifying_key.verify_strict(data, &signature);
+
+                if verified.is_ok() {
+                    Ok(())
+                } else {
+                    let error = Error::authentication_from("signature does not match the data and public key", verified.err().unwrap());
+                    Err(error)
+                }
+            }
+        }
     }
 }
 
 // below are functions not exposed as an api, used for inner implementation
 
-fn setup_private_key(private_key: &[u8], path_to_encryption_parameters: Option<&str>) -> Result<Vec<u8>, String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".ecdsa_private_key.der");
-    let boxed_private_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_private_key_path.is_err() {
-        return Err(boxed_private_key_path.err().unwrap());
+fn generate_private_key(algorithm: Algorithm) -> Secret<Vec<u8>> {
+    // signing keys wipe themselves on drop, copies of their bytes are wiped here
+    match algorithm {
+        Algorithm::EcdsaP256 => {
+            let mut bytes = SigningKey::random(&mut OsRng).to_bytes();
+            let private_key = Secret::new(bytes.to_vec());
+            bytes.as_mut_slice().zeroize();
+            private_key
+        }
+        Algorithm::Ed25519 => {
+            let mut bytes = Ed25519SigningKey::generate(&mut OsRng).to_bytes();
+            let private_key = Secret::new(bytes.to_vec());
+            bytes.zeroize();
+            private_key
+        }
     }
+}
 
-    let dsa_private_key_path = boxed_private_key_path.unwrap();
-    let boxed_private_key = get_or_create_value_at_path(dsa_private_key_path.as_str(), private_key);
-    if boxed_private_key.is_err() {
-        return Err(boxed_private_key.err().unwrap());
+pub(crate) fn derive_public_key(algorithm: Algorithm, private_key: &[u8]) -> Result<Vec<u8>, Error> {
+    match algorithm {
+        Algorithm::EcdsaP256 => {
+            let boxed_signing_key = parse_signing_key(private_key);
+            if boxed_signing_key.is_err() {
+                return Err(boxed_signing_key.err().unwrap());
+            }
+            let verifying_key = VerifyingKey::from(&boxed_signing_key.unwrap());
+            Ok(verifying_key.to_encoded_point(false).as_bytes().to_vec())
+        }
+        Algorithm::Ed25519 => {
+            let boxed_signing_key = parse_ed25519_signing_key(private_key);
+            if boxed_signing_key.is_err() {
+                return Err(boxed_signing_key.err().unwrap());
+            }
+            Ok(boxed_signing_key.unwrap().verifying_key().to_bytes().to_vec())
+        }
     }
-    let private_key = boxed_private_key.unwrap();
-    Ok(private_key)
 }
 
-fn get_private_key(path_to_encryption_parameters: Option<&str>) -> Result<Vec<u8>, String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".ecdsa_private_key.der");
-    let boxed_private_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_private_key_path.is_err() {
-        return Err(boxed_private_key_path.err().unwrap());
+fn read_private_key_of_other_encoding(store: &dyn KeyStore, algorithm: Algorithm, options: &KeyFileOptions) -> Result<Option<Secret<Vec<u8>>>, Error> {
+    let other_encoding = match options.encoding {
+        KeyEncoding::Der => KeyEncoding::Pem,
+        KeyEncoding::Pem => KeyEncoding::Der,
+    };
+
+    let boxed_exists = store.contains(algorithm.private_key_name(other_encoding));
+    if boxed_exists.is_err() {
+        return Err(boxed_exists.err().unwrap());
+    }
+    if !boxed_exists.unwrap() {
+        return Ok(None);
     }
 
-    let ecdsa_private_key_path = boxed_private_key_path.unwrap();
-    let boxed_private_key = read_file(ecdsa_private_key_path.as_str());
-    if boxed_private_key.is_err() {
-        return Err(boxed_private_key.err().unwrap());
+    let other_options = KeyFileOptions { encoding: other_encoding, passphrase: options.passphrase.clone() };
+    let boxed_params = get_signature_params_with_store(store, algorithm, &other_options);
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
     }
-    let private_key = boxed_private_key.unwrap();
-    Ok(private_key)
+    Ok(Some(boxed_params.unwrap().private_key))
 }
 
-fn setup_public_key(public_key: &[u8], path_to_encryption_parameters: Option<&str>) -> Result<Vec<u8>, String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".ecdsa_public_key.der");
-    let boxed_public_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_public_key_path.is_err() {
-        return Err(boxed_public_key_path.err().unwrap());
+// ECDSA public key stored by earlier versions may be a compressed point
+// derives public key of the stored private key and encodes it the same way
+fn derive_encoded_public_key(algorithm: Algorithm, stored_private_key: &[u8], options: &KeyFileOptions) -> Result<Vec<u8>, Error> {
+    let boxed_private_key = encoding::decode_private_key(algorithm, stored_private_key, options.passphrase.as_ref().map(|passphrase| passphrase.as_str()));
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
     }
 
-    let ecdsa_public_key_path = boxed_public_key_path.unwrap();
-    let boxed_public_key = get_or_create_value_at_path(ecdsa_public_key_path.as_str(), public_key);
+    let boxed_public_key = derive_public_key(algorithm, boxed_private_key.unwrap().as_slice());
     if boxed_public_key.is_err() {
         return Err(boxed_public_key.err().unwrap());
     }
 
-    let public_key = boxed_public_key.unwrap();
+    encoding::encode_public_key(algorithm, boxed_public_key.unwrap().as_slice(), options.encoding)
+}
+
+// stored public key may be in the legacy raw format, so keys are compared rather than their encoding
+fn is_same_encoded_public_key(algorithm: Algorithm, stored_public_key: &[u8], derived_public_key: &[u8]) -> Result<bool, Error> {
+    let boxed_stored_public_key = encoding::decode_public_key(algorithm, stored_public_key);
+    if boxed_stored_public_key.is_err() {
+        return Err(boxed_stored_public_key.err().unwrap());
+    }
+
+    let boxed_derived_public_key = encoding::decode_public_key(algorithm, derived_public_key);
+    if boxed_derived_public_key.is_err() {
+        return Err(boxed_derived_public_key.err().unwrap());
+    }
 
-    Ok(public_key)
+    is_same_public_key(algorithm, boxed_stored_public_key.unwrap().as_slice(), boxed_derived_public_key.unwrap().as_slice())
 }
 
-fn get_public_key(path_to_encryption_parameters: Option<&str>) -> Result<Vec<u8>, String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".ecdsa_public_key.der");
-    let boxed_public_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_public_key_path.is_err() {
-        return Err(boxed_public_key_path.err().unwrap());
+fn is_same_public_key(algorithm: Algorithm, public_key: &[u8], other_public_key: &[u8]) -> Result<bool, Error> {
+    match algorithm {
+        Algorithm::EcdsaP256 => {
+            let boxed_verifying_key = parse_verifying_key(public_key);
+            if boxed_verifying_key.is_err() {
+                return Err(boxed_verifying_key.err().unwrap());
+            }
+
+            let boxed_other_verifying_key = parse_verifying_key(other_public_key);
+            if boxed_other_verifying_key.is_err() {
+                return Err(boxed_other_verifying_key.err().unwrap());
+            }
+            Ok(boxed_verifying_key.unwrap() == boxed_other_verifying_key.unwrap())
+        }
+        Algorithm::Ed25519 => Ok(public_key == other_public_key),
     }
+}
 
-    let ecdsa_public_key_path = boxed_public_key_path.unwrap();
-    let boxed_public_key = read_file(ecdsa_public_key_path.as_str());
-    if boxed_public_key.is_err() {
-        return Err(boxed_public_key.err().unwrap());
+fn detect_algorithm(store: &dyn KeyStore) -> Result<Algorithm, Error> {
+    let algorithms = [Algorithm::EcdsaP256, Algorithm::Ed25519];
+    for algorithm in algorithms {
+        let boxed_encoding = find_key_files(store, algorithm);
+        if boxed_encoding.is_err() {
+            return Err(boxed_encoding.err().unwrap());
+        }
+
+        if boxed_encoding.unwrap().is_some() {
+            return Ok(algorithm);
+        }
     }
 
-    let public_key = boxed_public_key.unwrap();
+    // nothing is stored, reading default algorithm files will report missing file
+    Ok(Algorithm::default())
+}
 
-    Ok(public_key)
+fn detect_encoding(store: &dyn KeyStore, algorithm: Algorithm) -> Result<KeyEncoding, Error> {
+    let boxed_encoding = find_key_files(store, algorithm);
+    if boxed_encoding.is_err() {
+        return Err(boxed_encoding.err().unwrap());
+    }
+
+    // nothing is stored, reading default encoding files will report missing file
+    Ok(boxed_encoding.unwrap().unwrap_or_default())
 }
 
-fn get_or_create_value_at_path(path: &str, value: &[u8]) -> Result<Vec<u8>, String> {
+fn find_key_files(store: &dyn KeyStore, algorithm: Algorithm) -> Result<Option<KeyEncoding>, Error> {
+    let encodings = [KeyEncoding::Der, KeyEncoding::Pem];
+    for encoding in encodings {
+        let boxed_private_key_exists = store.contains(algorithm.private_key_name(encoding));
+        if boxed_private_key_exists.is_err() {
+            return Err(boxed_private_key_exists.err().unwrap());
+        }
+
+        let boxed_public_key_exists = store.contains(algorithm.public_key_name(encoding));
+        if boxed_public_key_exists.is_err() {
+            return Err(boxed_public_key_exists.err().unwrap());
+        }
+
+        if boxed_private_key_exists.unwrap() || boxed_public_key_exists.unwrap() {
+            return Ok(Some(encoding));
+        }
+    }
+
+    Ok(None)
+}
+
+fn validate_private_key(algorithm: Algorithm, private_key: &[u8]) -> Result<(), Error> {
+    match algorithm {
+        Algorithm::EcdsaP256 => parse_signing_key(private_key).map(|_| ()),
+        Algorithm::Ed25519 => parse_ed25519_signing_key(private_key).map(|_| ()),
+    }
+}
+
+fn validate_public_key(algorithm: Algorithm, public_key: &[u8]) -> Result<(), Error> {
+    match algorithm {
+        Algorithm::EcdsaP256 => parse_verifying_key(public_key).map(|_| ()),
+        Algorithm::Ed25519 => parse_ed25519_verifying_key(public_key).map(|_| ()),
+    }
+}
+
+fn parse_signing_key(private_key: &[u8]) -> Result<SigningKey, Error> {
+    let boxed_signing_key = SigningKey::from_bytes(private_key);
+    if boxed_signing_key.is_err() {
+        return Err(Error::format_from("unable to parse ECDSA private key", boxed_signing_key.err().unwrap()));
+    }
+    Ok(boxed_signing_key.unwrap())
+}
 
-    let boxed_passphrase = read_or_create_and_write(path, value);
-    if boxed_passphrase.is_err() {
-        let message = boxed_passphrase.err().unwrap();
-        return Err(message)
+fn parse_verifying_key(public_key: &[u8]) -> Result<VerifyingKey, Error> {
+    let boxed_point = EncodedPoint::from_bytes(public_key);
+    if boxed_point.is_err() {
+        let message = format!("unable to parse SEC1 encoded ECDSA public key: {}", boxed_point.err().unwrap());
+        return Err(Error::format(message));
     }
+    let point = boxed_point.unwrap();
 
-    let passphrase = boxed_passphrase.unwrap();
-    Ok(passphrase)
+    let boxed_verifying_key = VerifyingKey::from_encoded_point(&point);
+    if boxed_verifying_key.is_err() {
+        return Err(Error::format_from("ECDSA public key is not a valid P-256 point", boxed_verifying_key.err().unwrap()));
+    }
+    Ok(boxed_verifying_key.unwrap())
+}
+
+fn parse_ed25519_signing_key(private_key: &[u8]) -> Result<Ed25519SigningKey, Error> {
+    let boxed_secret_key: Result<[u8; ED25519_KEY_LENGTH], _> = private_key.try_into();
+    if boxed_secret_key.is_err() {
+        let message = format!("invalid Ed25519 private key length: {} bytes, expected {} bytes", private_key.len(), ED25519_KEY_LENGTH);
+        return Err(Error::format(message));
+    }
+    let mut secret_key = boxed_secret_key.unwrap();
+
+    let signing_key = Ed25519SigningKey::from_bytes(&secret_key);
+    secret_key.zeroize();
+    Ok(signing_key)
+}
+
+fn parse_ed25519_verifying_key(public_key: &[u8]) -> Result<Ed25519VerifyingKey, Error> {
+    let boxed_public_key: Result<[u8; ED25519_KEY_LENGTH], _> = public_key.try_into();
+    if boxed_public_key.is_err() {
+        let message = format!("invalid Ed25519 public key length: {} bytes, expected {} bytes", public_key.len(), ED25519_KEY_LENGTH);
+        return Err(Error::format(message));
+    }
+    let public_key = boxed_public_key.unwrap();
+
+    let boxed_verifying_key = Ed25519VerifyingKey::from_bytes(&public_key);
+    if boxed_verifying_key.is_err() {
+        return Err(Error::format_from("Ed25519 public key is not a valid curve point", boxed_verifying_key.err().unwrap()));
+    }
+    Ok(boxed_verifying_key.unwrap())
 }
@@ -0,0 +1,205 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use base64::Engine;
+use sha2::Digest;
+use crate::Error;
+
+#[cfg(test)]
+mod tests;
+
+/// Hash function used to compute digest of the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// SHA-256 as defined in FIPS 180-4
+    #[default]
+    Sha256,
+    /// SHA-384 as defined in FIPS 180-4
+    Sha384,
+    /// SHA-512 as defined in FIPS 180-4
+    Sha512,
+    /// SHA3-256 as defined in FIPS 202
+    Sha3_256,
+    /// SHA3-512 as defined in FIPS 202
+    Sha3_512,
+    /// BLAKE3 with 256 bits long output
+    Blake3,
+}
+
+impl Algorithm {
+    /// Returns length of the digest in bytes
+    pub fn output_length(&self) -> usize {
+        match self {
+            Algorithm::Sha256 => 32,
+            Algorithm::Sha384 => 48,
+            Algorithm::Sha512 => 64,
+            Algorithm::Sha3_256 => 32,
+            Algorithm::Sha3_512 => 64,
+            Algorithm::Blake3 => 32,
+        }
+    }
+}
+
+/// Computes digest of the given byte array
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::digest::{digest, to_hex, Algorithm};
+///
+///     #[test]
+///     fn sha256() {
+///         let digest = digest(Algorithm::Sha256, "abc".as_bytes());
+///         assert_eq!(to_hex(digest.as_slice()), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+///     }
+/// ```
+pub fn digest(algorithm: Algorithm, data: &[u8]) -> Vec<u8> {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Computes digest of everything read from the reader, without holding the data in memory
+///
+/// # Examples
+///
+/// ```
+///     use std::io::Cursor;
+///     use crypto_ext::digest::{digest, digest_reader, Algorithm};
+///
+///     #[test]
+///     fn reader() {
+///         let data = vec![7; 1024 * 1024];
+///
+///         // any Read implementation such as File or TcpStream is accepted
+///         let digest_of_reader = digest_reader(Algorithm::Blake3, Cursor::new(&data)).unwrap();
+///         assert_eq!(digest_of_reader, digest(Algorithm::Blake3, data.as_slice()));
+///     }
+/// ```
+pub fn digest_reader<R: Read>(algorithm: Algorithm, mut reader: R) -> Result<Vec<u8>, Error> {
+    let mut hasher = Hasher::new(algorithm);
+    let boxed_copy = io::copy(&mut reader, &mut hasher);
+    if boxed_copy.is_err() {
+        return Err(Error::io("unable to read data to digest", boxed_copy.err().unwrap()))
+    }
+    Ok(hasher.finalize())
+}
+
+/// Computes digest of the file at the given path, see [digest_reader]
+pub fn digest_file(algorithm: Algorithm, path: &str) -> Result<Vec<u8>, Error> {
+    let boxed_open = File::open(path);
+    if boxed_open.is_err() {
+        let message = format!("unable to read from file: {}", path);
+        return Err(Error::io(message, boxed_open.err().unwrap()))
+    }
+
+    digest_reader(algorithm, boxed_open.unwrap())
+}
+
+/// Encodes bytes as lowercase hexadecimal string
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Encodes bytes as base64 string with standard alphabet and padding, as defined in RFC 4648
+pub fn to_base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Hasher computes digest of the data given in parts, for data that is not available at once
+///
+/// It implements [Write], so [std::io::copy] can feed it from any reader.
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::digest::{digest, Algorithm, Hasher};
+///
+///     #[test]
+///     fn incremental() {
+///         let mut hasher = Hasher::new(Algorithm::Sha3_256);
+///         hasher.update("some ".as_bytes());
+///         hasher.update("data".as_bytes());
+///
+///         assert_eq!(hasher.finalize(), digest(Algorithm::Sha3_256, "some data".as_bytes()));
+///     }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Hasher {
+    state: State,
+}
+
+impl Hasher {
+    /// Creates hasher for the given algorithm
+    pub fn new(algorithm: Algorithm) -> Hasher {
+        let state = match algorithm {
+            Algorithm::Sha256 => State::Sha256(sha2::Sha256::new()),
+            Algorithm::Sha384 => State::Sha384(sha2::Sha384::new()),
+            Algorithm::Sha512 => State::Sha512(sha2::Sha512::new()),
+            Algorithm::Sha3_256 => State::Sha3_256(sha3::Sha3_256::new()),
+            Algorithm::Sha3_512 => State::Sha3_512(sha3::Sha3_512::new()),
+            Algorithm::Blake3 => State::Blake3(Box::new(blake3::Hasher::new())),
+        };
+        Hasher { state }
+    }
+
+    /// Returns algorithm of the hasher
+    pub fn algorithm(&self) -> Algorithm {
+        match self.state {
+            State::Sha256(_) => Algorithm::Sha256,
+            State::Sha384(_) => Algorithm::Sha384,
+            State::Sha512(_) => Algorithm::Sha512,
+            State::Sha3_256(_) => Algorithm::Sha3_256,
+            State::Sha3_512(_) => Algorithm::Sha3_512,
+            State::Blake3(_) => Algorithm::Blake3,
+        }
+    }
+
+    /// Processes next part of the data
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            State::Sha256(hasher) => hasher.update(data),
+            State::Sha384(hasher) => hasher.update(data),
+            State::Sha512(hasher) => hasher.update(data),
+            State::Sha3_256(hasher) => hasher.update(data),
+            State::Sha3_512(hasher) => hasher.update(data),
+            State::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Returns digest of all the data processed so far
+    pub fn finalize(self) -> Vec<u8> {
+        match self.state {
+            State::Sha256(hasher) => hasher.finalize().to_vec(),
+            State::Sha384(hasher) => hasher.finalize().to_vec(),
+            State::Sha512(hasher) => hasher.finalize().to_vec(),
+            State::Sha3_256(hasher) => hasher.finalize().to_vec(),
+            State::Sha3_512(hasher) => hasher.finalize().to_vec(),
+            State::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.update(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// BLAKE3 state is much larger than the others, it is boxed to keep the hasher small
+#[derive(Debug, Clone)]
+enum State {
+    Sha256(sha2::Sha256),
+    Sha384(sha2::Sha384),
+    Sha512(sha2::Sha512),
+    Sha3_256(sha3::Sha3_256),
+    Sha3_512(sha3::Sha3_512),
+    Blake3(Box<blake3::Hasher>),
+}
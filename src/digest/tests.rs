@@ -0,0 +1,149 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Write};
+use crate::Error;
+use crate::digest::{digest, digest_file, digest_reader, to_base64, to_hex, Algorithm, Hasher};
+use crate::passphrase::generate_bytes;
+
+const ALGORITHMS: [Algorithm; 6] = [Algorithm::Sha256, Algorithm::Sha384, Algorithm::Sha512, Algorithm::Sha3_256, Algorithm::Sha3_512, Algorithm::Blake3];
+
+// messages of the NIST examples: empty, "abc", 448 bits long message and one million of "a"
+fn messages() -> [Vec<u8>; 4] {
+    [
+        vec![],
+        "abc".as_bytes().to_vec(),
+        "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".as_bytes().to_vec(),
+        vec![b'a'; 1_000_000],
+    ]
+}
+
+#[test]
+fn nist_vectors() {
+    let vectors = [
+        (Algorithm::Sha256, [
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+        ]),
+        (Algorithm::Sha384, [
+            "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b",
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+            "3391fdddfc8dc7393707a65b1b4709397cf8b1d162af05abfe8f450de5f36bc6b0455a8520bc4e6f5fe95b1fe3c8452b",
+            "9d0e1809716474cb086e834e310a4a1ced149e9c00f248527972cec5704c2a5b07b8b3dc38ecc4ebae97ddd87f3d8985",
+        ]),
+        (Algorithm::Sha512, [
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            "204a8fc6dda82f0a0ced7beb8e08a41657c16ef468b228a8279be331a703c33596fd15c13b1b07f9aa1d3bea57789ca031ad85c7a71dd70354ec631238ca3445",
+            "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973ebde0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b",
+        ]),
+        (Algorithm::Sha3_256, [
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a",
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+            "41c0dba2a9d6240849100376a8235e2c82e1b9998a999e21db32dd97496d3376",
+            "5c8875ae474a3634ba4fd55ec85bffd661f32aca75c6d699d0cdcb6c115891c1",
+        ]),
+        (Algorithm::Sha3_512, [
+            "a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859e0d1dcc1475c80a615b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26",
+            "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0",
+            "04a371e84ecfb5b8b77cb48610fca8182dd457ce6f326a0fd3d7ec2f1e91636dee691fbe0c985302ba1b0d8dc78c086346b533b49c030d99a27daf1139d6e75e",
+            "3c3a876da14034ab60627c077bb98f7e120a2a5370212dffb3385a18d4f38859ed311d0a9d5141ce9cc5c66ee689b266a8aa18ace8282a0e0db596c90b0a7b87",
+        ]),
+        // BLAKE3 is not a NIST function, digests are computed by the reference implementation
+        (Algorithm::Blake3, [
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            "c19012cc2aaf0dc3d8e5c45a1b79114d2df42abb2a410bf54be09e891af06ff8",
+            "616f575a1b58d4c9797d4217b9730ae5e6eb319d76edef6549b46f4efe31ff8b",
+        ]),
+    ];
+
+    for (algorithm, expected) in vectors {
+        for (message, expected) in messages().iter().zip(expected) {
+            let digest = digest(algorithm, message.as_slice());
+            assert_eq!(digest.len(), algorithm.output_length());
+            assert_eq!(to_hex(digest.as_slice()), expected, "{:?} of {} bytes", algorithm, message.len());
+        }
+    }
+}
+
+#[test]
+fn blake3_tree_hashing() {
+    // official test vector, input of 1025 bytes spans two chunks
+    let input: Vec<u8> = (0..1025).map(|index| (index % 251) as u8).collect();
+    let digest = digest(Algorithm::Blake3, input.as_slice());
+    assert_eq!(to_hex(digest.as_slice()), "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444");
+}
+
+#[test]
+fn incremental_hashing() {
+    let data = generate_bytes(10_000).unwrap();
+
+    for algorithm in ALGORITHMS {
+        let expected = digest(algorithm, data.as_slice());
+
+        for part_length in [1, 63, 64, 65, 1000, 1024, 1025, 10_000] {
+            let mut hasher = Hasher::new(algorithm);
+            assert_eq!(hasher.algorithm(), algorithm);
+            for part in data.chunks(part_length) {
+                hasher.update(part);
+            }
+            assert_eq!(hasher.finalize(), expected, "{:?} in parts of {} bytes", algorithm, part_length);
+        }
+
+        // cloned hasher continues independently
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(&data[..5000]);
+        let mut clone = hasher.clone();
+        hasher.update(&data[5000..]);
+        clone.write_all(&data[5000..]).unwrap();
+        assert_eq!(hasher.finalize(), expected);
+        assert_eq!(clone.finalize(), expected);
+
+        assert_eq!(Hasher::new(algorithm).finalize(), digest(algorithm, &[]));
+    }
+}
+
+#[test]
+fn reader_and_file_hashing() {
+    let directory = "test/digest_files";
+    fs::create_dir_all(directory).unwrap();
+    let data = generate_bytes(3 * 1024 * 1024 + 7).unwrap();
+    let path = [directory, "data"].join("/");
+    fs::write(path.as_str(), data.as_slice()).unwrap();
+
+    for algorithm in ALGORITHMS {
+        let expected = digest(algorithm, data.as_slice());
+        assert_eq!(digest_reader(algorithm, Cursor::new(&data)).unwrap(), expected);
+        assert_eq!(digest_reader(algorithm, BufReader::new(File::open(path.as_str()).unwrap())).unwrap(), expected);
+        assert_eq!(digest_file(algorithm, path.as_str()).unwrap(), expected);
+    }
+
+    fs::remove_dir_all(directory).unwrap();
+
+    let error = digest_file(Algorithm::Sha256, "test/digest_files/missing").err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+}
+
+#[test]
+fn output_encoding() {
+    assert_eq!(to_hex(&[]), "");
+    assert_eq!(to_hex(&[0, 1, 0x7f, 0x80, 0xab, 0xff]), "00017f80abff");
+
+    // RFC 4648 section 10
+    let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+    for (data, expected) in vectors {
+        assert_eq!(to_base64(data.as_bytes()), expected);
+    }
+
+    let digest = digest(Algorithm::Sha256, "abc".as_bytes());
+    assert_eq!(to_base64(digest.as_slice()), "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=");
+}
+
+#[test]
+fn assert_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Algorithm>();
+    assert_send_sync::<Hasher>();
+}
@@ -0,0 +1,130 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests;
+
+type Source = Box<dyn StdError + Send + Sync + 'static>;
+
+/// Error returned by all functions of the crate
+///
+/// Underlying error of the openssl, aes-gcm, chacha20poly1305 or p256 crates, if any, is available via [StdError::source]
+#[derive(Debug)]
+pub enum Error {
+    /// Key material or data can not be read or written
+    Io {
+        message: String,
+        source: std::io::Error,
+    },
+    /// Location of the key material exists but is not a directory, so it can not be created there
+    NotADirectory {
+        message: String,
+        path: PathBuf,
+    },
+    /// Input can not be parsed: malformed key file, encoded key, signature, envelope or encrypted data
+    Format {
+        message: String,
+        source: Option<Source>,
+    },
+    /// Decryption or signature verification failed: data, associated data or signature was tampered with or wrong key was used
+    Authentication {
+        message: String,
+        source: Option<Source>,
+    },
+    /// Key does not fit the operation: it has wrong length, belongs to another algorithm or can not be unlocked with the passphrase
+    KeyMismatch {
+        message: String,
+        source: Option<Source>,
+    },
+    /// Algorithm identifier or option is unknown or not supported
+    UnsupportedAlgorithm {
+        message: String,
+        source: Option<Source>,
+    },
+    /// Underlying cryptographic library or random number generator failed
+    Crypto {
+        message: String,
+        source: Option<Source>,
+    },
+}
+
+impl Error {
+    pub(crate) fn io(message: impl Into<String>, source: std::io::Error) -> Error {
+        Error::Io { message: message.into(), source }
+    }
+
+    pub(crate) fn not_a_directory(path: &Path) -> Error {
+        let message = format!("path exists but is not a directory: {}", path.display());
+        Error::NotADirectory { message, path: path.to_path_buf() }
+    }
+
+    pub(crate) fn format(message: impl Into<String>) -> Error {
+        Error::Format { message: message.into(), source: None }
+    }
+
+    pub(crate) fn format_from(message: impl Into<String>, source: impl StdError + Send + Sync + 'static) -> Error {
+        Error::Format { message: message.into(), source: Some(Box::new(source)) }
+    }
+
+    pub(crate) fn authentication_from(message: impl Into<String>, source: impl StdError + Send + Sync + 'static) -> Error {
+        Error::Authentication { message: message.into(), source: Some(Box::new(source)) }
+    }
+
+    pub(crate) fn key_mismatch(message: impl Into<String>) -> Error {
+        Error::KeyMismatch { message: message.into(), source: None }
+    }
+
+    pub(crate) fn key_mismatch_from(message: impl Into<String>, source: impl StdError + Send + Sync + 'static) -> Error {
+        Error::KeyMismatch { message: message.into(), source: Some(Box::new(source)) }
+    }
+
+    pub(crate) fn unsupported_algorithm(message: impl Into<String>) -> Error {
+        Error::UnsupportedAlgorithm { message: message.into(), source: None }
+    }
+
+    pub(crate) fn unsupported_algorithm_from(message: impl Into<String>, source: impl StdError + Send + Sync + 'static) -> Error {
+        Error::UnsupportedAlgorithm { message: message.into(), source: Some(Box::new(source)) }
+    }
+
+    pub(crate) fn crypto(message: impl Into<String>) -> Error {
+        Error::Crypto { message: message.into(), source: None }
+    }
+
+    pub(crate) fn crypto_from(message: impl Into<String>, source: impl StdError + Send + Sync + 'static) -> Error {
+        Error::Crypto { message: message.into(), source: Some(Box::new(source)) }
+    }
+
+    /// Returns human readable description of the error, without the underlying error
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Io { message, .. } => message,
+            Error::NotADirectory { message, .. } => message,
+            Error::Format { message, .. } => message,
+            Error::Authentication { message, .. } => message,
+            Error::KeyMismatch { message, .. } => message,
+            Error::UnsupportedAlgorithm { message, .. } => message,
+            Error::Crypto { message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::NotADirectory { .. } => None,
+            Error::Format { source, .. } => source.as_deref().map(|source| source as &(dyn StdError + 'static)),
+            Error::Authentication { source, .. } => source.as_deref().map(|source| source as &(dyn StdError + 'static)),
+            Error::KeyMismatch { source, .. } => source.as_deref().map(|source| source as &(dyn StdError + 'static)),
+            Error::UnsupportedAlgorithm { source, .. } => source.as_deref().map(|source| source as &(dyn StdError + 'static)),
+            Error::Crypto { source, .. } => source.as_deref().map(|source| source as &(dyn StdError + 'static)),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::error::Error as StdError;
+use crate::error::Error;
+
+#[test]
+fn display_and_source() {
+    let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+    let error = Error::io("unable to read from file", io_error);
+    assert_eq!("unable to read from file", error.to_string());
+    assert_eq!("no such file", error.source().unwrap().to_string());
+    assert!(matches!(error, Error::Io { .. }));
+
+    let error = Error::key_mismatch("invalid key length");
+    assert_eq!("invalid key length", error.to_string());
+    assert!(error.source().is_none());
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let error = Error::not_a_directory(std::path::Path::new("/etc/hostname"));
+    assert_eq!("path exists but is not a directory: /etc/hostname", error.to_string());
+    assert!(error.source().is_none());
+    assert!(matches!(error, Error::NotADirectory { .. }));
+
+    let error = Error::unsupported_algorithm("unsupported algorithm identifier: 7");
+    assert_eq!("unsupported algorithm identifier: 7", error.message());
+    assert!(error.source().is_none());
+}
+
+#[test]
+fn source_is_downcastable() {
+    let openssl_error = openssl::rsa::Rsa::public_key_from_pem("not a key".as_bytes()).err().unwrap();
+    let error = Error::format_from("unable to parse public key", openssl_error);
+    let source = error.source().unwrap();
+    assert!(source.downcast_ref::<openssl::error::ErrorStack>().is_some());
+}
+
+#[test]
+fn error_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<Error>();
+
+    let boxed: Box<dyn StdError + Send + Sync> = Box::new(Error::format("failure"));
+    assert_eq!("failure", boxed.to_string());
+}
@@ -0,0 +1,399 @@
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha512};
+use crate::Error;
+use crate::asymmetric::signing;
+use crate::key_store::{FileSystemKeyStore, KeyStore};
+use crate::passphrase::generate_bytes;
+use crate::secret::Secret;
+use crate::symmetric;
+
+#[cfg(test)]
+mod tests;
+
+// name of the key in the key store, file name for the path based functions
+const KEY_NAME: &str = ".master_key";
+// magic of the key file, format version, algorithm identifier and master key follow it
+const KEY_FILE_MAGIC: &[u8] = b"CXHK";
+// master key shorter than 128 bits is rejected, generated master key is as long as the hash output
+const MIN_MASTER_KEY_LENGTH: usize = 16;
+
+// every key of the hierarchy is expanded with info: prefix, purpose, algorithm identifier, label
+const INFO_PREFIX: &[u8] = b"CXKH";
+const HIERARCHY_SALT: &[u8] = b"crypto-ext key hierarchy";
+const PURPOSE_CHILD: u8 = 0;
+const PURPOSE_KEY: u8 = 1;
+const PURPOSE_ENCRYPTION: u8 = 2;
+const PURPOSE_MAC: u8 = 3;
+const PURPOSE_SIGNATURE: u8 = 4;
+
+/// Hash function HKDF is instantiated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// HKDF with HMAC-SHA256 as defined in RFC 5869
+    #[default]
+    HkdfSha256,
+    /// HKDF with HMAC-SHA512 as defined in RFC 5869
+    HkdfSha512,
+}
+
+impl Algorithm {
+    /// Returns length of the hash output in bytes, it is the length of the pseudorandom key
+    pub fn hash_length(&self) -> usize {
+        match self {
+            Algorithm::HkdfSha256 => 32,
+            Algorithm::HkdfSha512 => 64,
+        }
+    }
+
+    /// Returns maximum length of the output of the single expansion in bytes
+    pub fn max_output_length(&self) -> usize {
+        255 * self.hash_length()
+    }
+
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Algorithm::HkdfSha256 => 1,
+            Algorithm::HkdfSha512 => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Algorithm, Error> {
+        match id {
+            1 => Ok(Algorithm::HkdfSha256),
+            2 => Ok(Algorithm::HkdfSha512),
+            _ => Err(Error::unsupported_algorithm(format!("unsupported key derivation algorithm identifier: {}", id))),
+        }
+    }
+}
+
+/// Extracts pseudorandom key from the input keying material, empty salt is the same as salt of zeros
+pub fn extract(algorithm: Algorithm, salt: &[u8], ikm: &[u8]) -> Secret<Vec<u8>> {
+    match algorithm {
+        Algorithm::HkdfSha256 => {
+            let (prk, _) = Hkdf::<Sha256>::extract(Some(salt), ikm);
+            Secret::new(prk.to_vec())
+        }
+        Algorithm::HkdfSha512 => {
+            let (prk, _) = Hkdf::<Sha512>::extract(Some(salt), ikm);
+            Secret::new(prk.to_vec())
+        }
+    }
+}
+
+/// Expands pseudorandom key into the output keying material of the given length bound to the info
+///
+/// Pseudorandom key has to be at least as long as the hash output, length is limited by [Algorithm::max_output_length]
+pub fn expand(algorithm: Algorithm, prk: &[u8], info: &[u8], length: usize) -> Result<Secret<Vec<u8>>, Error> {
+    let mut okm = vec![0; length];
+    let boxed_expand = match algorithm {
+        Algorithm::HkdfSha256 => Hkdf::<Sha256>::from_prk(prk).map(|hkdf| hkdf.expand(info, okm.as_mut_slice())),
+        Algorithm::HkdfSha512 => Hkdf::<Sha512>::from_prk(prk).map(|hkdf| hkdf.expand(info, okm.as_mut_slice())),
+    };
+    if boxed_expand.is_err() {
+        let message = format!("pseudorandom key is too short: {} bytes, expected at least {} bytes", prk.len(), algorithm.hash_length());
+        return Err(Error::key_mismatch_from(message, boxed_expand.err().unwrap()))
+    }
+
+    let boxed_okm = boxed_expand.unwrap();
+    if boxed_okm.is_err() {
+        let message = format!("unable to derive {} bytes with {:?}, maximum is {} bytes", length, algorithm, algorithm.max_output_length());
+        return Err(Error::crypto_from(message, boxed_okm.err().unwrap()))
+    }
+    Ok(Secret::new(okm))
+}
+
+/// Extracts pseudorandom key from the input keying material and expands it into the output keying material of the given length
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::kdf::hkdf::{derive, Algorithm};
+///
+///     #[test]
+///     fn hkdf() {
+///         let shared_secret = [7; 32];
+///
+///         let key = derive(Algorithm::HkdfSha256, "some salt".as_bytes(), &shared_secret, "some purpose".as_bytes(), 32).unwrap();
+///         assert_eq!(key.len(), 32);
+///
+///         // different info gives independent key
+///         let other_key = derive(Algorithm::HkdfSha256, "some salt".as_bytes(), &shared_secret, "other purpose".as_bytes(), 32).unwrap();
+///         assert_ne!(*key, *other_key);
+///     }
+/// ```
+pub fn derive(algorithm: Algorithm, salt: &[u8], ikm: &[u8], info: &[u8], length: usize) -> Result<Secret<Vec<u8>>, Error> {
+    let prk = extract(algorithm, salt, ikm);
+    expand(algorithm, prk.as_slice(), info, length)
+}
+
+/// KeyHierarchy derives any number of labeled keys from the single master key, the same master key and label always give the same key
+///
+/// Keys of different labels, purposes or algorithms are independent, knowing some of them reveals nothing about the others or the master key
+#[derive(Debug, Clone)]
+pub struct KeyHierarchy {
+    algorithm: Algorithm,
+    prk: Secret<Vec<u8>>,
+}
+
+impl KeyHierarchy {
+    /// Creates HKDF-SHA256 key hierarchy from the master key, which is at least 16 bytes long
+    pub fn new(master_key: &[u8]) -> Result<KeyHierarchy, Error> {
+        KeyHierarchy::with_algorithm(master_key, Algorithm::default())
+    }
+
+    /// Creates key hierarchy of the given algorithm from the master key, which is at least 16 bytes long
+    pub fn with_algorithm(master_key: &[u8], algorithm: Algorithm) -> Result<KeyHierarchy, Error> {
+        let boxed_validate = validate_master_key(master_key);
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        let prk = extract(algorithm, HIERARCHY_SALT, master_key);
+        Ok(KeyHierarchy { algorithm, prk })
+    }
+
+    /// Returns algorithm keys are derived with
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Derives key hierarchy for the label, for example per tenant, keys of the child are independent of the keys of the parent
+    pub fn child(&self, label: &str) -> Result<KeyHierarchy, Error> {
+        let boxed_master_key = self.expand(PURPOSE_CHILD, 0, label, self.algorithm.hash_length());
+        if boxed_master_key.is_err() {
+            return Err(boxed_master_key.err().unwrap());
+        }
+        KeyHierarchy::with_algorithm(boxed_master_key.unwrap().as_slice(), self.algorithm)
+    }
+
+    /// Derives raw key of the given length for the label, keys of different lengths are independent
+    pub fn derive_key(&self, label: &str, length: usize) -> Result<Secret<Vec<u8>>, Error> {
+        let boxed_length: Result<u16, _> = length.try_into();
+        if boxed_length.is_err() {
+            let message = format!("unable to derive {} bytes with {:?}, maximum is {} bytes", length, self.algorithm, self.algorithm.max_output_length());
+            return Err(Error::crypto_from(message, boxed_length.err().unwrap()))
+        }
+
+        // length is bound to the info, so shorter key is not a prefix of the longer one
+        let mut labeled = boxed_length.unwrap().to_be_bytes().to_vec();
+        labeled.extend_from_slice(label.as_bytes());
+        let info = [INFO_PREFIX, &[PURPOSE_KEY, 0], labeled.as_slice()].concat();
+        expand(self.algorithm, self.prk.as_slice(), info.as_slice(), length)
+    }
+
+    /// Derives symmetric encryption and decryption parameters for the label
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///     use crypto_ext::kdf::hkdf::setup;
+    ///     use crypto_ext::symmetric::encryption::{decrypt, encrypt, Algorithm};
+    ///
+    ///     #[test]
+    ///     fn key_hierarchy() {
+    ///         // path needs to be accessible by user with write permission for initial setup
+    ///         let hierarchy = setup(Some("/test/key_hierarchy_parameters/")).unwrap();
+    ///
+    ///         // every tenant and every file gets its own key, only the master key is stored
+    ///         let tenant = hierarchy.child("tenant 42").unwrap();
+    ///         let (encryption_params, decryption_params) = tenant.derive_encryption_params("invoice.pdf", Algorithm::Aes256Gcm).unwrap();
+    ///
+    ///         let encrypted = encrypt(encryption_params, "some data".as_bytes(), &[]).unwrap();
+    ///         assert_eq!(decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap(), "some data".as_bytes());
+    ///
+    ///         let (_, other_decryption_params) = tenant.derive_encryption_params("report.pdf", Algorithm::Aes256Gcm).unwrap();
+    ///         assert!(decrypt(other_decryption_params, encrypted.as_slice(), &[]).is_err());
+    ///     }
+    /// ```
+    pub fn derive_encryption_params(&self, label: &str, algorithm: symmetric::encryption::Algorithm) -> Result<(symmetric::encryption::EncryptionParameters, symmetric::encryption::DecryptionParameters), Error> {
+        let boxed_key = self.expand(PURPOSE_ENCRYPTION, algorithm.id(), label, algorithm.key_length());
+        if boxed_key.is_err() {
+            return Err(boxed_key.err().unwrap());
+        }
+        let key = boxed_key.unwrap();
+
+        let encryption_params = symmetric::encryption::EncryptionParameters { algorithm, key: key.clone() };
+        let decryption_params = symmetric::encryption::DecryptionParameters { algorithm, key };
+        Ok((encryption_params, decryption_params))
+    }
+
+    /// Derives message authentication parameters for the label
+    pub fn derive_mac_params(&self, label: &str, algorithm: symmetric::mac::Algorithm) -> Result<symmetric::mac::MacParameters, Error> {
+        let boxed_key = self.expand(PURPOSE_MAC, algorithm.id(), label, algorithm.key_length());
+        if boxed_key.is_err() {
+            return Err(boxed_key.err().unwrap());
+        }
+        Ok(symmetric::mac::MacParameters { algorithm, key: boxed_key.unwrap() })
+    }
+
+    /// Derives signing key pair for the label, verification parameters can be published as usual
+    pub fn derive_signature_params(&self, label: &str, algorithm: signing::Algorithm) -> Result<(signing::SignatureParameters, signing::VerificationParameters), Error> {
+        let boxed_private_key = match algorithm {
+            signing::Algorithm::EcdsaP256 => self.derive_ecdsa_private_key(label),
+            signing::Algorithm::Ed25519 => self.expand(PURPOSE_SIGNATURE, signature_algorithm_id(algorithm), label, 32),
+        };
+        if boxed_private_key.is_err() {
+            return Err(boxed_private_key.err().unwrap());
+        }
+        let private_key = boxed_private_key.unwrap();
+
+        let boxed_public_key = signing::derive_public_key(algorithm, private_key.as_slice());
+        if boxed_public_key.is_err() {
+            return Err(boxed_public_key.err().unwrap());
+        }
+
+        let signature_params = signing::SignatureParameters { algorithm, private_key };
+        let verification_params = signing::VerificationParameters { algorithm, public_key: boxed_public_key.unwrap() };
+        Ok((signature_params, verification_params))
+    }
+
+    fn expand(&self, purpose: u8, algorithm_id: u8, label: &str, length: usize) -> Result<Secret<Vec<u8>>, Error> {
+        let info = [INFO_PREFIX, &[purpose, algorithm_id], label.as_bytes()].concat();
+        expand(self.algorithm, self.prk.as_slice(), info.as_slice(), length)
+    }
+
+    // candidates are tried until one is a valid scalar, the first one is valid with overwhelming probability
+    fn derive_ecdsa_private_key(&self, label: &str) -> Result<Secret<Vec<u8>>, Error> {
+        let algorithm_id = signature_algorithm_id(signing::Algorithm::EcdsaP256);
+        for counter in 0..=255u8 {
+            let info = [INFO_PREFIX, &[PURPOSE_SIGNATURE, algorithm_id], label.as_bytes(), &[counter]].concat();
+            let boxed_candidate = expand(self.algorithm, self.prk.as_slice(), info.as_slice(), 32);
+            if boxed_candidate.is_err() {
+                return Err(boxed_candidate.err().unwrap());
+            }
+            let candidate = boxed_candidate.unwrap();
+
+            if p256::SecretKey::from_be_bytes(candidate.as_slice()).is_ok() {
+                return Ok(candidate)
+            }
+        }
+        Err(Error::crypto("unable to derive ECDSA private key from the master key"))
+    }
+}
+
+/// Will read or create master key at the given location which is relative to the working directory and returns its KeyHierarchy
+///
+/// New master key is created for the default algorithm, HKDF-SHA256. Existing master key is loaded regardless of its algorithm
+pub fn setup(path_to_master_key: Option<&str>) -> Result<KeyHierarchy, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_master_key);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+
+    let boxed_key = get_or_create_key(&boxed_store.unwrap(), Algorithm::default());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, master_key) = boxed_key.unwrap();
+
+    KeyHierarchy::with_algorithm(master_key.as_slice(), algorithm)
+}
+
+/// Will read or create master key for the given algorithm at the given location which is relative to the working directory
+///
+/// Returns an error if master key stored at the location belongs to another algorithm
+pub fn setup_with_algorithm(path_to_master_key: Option<&str>, algorithm: Algorithm) -> Result<KeyHierarchy, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_master_key);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    setup_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Will read or create master key for the given algorithm in the given key store
+///
+/// Returns an error if master key stored in the key store belongs to another algorithm
+pub fn setup_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<KeyHierarchy, Error> {
+    let boxed_key = get_or_create_key(store, algorithm);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (stored_algorithm, master_key) = boxed_key.unwrap();
+
+    if stored_algorithm != algorithm {
+        let message = format!("stored master key is for {:?} algorithm, requested {:?}", stored_algorithm, algorithm);
+        return Err(Error::key_mismatch(message))
+    }
+
+    KeyHierarchy::with_algorithm(master_key.as_slice(), algorithm)
+}
+
+/// Returns KeyHierarchy of the master key stored at the given location which is relative to the working directory
+pub fn get_key_hierarchy(path_to_master_key: Option<&str>) -> Result<KeyHierarchy, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_master_key);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_key_hierarchy_with_store(&boxed_store.unwrap())
+}
+
+/// Returns KeyHierarchy of the master key stored in the given key store
+pub fn get_key_hierarchy_with_store(store: &dyn KeyStore) -> Result<KeyHierarchy, Error> {
+    let boxed_key_file = store.read(KEY_NAME);
+    if boxed_key_file.is_err() {
+        return Err(boxed_key_file.err().unwrap());
+    }
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    let boxed_key = decode_key_file(key_file.as_slice());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, master_key) = boxed_key.unwrap();
+
+    KeyHierarchy::with_algorithm(master_key.as_slice(), algorithm)
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+fn signature_algorithm_id(algorithm: signing::Algorithm) -> u8 {
+    match algorithm {
+        signing::Algorithm::EcdsaP256 => 1,
+        signing::Algorithm::Ed25519 => 2,
+    }
+}
+
+fn get_or_create_key(store: &dyn KeyStore, algorithm: Algorithm) -> Result<(Algorithm, Secret<Vec<u8>>), Error> {
+    let boxed_key = generate_bytes(algorithm.hash_length());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let master_key = Secret::new(boxed_key.unwrap());
+
+    let key_file = encode_key_file(algorithm, master_key.as_slice());
+    let boxed_key_file = store.read_or_create(KEY_NAME, key_file.as_slice());
+    if boxed_key_file.is_err() {
+        return Err(boxed_key_file.err().unwrap());
+    }
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    decode_key_file(key_file.as_slice())
+}
+
+fn encode_key_file(algorithm: Algorithm, master_key: &[u8]) -> Secret<Vec<u8>> {
+    crate::encode_key_file(KEY_FILE_MAGIC, algorithm.id(), master_key)
+}
+
+fn decode_key_file(key_file: &[u8]) -> Result<(Algorithm, Secret<Vec<u8>>), Error> {
+    let boxed_master_key = crate::decode_key_file(KEY_FILE_MAGIC, "master", key_file, Algorithm::from_id);
+    if boxed_master_key.is_err() {
+        return Err(boxed_master_key.err().unwrap());
+    }
+    let (algorithm, master_key) = boxed_master_key.unwrap();
+
+    let boxed_validate = validate_master_key(master_key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    Ok((algorithm, master_key))
+}
+
+fn validate_master_key(master_key: &[u8]) -> Result<(), Error> {
+    if master_key.len() < MIN_MASTER_KEY_LENGTH {
+        let message = format!("master key is too short: {} bytes, expected at least {} bytes", master_key.len(), MIN_MASTER_KEY_LENGTH);
+        return Err(Error::key_mismatch(message))
+    }
+    Ok(())
+}
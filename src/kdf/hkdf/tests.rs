@@ -0,0 +1,223 @@
+use std::fs;
+use crate::Error;
+use crate::asymmetric::signing;
+use crate::kdf::hkdf::{decode_key_file, derive, encode_key_file, expand, extract, get_key_hierarchy, get_key_hierarchy_with_store, setup, setup_with_algorithm, setup_with_store, Algorithm, KeyHierarchy};
+use crate::key_store::MemoryKeyStore;
+use crate::symmetric;
+use crate::test_util::from_hex;
+
+const ALGORITHMS: [Algorithm; 2] = [Algorithm::HkdfSha256, Algorithm::HkdfSha512];
+
+#[test]
+fn rfc5869_vectors() {
+    // test cases 1, 2 and 3 of RFC 5869 appendix A
+    let vectors = [
+        (
+            vec![0x0B; 22],
+            (0x00..=0x0C).collect::<Vec<u8>>(),
+            (0xF0..=0xF9).collect::<Vec<u8>>(),
+            "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5",
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865",
+        ),
+        (
+            (0x00..=0x4F).collect::<Vec<u8>>(),
+            (0x60..=0xAF).collect::<Vec<u8>>(),
+            (0xB0..=0xFF).collect::<Vec<u8>>(),
+            "06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244",
+            "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71cc30c58179ec3e87c14c01d5c1f3434f1d87",
+        ),
+        (
+            vec![0x0B; 22],
+            vec![],
+            vec![],
+            "19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04",
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8",
+        ),
+    ];
+
+    for (ikm, salt, info, prk, okm) in vectors {
+        let extracted = extract(Algorithm::HkdfSha256, salt.as_slice(), ikm.as_slice());
+        assert_eq!(*extracted, from_hex(prk));
+
+        let okm = from_hex(okm);
+        assert_eq!(*expand(Algorithm::HkdfSha256, extracted.as_slice(), info.as_slice(), okm.len()).unwrap(), okm);
+        assert_eq!(*derive(Algorithm::HkdfSha256, salt.as_slice(), ikm.as_slice(), info.as_slice(), okm.len()).unwrap(), okm);
+    }
+}
+
+#[test]
+fn sha512_vectors() {
+    // inputs of RFC 5869 test cases 1 and 3, outputs computed by an independent implementation
+    let salt = (0x00..=0x0C).collect::<Vec<u8>>();
+    let info = (0xF0..=0xF9).collect::<Vec<u8>>();
+    let prk = extract(Algorithm::HkdfSha512, salt.as_slice(), &[0x0B; 22]);
+    assert_eq!(*prk, from_hex("665799823737ded04a88e47e54a5890bb2c3d247c7a4254a8e61350723590a26c36238127d8661b88cf80ef802d57e2f7cebcf1e00e083848be19929c61b4237"));
+    let okm = expand(Algorithm::HkdfSha512, prk.as_slice(), info.as_slice(), 42).unwrap();
+    assert_eq!(*okm, from_hex("832390086cda71fb47625bb5ceb168e4c8e26a1a16ed34d9fc7fe92c1481579338da362cb8d9f925d7cb"));
+
+    let okm = derive(Algorithm::HkdfSha512, &[], &[0x0B; 22], &[], 42).unwrap();
+    assert_eq!(*okm, from_hex("f5fa02b18298a72a8c23898a8703472c6eb179dc204c03425c970e3b164bf90fff22d04836d0e2343bac"));
+}
+
+#[test]
+fn invalid_lengths_are_rejected() {
+    for algorithm in ALGORITHMS {
+        let prk = extract(algorithm, &[], "some input".as_bytes());
+        assert_eq!(prk.len(), algorithm.hash_length());
+
+        let okm = expand(algorithm, prk.as_slice(), &[], algorithm.max_output_length()).unwrap();
+        assert_eq!(okm.len(), algorithm.max_output_length());
+        let error = expand(algorithm, prk.as_slice(), &[], algorithm.max_output_length() + 1).err().unwrap();
+        assert!(matches!(error, Error::Crypto { .. }));
+
+        let error = expand(algorithm, &prk[..algorithm.hash_length() - 1], &[], 32).err().unwrap();
+        assert!(matches!(error, Error::KeyMismatch { .. }));
+
+        assert!(expand(algorithm, prk.as_slice(), &[], 0).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn key_hierarchy_is_deterministic() {
+    // pinned outputs, changing the derivation would make previously derived keys unrecoverable
+    let hierarchy = KeyHierarchy::new(&[1; 32]).unwrap();
+    let (encryption_params, _) = hierarchy.derive_encryption_params("invoice.pdf", symmetric::encryption::Algorithm::Aes256Gcm).unwrap();
+    assert_eq!(*encryption_params.key, from_hex("d31e6d9b94cd00452d571b5934e989270ceab650b3a7b6d4e73dd1a3274f9af3"));
+
+    let tenant = hierarchy.child("tenant 42").unwrap();
+    let (encryption_params, _) = tenant.derive_encryption_params("invoice.pdf", symmetric::encryption::Algorithm::Aes256Gcm).unwrap();
+    assert_eq!(*encryption_params.key, from_hex("626243a5afc9fed80261729a86b26a01d70a543361d8c6597ed816eed0265f4a"));
+
+    let hierarchy = KeyHierarchy::with_algorithm(&[1; 32], Algorithm::HkdfSha512).unwrap();
+    assert_eq!(hierarchy.algorithm(), Algorithm::HkdfSha512);
+    assert_eq!(*hierarchy.derive_key("raw", 16).unwrap(), from_hex("2460344c5053d45e9ce1cc1da422d2c9"));
+}
+
+#[test]
+fn key_hierarchy_keys_are_independent() {
+    let hierarchy = KeyHierarchy::new(&[1; 32]).unwrap();
+    let key = hierarchy.derive_key("label", 32).unwrap();
+    assert_eq!(*key, *KeyHierarchy::new(&[1; 32]).unwrap().derive_key("label", 32).unwrap());
+
+    let others = [
+        hierarchy.derive_key("other label", 32).unwrap(),
+        hierarchy.derive_key("label", 64).unwrap(),
+        hierarchy.child("label").unwrap().derive_key("label", 32).unwrap(),
+        KeyHierarchy::new(&[2; 32]).unwrap().derive_key("label", 32).unwrap(),
+        KeyHierarchy::with_algorithm(&[1; 32], Algorithm::HkdfSha512).unwrap().derive_key("label", 32).unwrap(),
+        hierarchy.derive_encryption_params("label", symmetric::encryption::Algorithm::Aes256Gcm).unwrap().0.key,
+        hierarchy.derive_encryption_params("label", symmetric::encryption::Algorithm::ChaCha20Poly1305).unwrap().0.key,
+        hierarchy.derive_mac_params("label", symmetric::mac::Algorithm::HmacSha256).unwrap().key,
+    ];
+    for other in others {
+        assert_ne!(key[..], other[..32]);
+    }
+
+    // shorter key is not a prefix of the longer one
+    let short_key = hierarchy.derive_key("label", 16).unwrap();
+    assert_ne!(short_key[..], key[..16]);
+
+    let error = KeyHierarchy::new(&[1; 15]).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = hierarchy.derive_key("label", 255 * 32 + 1).err().unwrap();
+    assert!(matches!(error, Error::Crypto { .. }));
+}
+
+#[test]
+fn key_hierarchy_derives_usable_params() {
+    let hierarchy = KeyHierarchy::new(&[1; 32]).unwrap();
+    let data = "some data".as_bytes();
+
+    let algorithms = [
+        symmetric::encryption::Algorithm::Aes128Gcm,
+        symmetric::encryption::Algorithm::Aes256Gcm,
+        symmetric::encryption::Algorithm::ChaCha20Poly1305,
+        symmetric::encryption::Algorithm::XChaCha20Poly1305,
+    ];
+    for algorithm in algorithms {
+        let (encryption_params, decryption_params) = hierarchy.derive_encryption_params("file", algorithm).unwrap();
+        assert_eq!(encryption_params.algorithm, algorithm);
+        assert_eq!(encryption_params.key.len(), algorithm.key_length());
+        let encrypted = symmetric::encryption::encrypt(encryption_params, data, &[]).unwrap();
+        assert_eq!(symmetric::encryption::decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap(), data);
+    }
+
+    let algorithms = [symmetric::mac::Algorithm::HmacSha256, symmetric::mac::Algorithm::HmacSha384, symmetric::mac::Algorithm::HmacSha512];
+    for algorithm in algorithms {
+        let params = hierarchy.derive_mac_params("webhook", algorithm).unwrap();
+        assert_eq!(params.key.len(), algorithm.key_length());
+        let tag = symmetric::mac::mac(params.clone(), data).unwrap();
+        assert!(symmetric::mac::verify_mac(params, data, tag.as_slice()).is_ok());
+    }
+
+    for algorithm in [signing::Algorithm::EcdsaP256, signing::Algorithm::Ed25519] {
+        let (signature_params, verification_params) = hierarchy.derive_signature_params("release", algorithm).unwrap();
+        let (_, same_verification_params) = hierarchy.derive_signature_params("release", algorithm).unwrap();
+        assert_eq!(verification_params.public_key, same_verification_params.public_key);
+
+        let signature = signing::sign(signature_params, data).unwrap();
+        assert!(signing::verify(verification_params.clone(), data, signature.as_slice()).is_ok());
+
+        let (_, other_verification_params) = hierarchy.derive_signature_params("other release", algorithm).unwrap();
+        assert_ne!(verification_params.public_key, other_verification_params.public_key);
+        assert!(signing::verify(other_verification_params, data, signature.as_slice()).is_err());
+    }
+}
+
+#[test]
+fn setup_and_get_key_hierarchy() {
+    let path_to_params = "/test/key_hierarchy_parameters_setup/";
+
+    let hierarchy = setup(Some(path_to_params)).unwrap();
+    assert_eq!(hierarchy.algorithm(), Algorithm::HkdfSha256);
+
+    // stored master key is reused
+    let key = hierarchy.derive_key("label", 32).unwrap();
+    assert_eq!(*setup(Some(path_to_params)).unwrap().derive_key("label", 32).unwrap(), *key);
+    assert_eq!(*get_key_hierarchy(Some(path_to_params)).unwrap().derive_key("label", 32).unwrap(), *key);
+
+    let error = setup_with_algorithm(Some(path_to_params), Algorithm::HkdfSha512).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    fs::remove_dir_all("test/key_hierarchy_parameters_setup").unwrap();
+
+    let error = get_key_hierarchy(Some("/test/missing_key_hierarchy_parameters/")).err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+
+    for algorithm in ALGORITHMS {
+        let store = MemoryKeyStore::new();
+        let hierarchy = setup_with_store(&store, algorithm).unwrap();
+        assert_eq!(hierarchy.algorithm(), algorithm);
+        let key = hierarchy.derive_key("label", 32).unwrap();
+        assert_eq!(*get_key_hierarchy_with_store(&store).unwrap().derive_key("label", 32).unwrap(), *key);
+    }
+}
+
+#[test]
+fn key_file_format() {
+    for algorithm in ALGORITHMS {
+        let key_file = encode_key_file(algorithm, vec![7; algorithm.hash_length()].as_slice());
+        assert!(key_file.starts_with("CXHK".as_bytes()));
+
+        let (decoded_algorithm, decoded_key) = decode_key_file(key_file.as_slice()).unwrap();
+        assert_eq!(algorithm, decoded_algorithm);
+        assert_eq!(*decoded_key, vec![7; algorithm.hash_length()]);
+    }
+
+    let short_key = encode_key_file(Algorithm::HkdfSha256, &[7; 15]);
+    let error = decode_key_file(short_key.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let mut unknown_algorithm = encode_key_file(Algorithm::HkdfSha256, &[7; 32]).to_vec();
+    unknown_algorithm[5] = 0xFF;
+    let error = decode_key_file(unknown_algorithm.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+
+    let mut unknown_version = encode_key_file(Algorithm::HkdfSha256, &[7; 32]).to_vec();
+    unknown_version[4] = 2;
+    let error = decode_key_file(unknown_version.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let error = decode_key_file("not a key".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
@@ -0,0 +1,621 @@
+use std::io::{Read, Write};
+use argon2::Argon2;
+use sha2::Sha256;
+use zeroize::Zeroize;
+use crate::Error;
+use crate::passphrase::generate_bytes;
+use crate::secret::Secret;
+use crate::symmetric;
+
+pub mod hkdf;
+
+#[cfg(test)]
+mod tests;
+
+/// Length of the salt generated by [DerivationParameters::generate] in bytes
+pub const SALT_LENGTH: usize = 16;
+// salt shorter than 64 bits does not prevent precomputation, longer than 512 bits is pointless
+const MIN_SALT_LENGTH: usize = 8;
+const MAX_SALT_LENGTH: usize = 64;
+// upper bound of any key derivation: 4 GiB of memory, 16 GiB of memory passed over in total, 10 million PBKDF2 iterations
+const MAX_MEMORY_COST: u64 = 4 * 1024 * 1024 * 1024;
+const MAX_TIME_COST: u64 = 16 * 1024 * 1024 * 1024;
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+// parameters read from untrusted input are limited to a few times the cost of the constructors of Kdf by default
+const DEFAULT_MAX_MEMORY_COST: u64 = 256 * 1024 * 1024;
+const DEFAULT_MAX_TIME_COST: u64 = 1024 * 1024 * 1024;
+const DEFAULT_MAX_PBKDF2_ITERATIONS: u32 = 2_000_000;
+
+// output of encrypt_with_password layout: magic, format version, symmetric algorithm identifier, derivation parameters, encrypted data
+// derivation parameters layout: kdf identifier, cost parameters, salt length, salt
+const PASSWORD_MAGIC: &[u8] = b"CXPW";
+const PASSWORD_VERSION: u8 = 1;
+const PASSWORD_HEADER_LENGTH: usize = 6;
+
+/// Password based key derivation function and its cost parameters
+///
+/// Higher cost makes each guess of the password slower for the attacker as well as for the user,
+/// constructors return values recommended by OWASP at the time of writing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// Argon2id as defined in RFC 9106, memory cost is in KiB
+    Argon2id { memory_cost: u32, time_cost: u32, parallelism: u32 },
+    /// scrypt as defined in RFC 7914, CPU and memory cost is 2^log_n
+    Scrypt { log_n: u8, block_size: u32, parallelism: u32 },
+    /// PBKDF2 with HMAC-SHA-256 as defined in RFC 8018, use it only when FIPS compliance requires
+    Pbkdf2HmacSha256 { iterations: u32 },
+}
+
+impl Default for Kdf {
+    fn default() -> Kdf {
+        Kdf::argon2id()
+    }
+}
+
+impl Kdf {
+    /// Argon2id with 19 MiB of memory, 2 iterations and single lane
+    pub fn argon2id() -> Kdf {
+        Kdf::Argon2id { memory_cost: 19 * 1024, time_cost: 2, parallelism: 1 }
+    }
+
+    /// scrypt with N = 2^17, r = 8 and p = 1, takes 128 MiB of memory
+    pub fn scrypt() -> Kdf {
+        Kdf::Scrypt { log_n: 17, block_size: 8, parallelism: 1 }
+    }
+
+    /// PBKDF2-HMAC-SHA256 with 600 000 iterations
+    pub fn pbkdf2_hmac_sha256() -> Kdf {
+        Kdf::Pbkdf2HmacSha256 { iterations: 600_000 }
+    }
+
+    fn id(&self) -> u8 {
+        match self {
+            Kdf::Argon2id { .. } => 1,
+            Kdf::Scrypt { .. } => 2,
+            Kdf::Pbkdf2HmacSha256 { .. } => 3,
+        }
+    }
+
+    // checks upper bound of the cost, remaining parameters are checked by the implementation of the function
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if let Kdf::Pbkdf2HmacSha256 { iterations: 0 } = self {
+            return Err(Error::format("PBKDF2 iteration count must be positive"))
+        }
+
+        let limits = KdfLimits { max_memory_cost: MAX_MEMORY_COST, max_time_cost: MAX_TIME_COST, max_pbkdf2_iterations: MAX_PBKDF2_ITERATIONS };
+        limits.check(self)
+    }
+
+    fn memory_cost(&self) -> u64 {
+        match self {
+            Kdf::Argon2id { memory_cost, .. } => *memory_cost as u64 * 1024,
+            Kdf::Scrypt { log_n, block_size, .. } => {
+                // 128 * r * N bytes, saturating for the values too large to represent
+                let blocks = 1u64.checked_shl(*log_n as u32).unwrap_or(u64::MAX);
+                blocks.saturating_mul(128).saturating_mul(*block_size as u64)
+            }
+            Kdf::Pbkdf2HmacSha256 { .. } => 0,
+        }
+    }
+
+    // bytes of memory passed over, Argon2id makes time cost passes over its memory, scrypt repeats its work for each unit of parallelism
+    fn time_cost(&self) -> u64 {
+        match self {
+            Kdf::Argon2id { time_cost, .. } => self.memory_cost().saturating_mul(*time_cost as u64),
+            Kdf::Scrypt { parallelism, .. } => self.memory_cost().saturating_mul(*parallelism as u64),
+            Kdf::Pbkdf2HmacSha256 { .. } => 0,
+        }
+    }
+}
+
+/// KdfLimits cap the cost of key derivation with parameters read from encrypted data or password hashes,
+/// which may come from an untrusted source
+///
+/// Default limits are a few times the cost of the [Kdf] constructors: 256 MiB of memory, 1 GiB of memory passed over
+/// in total and 2 million PBKDF2 iterations. Limits above 4 GiB of memory, 16 GiB passed over and 10 million iterations
+/// have no effect, key derivation never exceeds them.
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::kdf::{decrypt_with_password, decrypt_with_password_with_limits, encrypt_with_password, Kdf, KdfLimits};
+///
+///     #[test]
+///     fn costly_password_encryption() {
+///         let kdf = Kdf::Pbkdf2HmacSha256 { iterations: 3_000_000 };
+///         let encrypted = encrypt_with_password("password", &kdf, "some data".as_bytes(), &[]).unwrap();
+///         assert!(decrypt_with_password("password", encrypted.as_slice(), &[]).is_err());
+///
+///         let limits = KdfLimits { max_pbkdf2_iterations: 5_000_000, ..KdfLimits::default() };
+///         let decrypted = decrypt_with_password_with_limits("password", encrypted.as_slice(), &[], &limits).unwrap();
+///         assert_eq!(decrypted, "some data".as_bytes());
+///     }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfLimits {
+    /// Memory taken by the function in bytes
+    pub max_memory_cost: u64,
+    /// Memory passed over in total in bytes, memory cost times Argon2id time cost or scrypt parallelism
+    pub max_time_cost: u64,
+    pub max_pbkdf2_iterations: u32,
+}
+
+impl Default for KdfLimits {
+    fn default() -> KdfLimits {
+        KdfLimits {
+            max_memory_cost: DEFAULT_MAX_MEMORY_COST,
+            max_time_cost: DEFAULT_MAX_TIME_COST,
+            max_pbkdf2_iterations: DEFAULT_MAX_PBKDF2_ITERATIONS,
+        }
+    }
+}
+
+impl KdfLimits {
+    /// Returns `Error::Format` if cost of the given function exceeds the limits, nothing is allocated or computed
+    pub fn check(&self, kdf: &Kdf) -> Result<(), Error> {
+        if kdf.memory_cost() > self.max_memory_cost {
+            let message = format!("key derivation would take more than {} bytes of memory: {:?}", self.max_memory_cost, kdf);
+            return Err(Error::format(message))
+        }
+
+        if kdf.time_cost() > self.max_time_cost {
+            let message = format!("key derivation would pass over more than {} bytes of memory: {:?}", self.max_time_cost, kdf);
+            return Err(Error::format(message))
+        }
+
+        if let Kdf::Pbkdf2HmacSha256 { iterations } = kdf {
+            if *iterations > self.max_pbkdf2_iterations {
+                let message = format!("PBKDF2 iteration count {} exceeds maximum of {}", iterations, self.max_pbkdf2_iterations);
+                return Err(Error::format(message))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// DerivationParameters are the key derivation function and salt, both are required to derive the same key again
+///
+/// They are not secret and are stored along the encrypted data, see [DerivationParameters::encode].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationParameters {
+    pub kdf: Kdf,
+    pub salt: Vec<u8>,
+}
+
+impl DerivationParameters {
+    /// Creates parameters for the given key derivation function with random 16 bytes long salt
+    pub fn generate(kdf: Kdf) -> Result<DerivationParameters, Error> {
+        let boxed_salt = generate_bytes(SALT_LENGTH);
+        if boxed_salt.is_err() {
+            return Err(boxed_salt.err().unwrap());
+        }
+        Ok(DerivationParameters { kdf, salt: boxed_salt.unwrap() })
+    }
+
+    /// Encodes parameters as bytes: identifier of the function, its cost parameters, salt length and salt
+    ///
+    /// Returns an error if the salt is shorter than 8 or longer than 64 bytes
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let boxed_validate = validate_salt(self.salt.as_slice());
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        let mut encoded = vec![self.kdf.id()];
+        match self.kdf {
+            Kdf::Argon2id { memory_cost, time_cost, parallelism } => {
+                encoded.extend_from_slice(&memory_cost.to_be_bytes());
+                encoded.extend_from_slice(&time_cost.to_be_bytes());
+                encoded.extend_from_slice(&parallelism.to_be_bytes());
+            }
+            Kdf::Scrypt { log_n, block_size, parallelism } => {
+                encoded.push(log_n);
+                encoded.extend_from_slice(&block_size.to_be_bytes());
+                encoded.extend_from_slice(&parallelism.to_be_bytes());
+            }
+            Kdf::Pbkdf2HmacSha256 { iterations } => {
+                encoded.extend_from_slice(&iterations.to_be_bytes());
+            }
+        }
+        encoded.push(self.salt.len() as u8);
+        encoded.extend_from_slice(self.salt.as_slice());
+        Ok(encoded)
+    }
+
+    /// Decodes parameters encoded by [DerivationParameters::encode]
+    pub fn decode(encoded: &[u8]) -> Result<DerivationParameters, Error> {
+        let boxed_params = decode_prefix(encoded);
+        if boxed_params.is_err() {
+            return Err(boxed_params.err().unwrap());
+        }
+        let (params, length) = boxed_params.unwrap();
+        if length != encoded.len() {
+            let message = format!("derivation parameters are followed by {} unexpected bytes", encoded.len() - length);
+            return Err(Error::format(message))
+        }
+        Ok(params)
+    }
+}
+
+/// Derives key of the given length from the password
+///
+/// Parameters decoded from an untrusted source have to be checked with [KdfLimits::check] first.
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::kdf::{derive_key, DerivationParameters, Kdf};
+///
+///     #[test]
+///     fn key_derivation() {
+///         // parameters are stored, the same password and parameters give the same key
+///         let params = DerivationParameters::generate(Kdf::default()).unwrap();
+///
+///         let key = derive_key("correct horse battery staple", &params, 32).unwrap();
+///         assert_eq!(key.len(), 32);
+///         assert_eq!(*key, *derive_key("correct horse battery staple", &params, 32).unwrap());
+///     }
+/// ```
+pub fn derive_key(password: &str, params: &DerivationParameters, length: usize) -> Result<Secret<Vec<u8>>, Error> {
+    let boxed_validate = validate(params);
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let password = password.as_bytes();
+    let salt = params.salt.as_slice();
+    let mut key = vec![0; length];
+    let boxed_derive = match params.kdf {
+        Kdf::Argon2id { memory_cost, time_cost, parallelism } => derive_argon2id(password, salt, memory_cost, time_cost, parallelism, key.as_mut_slice()),
+        Kdf::Scrypt { log_n, block_size, parallelism } => derive_scrypt(password, salt, log_n, block_size, parallelism, key.as_mut_slice()),
+        Kdf::Pbkdf2HmacSha256 { iterations } => derive_pbkdf2_hmac_sha256(password, salt, iterations, key.as_mut_slice()),
+    };
+    if boxed_derive.is_err() {
+        key.zeroize();
+        return Err(boxed_derive.err().unwrap());
+    }
+    Ok(Secret::new(key))
+}
+
+/// Derives symmetric encryption and decryption parameters for the given algorithm from the password
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::kdf::{derive_encryption_params, DerivationParameters, Kdf};
+///     use crypto_ext::symmetric::encryption::{decrypt, encrypt, Algorithm};
+///
+///     #[test]
+///     fn password_encryption_params() {
+///         let derivation_params = DerivationParameters::generate(Kdf::default()).unwrap();
+///         let (encryption_params, decryption_params) = derive_encryption_params("password", &derivation_params, Algorithm::Aes256Gcm).unwrap();
+///
+///         let encrypted = encrypt(encryption_params, "some data".as_bytes(), &[]).unwrap();
+///         assert_eq!(decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap(), "some data".as_bytes());
+///     }
+/// ```
+pub fn derive_encryption_params(password: &str, params: &DerivationParameters, algorithm: symmetric::encryption::Algorithm) -> Result<(symmetric::encryption::EncryptionParameters, symmetric::encryption::DecryptionParameters), Error> {
+    let boxed_key = derive_key(password, params, algorithm.key_length());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let key = boxed_key.unwrap();
+
+    let encryption_params = symmetric::encryption::EncryptionParameters { algorithm, key: key.clone() };
+    let decryption_params = symmetric::encryption::DecryptionParameters { algorithm, key };
+    Ok((encryption_params, decryption_params))
+}
+
+/// Encrypts given byte array with the key derived from the password, derivation parameters with random salt
+/// are prepended to the encrypted data, so [decrypt_with_password] needs only the password
+///
+/// Data is encrypted with AES-256-GCM, see [symmetric::encryption::encrypt]
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::kdf::{decrypt_with_password, encrypt_with_password, Kdf};
+///
+///     #[test]
+///     fn password_encryption() {
+///         let data = "some data to encrypt".as_bytes();
+///         let associated_data = "some unencrypted data".as_bytes();
+///
+///         let encrypted = encrypt_with_password("password", &Kdf::default(), data, associated_data).unwrap();
+///         let decrypted = decrypt_with_password("password", encrypted.as_slice(), associated_data).unwrap();
+///         assert_eq!(data, decrypted);
+///
+///         assert!(decrypt_with_password("wrong password", encrypted.as_slice(), associated_data).is_err());
+///     }
+/// ```
+pub fn encrypt_with_password(password: &str, kdf: &Kdf, data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_header = prepare_password_header(password, kdf);
+    if boxed_header.is_err() {
+        return Err(boxed_header.err().unwrap());
+    }
+    let (mut header, encryption_params) = boxed_header.unwrap();
+
+    // header is authenticated along the associated data, so cost parameters and salt can not be swapped
+    let header_associated_data = [header.as_slice(), associated_data].concat();
+    let boxed_encrypted_data = symmetric::encryption::encrypt(encryption_params, data, header_associated_data.as_slice());
+    if boxed_encrypted_data.is_err() {
+        return Err(boxed_encrypted_data.err().unwrap());
+    }
+
+    header.extend_from_slice(boxed_encrypted_data.unwrap().as_slice());
+    Ok(header)
+}
+
+/// Decrypts data encrypted by [encrypt_with_password]
+///
+/// Cost of the key derivation is read from the encrypted data and checked against default [KdfLimits] before
+/// the key is derived, use [decrypt_with_password_with_limits] for data encrypted with costlier parameters.
+pub fn decrypt_with_password(password: &str, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    decrypt_with_password_with_limits(password, encrypted_data, associated_data, &KdfLimits::default())
+}
+
+/// Decrypts data encrypted by [encrypt_with_password], cost of the key derivation is checked against the given limits
+pub fn decrypt_with_password_with_limits(password: &str, encrypted_data: &[u8], associated_data: &[u8], limits: &KdfLimits) -> Result<Vec<u8>, Error> {
+    let boxed_header = read_password_header(password, encrypted_data, limits);
+    if boxed_header.is_err() {
+        return Err(boxed_header.err().unwrap());
+    }
+    let (header_length, decryption_params) = boxed_header.unwrap();
+    let (header, encrypted_data) = encrypted_data.split_at(header_length);
+
+    let header_associated_data = [header, associated_data].concat();
+    symmetric::encryption::decrypt(decryption_params, encrypted_data, header_associated_data.as_slice())
+}
+
+/// Encrypts everything read from the reader with the key derived from the password and writes it to the writer,
+/// same as [encrypt_with_password] for data that does not fit in memory, returns number of bytes read
+///
+/// Data is encrypted in chunks, see [symmetric::stream::encrypt_stream]
+///
+/// # Examples
+///
+/// ```
+///     use std::io::Cursor;
+///     use crypto_ext::kdf::{decrypt_stream_with_password, encrypt_stream_with_password, Kdf};
+///
+///     #[test]
+///     fn password_stream_encryption() {
+///         let data = vec![7; 1024 * 1024];
+///
+///         // any Read and Write implementation such as File is accepted
+///         let mut encrypted = vec![];
+///         encrypt_stream_with_password("password", &Kdf::default(), Cursor::new(&data), &mut encrypted, &[]).unwrap();
+///
+///         let mut decrypted = vec![];
+///         decrypt_stream_with_password("password", Cursor::new(&encrypted), &mut decrypted, &[]).unwrap();
+///         assert_eq!(data, decrypted);
+///     }
+/// ```
+pub fn encrypt_stream_with_password<R: Read, W: Write>(password: &str, kdf: &Kdf, reader: R, mut writer: W, associated_data: &[u8]) -> Result<u64, Error> {
+    let boxed_header = prepare_password_header(password, kdf);
+    if boxed_header.is_err() {
+        return Err(boxed_header.err().unwrap());
+    }
+    let (header, encryption_params) = boxed_header.unwrap();
+
+    let boxed_write = writer.write_all(header.as_slice());
+    if boxed_write.is_err() {
+        return Err(Error::io("unable to write derivation parameters", boxed_write.err().unwrap()))
+    }
+
+    let header_associated_data = [header.as_slice(), associated_data].concat();
+    symmetric::stream::encrypt_stream(encryption_params, reader, writer, header_associated_data.as_slice())
+}
+
+/// Decrypts stream encrypted by [encrypt_stream_with_password], returns number of bytes written
+///
+/// If an error is returned data written so far is incomplete and has to be discarded, see [symmetric::stream::decrypt_stream].
+/// Cost of the key derivation is checked against default [KdfLimits], see [decrypt_with_password]
+pub fn decrypt_stream_with_password<R: Read, W: Write>(password: &str, reader: R, writer: W, associated_data: &[u8]) -> Result<u64, Error> {
+    decrypt_stream_with_password_with_limits(password, reader, writer, associated_data, &KdfLimits::default())
+}
+
+/// Decrypts stream encrypted by [encrypt_stream_with_password], cost of the key derivation is checked against the given limits
+pub fn decrypt_stream_with_password_with_limits<R: Read, W: Write>(password: &str, mut reader: R, writer: W, associated_data: &[u8], limits: &KdfLimits) -> Result<u64, Error> {
+    // header length is known only once kdf identifier and salt length are read
+    let mut header = vec![0; PASSWORD_HEADER_LENGTH + 1];
+    let boxed_read = read_header_part(&mut reader, &mut header, 0);
+    if boxed_read.is_err() {
+        return Err(boxed_read.err().unwrap());
+    }
+
+    let boxed_length = encoded_length(header[PASSWORD_HEADER_LENGTH]);
+    if boxed_length.is_err() {
+        return Err(boxed_length.err().unwrap());
+    }
+    let offset = header.len();
+    header.resize(PASSWORD_HEADER_LENGTH + boxed_length.unwrap(), 0);
+    let boxed_read = read_header_part(&mut reader, &mut header, offset);
+    if boxed_read.is_err() {
+        return Err(boxed_read.err().unwrap());
+    }
+
+    // salt length is the last byte before the salt
+    let offset = header.len();
+    header.resize(offset + header[offset - 1] as usize, 0);
+    let boxed_read = read_header_part(&mut reader, &mut header, offset);
+    if boxed_read.is_err() {
+        return Err(boxed_read.err().unwrap());
+    }
+
+    let boxed_header = read_password_header(password, header.as_slice(), limits);
+    if boxed_header.is_err() {
+        return Err(boxed_header.err().unwrap());
+    }
+    let (_, decryption_params) = boxed_header.unwrap();
+
+    let header_associated_data = [header.as_slice(), associated_data].concat();
+    symmetric::stream::decrypt_stream(decryption_params, reader, writer, header_associated_data.as_slice())
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+fn prepare_password_header(password: &str, kdf: &Kdf) -> Result<(Vec<u8>, symmetric::encryption::EncryptionParameters), Error> {
+    let algorithm = symmetric::encryption::Algorithm::default();
+
+    let boxed_derivation_params = DerivationParameters::generate(*kdf);
+    if boxed_derivation_params.is_err() {
+        return Err(boxed_derivation_params.err().unwrap());
+    }
+    let derivation_params = boxed_derivation_params.unwrap();
+
+    let boxed_params = derive_encryption_params(password, &derivation_params, algorithm);
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
+    }
+    let (encryption_params, _) = boxed_params.unwrap();
+
+    let mut header = vec![];
+    header.extend_from_slice(PASSWORD_MAGIC);
+    header.push(PASSWORD_VERSION);
+    header.push(algorithm.id());
+
+    let boxed_encoded = derivation_params.encode();
+    if boxed_encoded.is_err() {
+        return Err(boxed_encoded.err().unwrap());
+    }
+    header.extend_from_slice(boxed_encoded.unwrap().as_slice());
+    Ok((header, encryption_params))
+}
+
+// returns length of the header and parameters to decrypt the data following it
+fn read_password_header(password: &str, encrypted_data: &[u8], limits: &KdfLimits) -> Result<(usize, symmetric::encryption::DecryptionParameters), Error> {
+    if encrypted_data.len() < PASSWORD_HEADER_LENGTH || !encrypted_data.starts_with(PASSWORD_MAGIC) {
+        return Err(Error::format("unrecognized password encrypted data format"))
+    }
+
+    let version = encrypted_data[PASSWORD_MAGIC.len()];
+    if version != PASSWORD_VERSION {
+        let message = format!("unsupported password encrypted data version: {}", version);
+        return Err(Error::format(message))
+    }
+
+    let boxed_algorithm = symmetric::encryption::Algorithm::from_id(encrypted_data[PASSWORD_MAGIC.len() + 1]);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+    let algorithm = boxed_algorithm.unwrap();
+
+    let boxed_derivation_params = decode_prefix(&encrypted_data[PASSWORD_HEADER_LENGTH..]);
+    if boxed_derivation_params.is_err() {
+        return Err(boxed_derivation_params.err().unwrap());
+    }
+    let (derivation_params, derivation_params_length) = boxed_derivation_params.unwrap();
+
+    let boxed_check = limits.check(&derivation_params.kdf);
+    if boxed_check.is_err() {
+        return Err(boxed_check.err().unwrap());
+    }
+
+    let boxed_params = derive_encryption_params(password, &derivation_params, algorithm);
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
+    }
+    let (_, decryption_params) = boxed_params.unwrap();
+
+    Ok((PASSWORD_HEADER_LENGTH + derivation_params_length, decryption_params))
+}
+
+fn read_header_part<R: Read>(reader: &mut R, header: &mut [u8], offset: usize) -> Result<(), Error> {
+    let boxed_read = reader.read_exact(&mut header[offset..]);
+    if boxed_read.is_err() {
+        return Err(Error::io("unable to read derivation parameters", boxed_read.err().unwrap()))
+    }
+    Ok(())
+}
+
+// length of the kdf identifier, cost parameters and salt length
+fn encoded_length(id: u8) -> Result<usize, Error> {
+    match id {
+        1 => Ok(1 + 12 + 1),
+        2 => Ok(1 + 9 + 1),
+        3 => Ok(1 + 4 + 1),
+        _ => Err(Error::unsupported_algorithm(format!("unsupported key derivation function identifier: {}", id))),
+    }
+}
+
+// decodes parameters at the beginning of the given bytes, returns them and their length
+fn decode_prefix(encoded: &[u8]) -> Result<(DerivationParameters, usize), Error> {
+    if encoded.is_empty() {
+        return Err(Error::format("derivation parameters are empty"))
+    }
+
+    let boxed_length = encoded_length(encoded[0]);
+    if boxed_length.is_err() {
+        return Err(boxed_length.err().unwrap());
+    }
+    let length = boxed_length.unwrap();
+    if encoded.len() < length {
+        return Err(Error::format("derivation parameters are truncated"))
+    }
+
+    let read_u32 = |offset: usize| u32::from_be_bytes([encoded[offset], encoded[offset + 1], encoded[offset + 2], encoded[offset + 3]]);
+    let kdf = match encoded[0] {
+        1 => Kdf::Argon2id { memory_cost: read_u32(1), time_cost: read_u32(5), parallelism: read_u32(9) },
+        2 => Kdf::Scrypt { log_n: encoded[1], block_size: read_u32(2), parallelism: read_u32(6) },
+        _ => Kdf::Pbkdf2HmacSha256 { iterations: read_u32(1) },
+    };
+
+    let salt_length = encoded[length - 1] as usize;
+    if encoded.len() < length + salt_length {
+        return Err(Error::format("derivation parameters are truncated, salt is incomplete"))
+    }
+    let salt = encoded[length..length + salt_length].to_vec();
+
+    Ok((DerivationParameters { kdf, salt }, length + salt_length))
+}
+
+fn validate(params: &DerivationParameters) -> Result<(), Error> {
+    let boxed_validate = validate_salt(params.salt.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    params.kdf.validate()
+}
+
+fn validate_salt(salt: &[u8]) -> Result<(), Error> {
+    if !(MIN_SALT_LENGTH..=MAX_SALT_LENGTH).contains(&salt.len()) {
+        let message = format!("invalid salt length: {} bytes, expected from {} to {} bytes", salt.len(), MIN_SALT_LENGTH, MAX_SALT_LENGTH);
+        return Err(Error::format(message))
+    }
+    Ok(())
+}
+
+fn derive_argon2id(password: &[u8], salt: &[u8], memory_cost: u32, time_cost: u32, parallelism: u32, key: &mut [u8]) -> Result<(), Error> {
+    let boxed_params = argon2::Params::new(memory_cost, time_cost, parallelism, Some(key.len()));
+    if boxed_params.is_err() {
+        return Err(Error::format_from("invalid Argon2id parameters", boxed_params.err().unwrap()))
+    }
+
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, boxed_params.unwrap());
+    let boxed_hash = argon2.hash_password_into(password, salt, key);
+    if boxed_hash.is_err() {
+        return Err(Error::crypto_from("unable to derive key with Argon2id", boxed_hash.err().unwrap()))
+    }
+    Ok(())
+}
+
+fn derive_scrypt(password: &[u8], salt: &[u8], log_n: u8, block_size: u32, parallelism: u32, key: &mut [u8]) -> Result<(), Error> {
+    let boxed_params = scrypt::Params::new(log_n, block_size, parallelism, key.len());
+    if boxed_params.is_err() {
+        return Err(Error::format_from("invalid scrypt parameters", boxed_params.err().unwrap()))
+    }
+
+    let boxed_hash = scrypt::scrypt(password, salt, &boxed_params.unwrap(), key);
+    if boxed_hash.is_err() {
+        return Err(Error::crypto_from("unable to derive key with scrypt", boxed_hash.err().unwrap()))
+    }
+    Ok(())
+}
+
+fn derive_pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], iterations: u32, key: &mut [u8]) -> Result<(), Error> {
+    pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, key);
+    Ok(())
+}
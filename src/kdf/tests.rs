@@ -0,0 +1,316 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor};
+use crate::Error;
+use crate::kdf::{decrypt_stream_with_password, decrypt_stream_with_password_with_limits, decrypt_with_password, decrypt_with_password_with_limits, derive_encryption_params, derive_key, derive_pbkdf2_hmac_sha256, derive_scrypt, encrypt_stream_with_password, encrypt_with_password, DerivationParameters, Kdf, KdfLimits, SALT_LENGTH};
+use crate::passphrase::generate_bytes;
+use crate::symmetric::encryption::{Algorithm, Decryptor, Encryptor};
+use crate::test_util::from_hex;
+
+// cheap parameters, cost does not matter for correctness
+const FAST_KDFS: [Kdf; 3] = [
+    Kdf::Argon2id { memory_cost: 64, time_cost: 1, parallelism: 1 },
+    Kdf::Scrypt { log_n: 4, block_size: 8, parallelism: 1 },
+    Kdf::Pbkdf2HmacSha256 { iterations: 10 },
+];
+
+#[test]
+fn argon2id_reference_vector() {
+    // reference implementation: argon2 -id -t 2 -m 16 -p 1 -l 32, password "password", salt "somesalt"
+    let kdf = Kdf::Argon2id { memory_cost: 65536, time_cost: 2, parallelism: 1 };
+    let params = DerivationParameters { kdf, salt: "somesalt".as_bytes().to_vec() };
+
+    let key = derive_key("password", &params, 32).unwrap();
+    assert_eq!(*key, from_hex("09316115d5cf24ed5a15a31a3ba326e5cf32edc24702987c02b6566f61913cf7"));
+}
+
+#[test]
+fn scrypt_reference_vector() {
+    // RFC 7914 section 12, salt is shorter than derive_key accepts
+    let mut key = vec![0; 64];
+    derive_scrypt("password".as_bytes(), "NaCl".as_bytes(), 10, 8, 16, key.as_mut_slice()).unwrap();
+    assert_eq!(key, from_hex("fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"));
+}
+
+#[test]
+fn pbkdf2_hmac_sha256_reference_vector() {
+    // RFC 7914 section 11, salt is shorter than derive_key accepts
+    let mut key = vec![0; 64];
+    derive_pbkdf2_hmac_sha256("passwd".as_bytes(), "salt".as_bytes(), 1, key.as_mut_slice()).unwrap();
+    assert_eq!(key, from_hex("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"));
+}
+
+#[test]
+fn derivation_depends_on_password_salt_and_cost() {
+    for kdf in FAST_KDFS {
+        let params = DerivationParameters::generate(kdf).unwrap();
+        assert_eq!(params.salt.len(), SALT_LENGTH);
+
+        let key = derive_key("password", &params, 32).unwrap();
+        assert_eq!(*key, *derive_key("password", &params, 32).unwrap());
+        assert_ne!(*key, *derive_key("Password", &params, 32).unwrap());
+
+        let other_salt = DerivationParameters::generate(kdf).unwrap();
+        assert_ne!(params.salt, other_salt.salt);
+        assert_ne!(*key, *derive_key("password", &other_salt, 32).unwrap());
+
+        let other_cost = match kdf {
+            Kdf::Argon2id { memory_cost, time_cost, parallelism } => Kdf::Argon2id { memory_cost, time_cost: time_cost + 1, parallelism },
+            Kdf::Scrypt { log_n, block_size, parallelism } => Kdf::Scrypt { log_n: log_n + 1, block_size, parallelism },
+            Kdf::Pbkdf2HmacSha256 { iterations } => Kdf::Pbkdf2HmacSha256 { iterations: iterations + 1 },
+        };
+        let other_cost = DerivationParameters { kdf: other_cost, salt: params.salt.clone() };
+        assert_ne!(*key, *derive_key("password", &other_cost, 32).unwrap());
+    }
+}
+
+#[test]
+fn invalid_parameters_are_rejected() {
+    let salt = generate_bytes(SALT_LENGTH).unwrap();
+    let invalid = [
+        DerivationParameters { kdf: Kdf::default(), salt: vec![0; 7] },
+        DerivationParameters { kdf: Kdf::default(), salt: vec![0; 65] },
+        DerivationParameters { kdf: Kdf::Argon2id { memory_cost: 8 * 1024 * 1024, time_cost: 1, parallelism: 1 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Argon2id { memory_cost: 64, time_cost: 0, parallelism: 1 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Argon2id { memory_cost: 64, time_cost: 1, parallelism: 0 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Scrypt { log_n: 23, block_size: 8, parallelism: 1 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Scrypt { log_n: 200, block_size: 8, parallelism: 1 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Scrypt { log_n: 4, block_size: 0, parallelism: 1 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Argon2id { memory_cost: 64, time_cost: u32::MAX, parallelism: 1 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Argon2id { memory_cost: 4 * 1024 * 1024, time_cost: 5, parallelism: 1 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Scrypt { log_n: 17, block_size: 8, parallelism: 1000 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Pbkdf2HmacSha256 { iterations: 0 }, salt: salt.clone() },
+        DerivationParameters { kdf: Kdf::Pbkdf2HmacSha256 { iterations: u32::MAX }, salt },
+    ];
+
+    for params in invalid {
+        let error = derive_key("password", &params, 32).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }), "{:?}", params);
+    }
+}
+
+#[test]
+fn parameters_encoding() {
+    for kdf in [Kdf::argon2id(), Kdf::scrypt(), Kdf::pbkdf2_hmac_sha256()] {
+        let params = DerivationParameters::generate(kdf).unwrap();
+        let encoded = params.encode().unwrap();
+        assert_eq!(DerivationParameters::decode(encoded.as_slice()).unwrap(), params);
+
+        let error = DerivationParameters::decode(&encoded[..encoded.len() - 1]).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }));
+
+        let error = DerivationParameters::decode(&encoded[..3]).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }));
+
+        let error = DerivationParameters::decode([encoded.as_slice(), &[0]].concat().as_slice()).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }));
+    }
+
+    let encoded = DerivationParameters { kdf: Kdf::argon2id(), salt: "somesalt".as_bytes().to_vec() }.encode().unwrap();
+    assert_eq!(encoded, from_hex("0100004c00000000020000000108736f6d6573616c74"));
+
+    // salt length is a single byte, salt which would not decode back is rejected
+    for salt_length in [0, 7, 65, 256, 300] {
+        let error = DerivationParameters { kdf: Kdf::argon2id(), salt: vec![0; salt_length] }.encode().err().unwrap();
+        assert!(matches!(error, Error::Format { .. }), "salt length {}", salt_length);
+    }
+
+    let error = DerivationParameters::decode(&[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let error = DerivationParameters::decode(&[4, 0, 0, 0, 1, 0]).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+}
+
+#[test]
+fn derived_encryption_params() {
+    for algorithm in [Algorithm::Aes128Gcm, Algorithm::Aes256Gcm, Algorithm::ChaCha20Poly1305, Algorithm::XChaCha20Poly1305] {
+        let derivation_params = DerivationParameters::generate(FAST_KDFS[0]).unwrap();
+        let (encryption_params, decryption_params) = derive_encryption_params("password", &derivation_params, algorithm).unwrap();
+        assert_eq!(encryption_params.algorithm, algorithm);
+        assert_eq!(encryption_params.key.len(), algorithm.key_length());
+
+        let encrypted = Encryptor::new(&encryption_params).unwrap().encrypt("some data".as_bytes(), &[]).unwrap();
+
+        // the same password and derivation parameters later give the same key
+        let (_, decryption_params_again) = derive_encryption_params("password", &derivation_params, algorithm).unwrap();
+        assert_eq!(*decryption_params.key, *decryption_params_again.key);
+        let decrypted = Decryptor::new(&decryption_params_again).unwrap().decrypt(encrypted.as_slice(), &[]).unwrap();
+        assert_eq!(decrypted, "some data".as_bytes());
+    }
+}
+
+#[test]
+fn password_encryption_round_trip() {
+    let data = "some data to encrypt".as_bytes();
+    let associated_data = "some unencrypted data".as_bytes();
+
+    for kdf in FAST_KDFS {
+        let encrypted = encrypt_with_password("password", &kdf, data, associated_data).unwrap();
+        assert!(encrypted.starts_with("CXPW".as_bytes()));
+        assert_eq!(decrypt_with_password("password", encrypted.as_slice(), associated_data).unwrap(), data);
+
+        // salt is random, the same data encrypts differently every time
+        let encrypted_again = encrypt_with_password("password", &kdf, data, associated_data).unwrap();
+        assert_ne!(encrypted, encrypted_again);
+
+        let error = decrypt_with_password("wrong password", encrypted.as_slice(), associated_data).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        let error = decrypt_with_password("password", encrypted.as_slice(), "other data".as_bytes()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+    }
+
+    let empty = encrypt_with_password("", &FAST_KDFS[0], &[], &[]).unwrap();
+    assert!(decrypt_with_password("", empty.as_slice(), &[]).unwrap().is_empty());
+}
+
+#[test]
+fn tampered_password_encrypted_data_is_rejected() {
+    let data = "some data to encrypt".as_bytes();
+    let encrypted = encrypt_with_password("password", &FAST_KDFS[2], data, &[]).unwrap();
+
+    // magic, version, algorithm, kdf identifier, iterations, salt length, salt, nonce, data, tag
+    let header_length = 4 + 1 + 1 + 1 + 4 + 1 + SALT_LENGTH;
+    assert_eq!(encrypted.len(), header_length + 12 + data.len() + 16);
+
+    let mut unknown_magic = encrypted.clone();
+    unknown_magic[0] ^= 1;
+    let error = decrypt_with_password("password", unknown_magic.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let mut unknown_version = encrypted.clone();
+    unknown_version[4] = 2;
+    let error = decrypt_with_password("password", unknown_version.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    // weaker cost and other salt give other key, besides header is authenticated
+    for index in [10, header_length - 1, header_length, encrypted.len() - 1] {
+        let mut tampered = encrypted.clone();
+        tampered[index] ^= 1;
+        let error = decrypt_with_password("password", tampered.as_slice(), &[]).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }), "byte {}", index);
+    }
+
+    let truncated_length = [0, 5, header_length - 1, header_length + 12 + 15];
+    for length in truncated_length {
+        let error = decrypt_with_password("password", &encrypted[..length], &[]).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }), "length {}", length);
+    }
+}
+
+#[test]
+fn excessive_cost_of_untrusted_data_is_rejected() {
+    let encrypted = encrypt_with_password("password", &FAST_KDFS[2], "some data".as_bytes(), &[]).unwrap();
+
+    // iteration count follows magic, version, algorithm and kdf identifier
+    let mut endless_iterations = encrypted.clone();
+    endless_iterations[7..11].copy_from_slice(&u32::MAX.to_be_bytes());
+    let error = decrypt_with_password("password", endless_iterations.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+    let error = decrypt_stream_with_password("password", Cursor::new(&endless_iterations), vec![], &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    // time cost follows memory cost
+    let encrypted = encrypt_with_password("password", &FAST_KDFS[0], "some data".as_bytes(), &[]).unwrap();
+    let mut endless_passes = encrypted.clone();
+    endless_passes[11..15].copy_from_slice(&u32::MAX.to_be_bytes());
+    let error = decrypt_with_password("password", endless_passes.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+    let error = decrypt_stream_with_password("password", Cursor::new(&endless_passes), vec![], &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn untrusted_cost_is_limited() {
+    let encrypted = encrypt_with_password("password", &FAST_KDFS[0], "some data".as_bytes(), &[]).unwrap();
+
+    // 1 GiB of memory is within what key derivation supports, but above the default limits; rejection is reported
+    // as a format error, had the key been derived with the wrong parameters decryption would fail authentication instead
+    let mut oversized = encrypted.clone();
+    oversized[7..11].copy_from_slice(&(1024u32 * 1024).to_be_bytes());
+    let error = decrypt_with_password("password", oversized.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+    let error = decrypt_stream_with_password("password", Cursor::new(&oversized), vec![], &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    // limits are configurable
+    let limits = KdfLimits { max_memory_cost: 32 * 1024, ..KdfLimits::default() };
+    let error = decrypt_with_password_with_limits("password", encrypted.as_slice(), &[], &limits).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+    let error = decrypt_stream_with_password_with_limits("password", Cursor::new(&encrypted), vec![], &[], &limits).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let limits = KdfLimits { max_memory_cost: 64 * 1024, max_time_cost: 64 * 1024, max_pbkdf2_iterations: 10 };
+    for kdf in FAST_KDFS {
+        let encrypted = encrypt_with_password("password", &kdf, "some data".as_bytes(), &[]).unwrap();
+        assert_eq!(decrypt_with_password_with_limits("password", encrypted.as_slice(), &[], &limits).unwrap(), "some data".as_bytes());
+
+        let mut encrypted = vec![];
+        encrypt_stream_with_password("password", &kdf, Cursor::new("some data".as_bytes()), &mut encrypted, &[]).unwrap();
+        let mut decrypted = vec![];
+        decrypt_stream_with_password_with_limits("password", Cursor::new(&encrypted), &mut decrypted, &[], &limits).unwrap();
+        assert_eq!(decrypted, "some data".as_bytes());
+    }
+
+    // constructors are within the default limits
+    for kdf in [Kdf::argon2id(), Kdf::scrypt(), Kdf::pbkdf2_hmac_sha256()] {
+        assert!(KdfLimits::default().check(&kdf).is_ok(), "{:?}", kdf);
+    }
+    assert!(KdfLimits::default().check(&Kdf::Scrypt { log_n: 19, block_size: 8, parallelism: 1 }).is_err());
+    assert!(KdfLimits::default().check(&Kdf::Argon2id { memory_cost: 128 * 1024, time_cost: 16, parallelism: 1 }).is_err());
+    assert!(KdfLimits::default().check(&Kdf::Pbkdf2HmacSha256 { iterations: 2_000_001 }).is_err());
+}
+
+#[test]
+fn password_stream_encryption_round_trip() {
+    let data = generate_bytes(200 * 1024).unwrap();
+    let associated_data = "some unencrypted data".as_bytes();
+
+    for kdf in FAST_KDFS {
+        let mut encrypted = vec![];
+        let length = encrypt_stream_with_password("password", &kdf, Cursor::new(&data), &mut encrypted, associated_data).unwrap();
+        assert_eq!(length, data.len() as u64);
+
+        let mut decrypted = vec![];
+        let length = decrypt_stream_with_password("password", Cursor::new(&encrypted), &mut decrypted, associated_data).unwrap();
+        assert_eq!(length, data.len() as u64);
+        assert!(decrypted == data);
+
+        let error = decrypt_stream_with_password("wrong password", Cursor::new(&encrypted), &mut vec![], associated_data).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        let error = decrypt_stream_with_password("password", Cursor::new(&encrypted), &mut vec![], &[]).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        let error = decrypt_stream_with_password("password", Cursor::new(&encrypted[..20]), &mut vec![], associated_data).err().unwrap();
+        assert!(matches!(error, Error::Io { .. }));
+    }
+}
+
+#[test]
+fn password_encrypted_files_round_trip() {
+    let directory = "test/kdf_files";
+    fs::create_dir_all(directory).unwrap();
+    let data = generate_bytes(1024 * 1024).unwrap();
+    fs::write([directory, "data"].join("/"), data.as_slice()).unwrap();
+
+    let reader = BufReader::new(File::open([directory, "data"].join("/")).unwrap());
+    let writer = BufWriter::new(File::create([directory, "data.encrypted"].join("/")).unwrap());
+    encrypt_stream_with_password("password", &FAST_KDFS[0], reader, writer, &[]).unwrap();
+
+    let reader = BufReader::new(File::open([directory, "data.encrypted"].join("/")).unwrap());
+    let writer = BufWriter::new(File::create([directory, "data.decrypted"].join("/")).unwrap());
+    decrypt_stream_with_password("password", reader, writer, &[]).unwrap();
+
+    assert!(fs::read([directory, "data.decrypted"].join("/")).unwrap() == data);
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn assert_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Kdf>();
+    assert_send_sync::<DerivationParameters>();
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zeroize::Zeroize;
+use crate::{create_directory_tree, does_file_exist, get_path_relative_to_working_directory, get_static_filepath, read_file, read_or_create_and_write};
+use crate::Error;
+use crate::secret::Secret;
+use crate::symmetric::encryption::{decrypt, encrypt, Algorithm, DecryptionParameters, EncryptionParameters};
+
+#[cfg(test)]
+mod tests;
+
+/// KeyStore keeps named key material, `setup` and `get_*_params` functions of every module
+/// have `*_with_store` variants accepting any implementation
+///
+/// Names used by the crate:
+///
+/// | module | names |
+/// |---|---|
+/// | symmetric::encryption | `.aes_key`, rotated keyring keys `.aes_key.2`, `.aes_key.3` and so on |
+/// | symmetric::mac | `.hmac_key` |
+/// | asymmetric::encryption | `.rsa_passphrase`, `.rsa_private_key`, `.rsa_public_key`, rotated keyring keys `.rsa_passphrase.2`, `.rsa_private_key.2`, `.rsa_public_key.2` and so on |
+/// | asymmetric::signing | `.ecdsa_private_key.der`, `.ecdsa_public_key.der`, `.ed25519_private_key.der`, `.ed25519_public_key.der` and `.pem` counterparts |
+/// | asymmetric::key_agreement, asymmetric::ecies | `.x25519_private_key`, `.x25519_public_key`, `.ecdh_p256_private_key`, `.ecdh_p256_public_key` |
+/// | kdf::hkdf | `.master_key` |
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::key_store::MemoryKeyStore;
+///     use crypto_ext::symmetric::encryption::{decrypt, encrypt, get_decryption_params_with_store, setup_with_store, Algorithm};
+///
+///     #[test]
+///     fn keys_in_memory() {
+///         let store = MemoryKeyStore::new();
+///         let (encryption_params, _) = setup_with_store(&store, Algorithm::Aes256Gcm).unwrap();
+///
+///         let encrypted = encrypt(encryption_params, "some data".as_bytes(), &[]).unwrap();
+///
+///         let decryption_params = get_decryption_params_with_store(&store).unwrap();
+///         let decrypted = decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap();
+///         assert_eq!(decrypted, "some data".as_bytes());
+///     }
+/// ```
+pub trait KeyStore: Send + Sync {
+    /// Returns whether value is stored under the given name
+    fn contains(&self, name: &str) -> Result<bool, Error>;
+
+    /// Returns value stored under the given name, [Error::Io] with `NotFound` kind if there is none
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error>;
+
+    /// Stores value under the given name unless there is one already, returns the stored value
+    ///
+    /// Concurrent callers have to get the same value back. Key pairs rely on it: public key is derived from the returned
+    /// private key rather than the one just generated, so concurrent setups converge on a matching key pair
+    fn read_or_create(&self, name: &str, value: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// FileSystemKeyStore keeps each value in a separate file inside the given directory
+///
+/// By default file is named after the value, use [FileSystemKeyStore::with_filename] to pick another name.
+/// Missing directory is created, accessible by the owner only, once the first value is stored
+pub struct FileSystemKeyStore {
+    directory: PathBuf,
+    filenames: HashMap<String, String>,
+    // path based functions append file name to the given path as is, as they always did
+    concatenated: bool,
+}
+
+impl FileSystemKeyStore {
+    /// Creates store in the given directory, relative path is resolved against the working directory at the time of access
+    pub fn new(directory: impl Into<PathBuf>) -> FileSystemKeyStore {
+        FileSystemKeyStore {
+            directory: directory.into(),
+            filenames: HashMap::new(),
+            concatenated: false,
+        }
+    }
+
+    /// Stores value of the given name in the file with the given name
+    pub fn with_filename(mut self, name: &str, filename: &str) -> FileSystemKeyStore {
+        self.filenames.insert(name.to_string(), filename.to_string());
+        self
+    }
+
+    /// Returns directory of the store
+    pub fn directory(&self) -> &Path {
+        self.directory.as_path()
+    }
+
+    /// Returns path of the file holding value of the given name
+    pub fn path_of(&self, name: &str) -> PathBuf {
+        let filename = self.filenames.get(name).map(|filename| filename.as_str()).unwrap_or(name);
+        if self.concatenated {
+            let mut path = self.directory.clone().into_os_string();
+            path.push(filename);
+            return PathBuf::from(path);
+        }
+        self.directory.join(filename)
+    }
+
+    // location of the parameters given to path based functions: file name is appended to the working directory
+    // and the given path without a separator, so `Some("/keys/app")` keeps `.aes_key` in `<cwd>/keys/app.aes_key`
+    pub(crate) fn relative_to_working_directory(path_to_encryption_parameters: Option<&str>) -> Result<FileSystemKeyStore, Error> {
+        let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, "");
+        let boxed_directory = get_static_filepath(relative_path.as_str());
+        if boxed_directory.is_err() {
+            return Err(boxed_directory.err().unwrap());
+        }
+        let mut store = FileSystemKeyStore::new(boxed_directory.unwrap());
+        store.concatenated = true;
+        Ok(store)
+    }
+
+    fn filepath_of(&self, name: &str) -> Result<String, Error> {
+        let path = self.path_of(name);
+        let boxed_path = path.to_str();
+        if boxed_path.is_none() {
+            let message = format!("key store path is not a valid UTF-8 path: {}", path.display());
+            return Err(Error::format(message));
+        }
+        Ok(boxed_path.unwrap().to_string())
+    }
+}
+
+impl KeyStore for FileSystemKeyStore {
+    fn contains(&self, name: &str) -> Result<bool, Error> {
+        let boxed_path = self.filepath_of(name);
+        if boxed_path.is_err() {
+            return Err(boxed_path.err().unwrap());
+        }
+        Ok(does_file_exist(boxed_path.unwrap().as_str()))
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let boxed_path = self.filepath_of(name);
+        if boxed_path.is_err() {
+            return Err(boxed_path.err().unwrap());
+        }
+        read_file(boxed_path.unwrap().as_str())
+    }
+
+    fn read_or_create(&self, name: &str, value: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_path = self.filepath_of(name);
+        if boxed_path.is_err() {
+            return Err(boxed_path.err().unwrap());
+        }
+        let path = boxed_path.unwrap();
+
+        let file_path = self.path_of(name);
+        if let Some(directory) = file_path.parent() {
+            let boxed_create = create_directory_tree(directory);
+            if boxed_create.is_err() {
+                return Err(boxed_create.err().unwrap());
+            }
+        }
+
+        read_or_create_and_write(path.as_str(), value)
+    }
+}
+
+/// MemoryKeyStore keeps values in memory of the process, they are wiped once the store is dropped
+#[derive(Default)]
+pub struct MemoryKeyStore {
+    values: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryKeyStore {
+    /// Creates empty store
+    pub fn new() -> MemoryKeyStore {
+        MemoryKeyStore::default()
+    }
+
+    /// Creates store holding the given values, for example keys provisioned by the deployment
+    pub fn with_values(values: HashMap<String, Vec<u8>>) -> MemoryKeyStore {
+        MemoryKeyStore {
+            values: Mutex::new(values),
+        }
+    }
+}
+
+impl Drop for MemoryKeyStore {
+    fn drop(&mut self) {
+        let values = self.values.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for value in values.values_mut() {
+            value.zeroize();
+        }
+    }
+}
+
+impl KeyStore for MemoryKeyStore {
+    fn contains(&self, name: &str) -> Result<bool, Error> {
+        // values are never left half written, so lock poisoned by a panicking thread is still usable
+        let values = self.values.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        Ok(values.contains_key(name))
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let values = self.values.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let boxed_value = values.get(name);
+        if boxed_value.is_none() {
+            let message = format!("no value stored under the name: {}", name);
+            return Err(Error::io(message, io::Error::from(io::ErrorKind::NotFound)));
+        }
+        Ok(boxed_value.unwrap().to_vec())
+    }
+
+    fn read_or_create(&self, name: &str, value: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut values = self.values.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let stored = values.entry(name.to_string()).or_insert_with(|| value.to_vec());
+        Ok(stored.to_vec())
+    }
+}
+
+/// EncryptedFileKeyStore keeps each value in a separate file like [FileSystemKeyStore],
+/// encrypted by the symmetric key given on creation
+///
+/// Name of the value is used as associated data, so encrypted files can not be swapped with each other
+pub struct EncryptedFileKeyStore {
+    files: FileSystemKeyStore,
+    algorithm: Algorithm,
+    key: Secret<Vec<u8>>,
+}
+
+impl EncryptedFileKeyStore {
+    /// Creates store in the given directory, values are encrypted by the given symmetric parameters
+    pub fn new(directory: impl Into<PathBuf>, params: EncryptionParameters) -> EncryptedFileKeyStore {
+        EncryptedFileKeyStore {
+            files: FileSystemKeyStore::new(directory),
+            algorithm: params.algorithm,
+            key: params.key,
+        }
+    }
+
+    /// Stores value of the given name in the file with the given name
+    pub fn with_filename(mut self, name: &str, filename: &str) -> EncryptedFileKeyStore {
+        self.files = self.files.with_filename(name, filename);
+        self
+    }
+
+    /// Returns path of the file holding encrypted value of the given name
+    pub fn path_of(&self, name: &str) -> PathBuf {
+        self.files.path_of(name)
+    }
+
+    fn decrypt_value(&self, name: &str, encrypted_value: &[u8]) -> Result<Vec<u8>, Error> {
+        let params = DecryptionParameters { algorithm: self.algorithm, key: self.key.clone() };
+        decrypt(params, encrypted_value, name.as_bytes())
+    }
+}
+
+impl KeyStore for EncryptedFileKeyStore {
+    fn contains(&self, name: &str) -> Result<bool, Error> {
+        self.files.contains(name)
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let boxed_encrypted_value = self.files.read(name);
+        if boxed_encrypted_value.is_err() {
+            return Err(boxed_encrypted_value.err().unwrap());
+        }
+        self.decrypt_value(name, boxed_encrypted_value.unwrap().as_slice())
+    }
+
+    fn read_or_create(&self, name: &str, value: &[u8]) -> Result<Vec<u8>, Error> {
+        let params = EncryptionParameters { algorithm: self.algorithm, key: self.key.clone() };
+        let boxed_encrypted_value = encrypt(params, value, name.as_bytes());
+        if boxed_encrypted_value.is_err() {
+            return Err(boxed_encrypted_value.err().unwrap());
+        }
+
+        // value stored earlier is returned as is, otherwise the one just encrypted
+        let boxed_stored_value = self.files.read_or_create(name, boxed_encrypted_value.unwrap().as_slice());
+        if boxed_stored_value.is_err() {
+            return Err(boxed_stored_value.err().unwrap());
+        }
+        self.decrypt_value(name, boxed_stored_value.unwrap().as_slice())
+    }
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+// stores the private key unless there is one already, then the public key derived from the stored private key,
+// returns stored private and public key values, derive_public_key and is_same_public_key work on stored values
+pub(crate) fn read_or_create_key_pair(
+    store: &dyn KeyStore,
+    private_key_name: &str,
+    private_key: &[u8],
+    public_key_name: &str,
+    derive_public_key: impl Fn(&[u8]) -> Result<Vec<u8>, Error>,
+    is_same_public_key: impl Fn(&[u8], &[u8]) -> Result<bool, Error>,
+) -> Result<(Secret<Vec<u8>>, Vec<u8>), Error> {
+    let boxed_private_key = store.read_or_create(private_key_name, private_key);
+    if boxed_private_key.is_err() {
+        return Err(boxed_private_key.err().unwrap());
+    }
+    let private_key = Secret::new(boxed_private_key.unwrap());
+
+    let boxed_derived_public_key = derive_public_key(private_key.as_slice());
+    if boxed_derived_public_key.is_err() {
+        return Err(boxed_derived_public_key.err().unwrap());
+    }
+    let derived_public_key = boxed_derived_public_key.unwrap();
+
+    let boxed_public_key = store.read_or_create(public_key_name, derived_public_key.as_slice());
+    if boxed_public_key.is_err() {
+        return Err(boxed_public_key.err().unwrap());
+    }
+    let public_key = boxed_public_key.unwrap();
+
+    let boxed_same_key = is_same_public_key(public_key.as_slice(), derived_public_key.as_slice());
+    if boxed_same_key.is_err() {
+        return Err(boxed_same_key.err().unwrap());
+    }
+    if !boxed_same_key.unwrap() {
+        return Err(Error::key_mismatch("stored public key does not belong to the stored private key"));
+    }
+
+    Ok((private_key, public_key))
+}
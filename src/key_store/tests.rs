@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use crate::asymmetric;
+use crate::asymmetric::signing;
+use crate::Error;
+use crate::read_or_create_and_write_with;
+use crate::key_store::{EncryptedFileKeyStore, FileSystemKeyStore, KeyStore, MemoryKeyStore};
+use crate::passphrase::generate_passphrase_with;
+use crate::passphrase::Alphabet;
+use crate::symmetric;
+
+fn temporary_directory() -> PathBuf {
+    let name = ["crypto_ext_key_store_", generate_passphrase_with(16, Alphabet::Hex).unwrap().as_str()].join("");
+    let directory = env::temp_dir().join(name);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn key_store_contract(store: &dyn KeyStore) {
+    assert!(!store.contains("name").unwrap());
+    let error = store.read("name").err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+
+    assert_eq!(store.read_or_create("name", b"first value").unwrap(), b"first value");
+    assert!(store.contains("name").unwrap());
+    assert_eq!(store.read("name").unwrap(), b"first value");
+
+    // existing value is never overwritten
+    assert_eq!(store.read_or_create("name", b"second value").unwrap(), b"first value");
+    assert_eq!(store.read("name").unwrap(), b"first value");
+
+    assert_eq!(store.read_or_create("other name", b"second value").unwrap(), b"second value");
+    assert_eq!(store.read("name").unwrap(), b"first value");
+}
+
+#[test]
+fn file_system_key_store() {
+    let directory = temporary_directory();
+    let store = FileSystemKeyStore::new(&directory).with_filename("other name", "renamed.key");
+    key_store_contract(&store);
+
+    assert_eq!(fs::read(directory.join("name")).unwrap(), b"first value");
+    assert_eq!(fs::read(directory.join("renamed.key")).unwrap(), b"second value");
+    assert_eq!(store.path_of("other name"), directory.join("renamed.key"));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn file_system_key_store_writes_securely() {
+    let directory = temporary_directory();
+    let store = FileSystemKeyStore::new(&directory);
+
+    let handles: Vec<_> = (0..16u8)
+        .map(|index| {
+            let directory = directory.to_path_buf();
+            thread::spawn(move || FileSystemKeyStore::new(directory).read_or_create("name", &[index; 1024]).unwrap())
+        })
+        .collect();
+    let values: Vec<Vec<u8>> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+    // every caller observes the value published first, and it is never replaced
+    assert!(values.iter().all(|value| *value == values[0]));
+    assert_eq!(store.read("name").unwrap(), values[0]);
+
+    // temporary files are cleaned up
+    let entries: Vec<_> = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(entries, vec!["name"]);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(directory.join("name")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // missing directories are created accessible by the owner only
+    let nested_store = FileSystemKeyStore::new(directory.join("missing").join("nested"));
+    assert!(!nested_store.contains("name").unwrap());
+    assert!(matches!(nested_store.read("name").err().unwrap(), Error::Io { .. }));
+    assert!(!directory.join("missing").exists());
+    assert_eq!(nested_store.read_or_create("name", b"value").unwrap(), b"value");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(directory.join("missing")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        let mode = fs::metadata(directory.join("missing").join("nested")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    // file in place of the directory is reported
+    let file_store = FileSystemKeyStore::new(directory.join("name").join("nested"));
+    let error = file_store.read_or_create("name", b"value").err().unwrap();
+    match error {
+        Error::NotADirectory { path, .. } => assert_eq!(path, directory.join("name")),
+        _ => panic!("unexpected error: {:?}", error),
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn memory_key_store() {
+    let store = MemoryKeyStore::new();
+    key_store_contract(&store);
+
+    let mut values = HashMap::new();
+    values.insert("name".to_string(), b"provisioned value".to_vec());
+    let store = MemoryKeyStore::with_values(values);
+    assert_eq!(store.read_or_create("name", b"first value").unwrap(), b"provisioned value");
+}
+
+#[test]
+fn memory_key_store_is_shared_between_threads() {
+    let store = Arc::new(MemoryKeyStore::new());
+
+    let handles: Vec<_> = (0..8u8)
+        .map(|index| {
+            let store = Arc::clone(&store);
+            thread::spawn(move || store.read_or_create("name", &[index]).unwrap())
+        })
+        .collect();
+    let values: Vec<Vec<u8>> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+    // every thread observes the value created by the first one
+    assert!(values.iter().all(|value| *value == values[0]));
+}
+
+#[test]
+fn encrypted_file_key_store() {
+    let directory = temporary_directory();
+    let (encryption_params, _) = symmetric::encryption::setup_with_store(&MemoryKeyStore::new(), symmetric::encryption::Algorithm::Aes256Gcm).unwrap();
+    let key = encryption_params.key.to_vec();
+    let store = EncryptedFileKeyStore::new(&directory, encryption_params);
+    key_store_contract(&store);
+
+    // values are not stored in plain text
+    let encrypted_value = fs::read(directory.join("name")).unwrap();
+    assert!(!encrypted_value.windows(b"first value".len()).any(|window| window == b"first value"));
+
+    // files of different names can not be swapped
+    fs::copy(directory.join("other name"), directory.join("name")).unwrap();
+    let error = store.read("name").err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    // another key can not read the values
+    let mut wrong_key = key.to_vec();
+    wrong_key[0] ^= 1;
+    let wrong_params = symmetric::encryption::EncryptionParameters { algorithm: symmetric::encryption::Algorithm::Aes256Gcm, key: wrong_key.into() };
+    let wrong_store = EncryptedFileKeyStore::new(&directory, wrong_params);
+    let error = wrong_store.read("other name").err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn modules_accept_any_key_store() {
+    let directory = temporary_directory();
+    let (encryption_params, _) = symmetric::encryption::setup_with_store(&MemoryKeyStore::new(), symmetric::encryption::Algorithm::Aes256Gcm).unwrap();
+    let stores: Vec<Box<dyn KeyStore>> = vec![
+        Box::new(MemoryKeyStore::new()),
+        Box::new(FileSystemKeyStore::new(directory.join("plain"))),
+        Box::new(EncryptedFileKeyStore::new(directory.join("encrypted"), encryption_params)),
+    ];
+
+    let data = "some data".as_bytes();
+    for store in stores {
+        let store = store.as_ref();
+
+        let (encryption_params, _) = symmetric::encryption::setup_with_store(store, symmetric::encryption::Algorithm::ChaCha20Poly1305).unwrap();
+        let encrypted = symmetric::encryption::encrypt(encryption_params, data, &[]).unwrap();
+        let decryption_params = symmetric::encryption::get_decryption_params_with_store(store).unwrap();
+        assert_eq!(symmetric::encryption::decrypt(decryption_params, encrypted.as_slice(), &[]).unwrap(), data);
+
+        let (encryption_params, _) = asymmetric::encryption::setup_with_store(store).unwrap();
+        let encrypted = asymmetric::encryption::encrypt(encryption_params, data).unwrap();
+        let decryption_params = asymmetric::encryption::get_decryption_params_with_store(store).unwrap();
+        assert_eq!(asymmetric::encryption::decrypt(decryption_params, encrypted.as_slice()).unwrap(), data);
+        assert!(asymmetric::encryption::get_encryption_params_with_store(store).is_ok());
+
+        let options = signing::KeyFileOptions::default();
+        let (signature_params, _) = signing::setup_with_store(store, signing::Algorithm::Ed25519, &options).unwrap();
+        let signature = signing::sign(signature_params, data).unwrap();
+        let verification_params = signing::get_verification_params_with_store(store, signing::Algorithm::Ed25519, &options).unwrap();
+        assert!(signing::verify(verification_params, data, signature.as_slice()).is_ok());
+        assert!(signing::get_signature_params_with_store(store, signing::Algorithm::Ed25519, &options).is_ok());
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+fn unsupported_hard_link(_original: &str, _link: &str) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[test]
+fn file_is_published_atomically_without_hard_links() {
+    let directory = temporary_directory();
+    let path = directory.join("name").to_str().unwrap().to_string();
+
+    let handles: Vec<_> = (0..16u8)
+        .map(|index| {
+            let path = path.clone();
+            thread::spawn(move || read_or_create_and_write_with(path.as_str(), &[index; 1024], unsupported_hard_link).unwrap())
+        })
+        .collect();
+    let values: Vec<Vec<u8>> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+    // every caller observes the value published first, and it is never replaced
+    assert!(values.iter().all(|value| *value == values[0]));
+    assert_eq!(fs::read(path.as_str()).unwrap(), values[0]);
+    assert_eq!(read_or_create_and_write_with(path.as_str(), b"value", unsupported_hard_link).unwrap(), values[0]);
+
+    // temporary and lock files are cleaned up
+    let entries: Vec<_> = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(entries, vec!["name"]);
+
+    // lock file of a crashed writer is reported, nothing is published
+    let other_path = directory.join("other").to_str().unwrap().to_string();
+    fs::write([other_path.as_str(), ".lock"].join(""), []).unwrap();
+    let error = read_or_create_and_write_with(other_path.as_str(), b"value", unsupported_hard_link).err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+    assert!(!PathBuf::from(other_path.as_str()).exists());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn path_based_functions_keep_legacy_file_locations() {
+    let working_directory = env::current_dir().unwrap().to_str().unwrap().to_string();
+
+    // file name is appended to the given path as is, without a separator
+    let store = FileSystemKeyStore::relative_to_working_directory(Some("/test/legacy_parameters")).unwrap();
+    let expected = [working_directory.as_str(), "/test/legacy_parameters.aes_key"].join("");
+    assert_eq!(store.path_of(".aes_key"), PathBuf::from(expected.as_str()));
+
+    let store = FileSystemKeyStore::relative_to_working_directory(None).unwrap();
+    let expected_without_path = [working_directory.as_str(), ".aes_key"].join("");
+    assert_eq!(store.path_of(".aes_key"), PathBuf::from(expected_without_path));
+
+    let store = FileSystemKeyStore::relative_to_working_directory(Some("/test/legacy_parameters/")).unwrap();
+    let expected_in_directory = [working_directory.as_str(), "/test/legacy_parameters/.aes_key"].join("");
+    assert_eq!(store.path_of(".aes_key"), PathBuf::from(expected_in_directory));
+
+    // keys of earlier versions are found at their location
+    let _ = fs::remove_file(expected.as_str());
+    let (_, decryption_params) = symmetric::encryption::setup(Some("/test/legacy_parameters")).unwrap();
+    assert!(PathBuf::from(expected.as_str()).is_file());
+    let reloaded = symmetric::encryption::get_decryption_params(Some("/test/legacy_parameters")).unwrap();
+    assert_eq!(reloaded.key.expose(), decryption_params.key.expose());
+}
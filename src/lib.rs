@@ -4,37 +4,62 @@
 //!
 //! ## Features
 //! 1. [Asymmetric cryptography](https://en.wikipedia.org/wiki/Public-key_cryptography) via [RSA](https://en.wikipedia.org/wiki/RSA_(cryptosystem))
-//! 1. [Symmetric cryptography](https://en.wikipedia.org/wiki/Symmetric-key_algorithm) via [AES](https://en.wikipedia.org/wiki/Advanced_Encryption_Standard)
-//! 1. [Digital signature](https://en.wikipedia.org/wiki/Digital_signature) via [ECDSA](https://en.wikipedia.org/wiki/Elliptic_Curve_Digital_Signature_Algorithm)
+//! 1. [Symmetric cryptography](https://en.wikipedia.org/wiki/Symmetric-key_algorithm) via [AES](https://en.wikipedia.org/wiki/Advanced_Encryption_Standard) and [ChaCha20-Poly1305](https://en.wikipedia.org/wiki/ChaCha20-Poly1305)
+//! 1. [Digital signature](https://en.wikipedia.org/wiki/Digital_signature) via [ECDSA](https://en.wikipedia.org/wiki/Elliptic_Curve_Digital_Signature_Algorithm) and [Ed25519](https://en.wikipedia.org/wiki/EdDSA#Ed25519)
 //! 1. [Passphrase](https://en.wikipedia.org/wiki/Passphrase)
 
+// examples in documentation follow the layout of the unit tests they are copied from
+#![allow(clippy::test_attr_in_doctest)]
 
 use std::env;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use std::fs;
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
 use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::passphrase::{Alphabet, generate_passphrase_with};
+use crate::secret::Secret;
 
 pub mod asymmetric;
 pub mod symmetric;
 pub mod passphrase;
+pub mod password;
+pub mod digest;
+pub mod error;
+pub mod key_store;
+pub mod secret;
+pub mod kdf;
+
+#[cfg(test)]
+mod test_util;
+
+pub use error::Error;
+
+// key file layout: magic identifying the kind of key, format version, algorithm identifier, key
+const KEY_FILE_VERSION: u8 = 1;
+
+// how long a writer waits for the lock file of another one where hard links are not supported
+const LOCK_FILE_TIMEOUT_MILLIS: u64 = 10_000;
 
 // below are functions not exposed as an api, used for inner implementation
 
-fn get_static_filepath(path: &str) -> Result<String, String> {
+fn get_static_filepath(path: &str) -> Result<String, Error> {
     let boxed_dir = env::current_dir();
     if boxed_dir.is_err() {
-        let error = boxed_dir.err().unwrap();
-        eprintln!("{}", error);
-        return Err(error.to_string());
+        let error = Error::io("unable to get working directory", boxed_dir.err().unwrap());
+        return Err(error);
     }
     let dir = boxed_dir.unwrap();
 
 
     let boxed_working_directory = dir.as_path().to_str();
     if boxed_working_directory.is_none() {
-        let error = "working directory is not set";
-        eprintln!("{}", error);
-        return Err(error.to_string());
+        let error = Error::format("working directory is not a valid UTF-8 path");
+        return Err(error);
     }
 
     let working_directory = boxed_working_directory.unwrap();
@@ -43,8 +68,7 @@ fn get_static_filepath(path: &str) -> Result<String, String> {
 }
 
 fn get_path_relative_to_working_directory(boxed_path_to_encryption_parameters: Option<&str>, filename: &str) -> String {
-    if boxed_path_to_encryption_parameters.is_some() {
-        let path_to_encryption_parameters = boxed_path_to_encryption_parameters.unwrap();
+    if let Some(path_to_encryption_parameters) = boxed_path_to_encryption_parameters {
         return [path_to_encryption_parameters, filename].join("");
     }
 
@@ -52,41 +76,206 @@ fn get_path_relative_to_working_directory(boxed_path_to_encryption_parameters: O
 }
 
 
-fn read_or_create_and_write(path: &str, content: &[u8]) -> Result<Vec<u8>, String> {
-    let does_passphrase_exist = does_file_exist(path);
-    return if does_passphrase_exist {
-        let boxed_read = read_file(path);
-        if boxed_read.is_err() {
-            return Err(boxed_read.err().unwrap());
+// value of an existing file is never replaced: new content is written to a temporary file next to the target,
+// flushed to disk and published by a hard link which, unlike rename, fails if the target already exists,
+// so concurrent callers converge on the content published first
+fn read_or_create_and_write(path: &str, content: &[u8]) -> Result<Vec<u8>, Error> {
+    read_or_create_and_write_with(path, content, |original, link| fs::hard_link(original, link))
+}
+
+fn read_or_create_and_write_with(path: &str, content: &[u8], hard_link: fn(&str, &str) -> io::Result<()>) -> Result<Vec<u8>, Error> {
+    if does_file_exist(path) {
+        return read_file(path);
+    }
+
+    let boxed_suffix = generate_passphrase_with(16, Alphabet::Hex);
+    if boxed_suffix.is_err() {
+        return Err(boxed_suffix.err().unwrap());
+    }
+    let temporary_path = [path, ".tmp-", boxed_suffix.unwrap().as_str()].join("");
+
+    let boxed_write = write_new_file(temporary_path.as_str(), content);
+    if boxed_write.is_err() {
+        let _ = fs::remove_file(temporary_path.as_str());
+        return Err(boxed_write.err().unwrap());
+    }
+
+    let boxed_link = hard_link(temporary_path.as_str(), path);
+    if boxed_link.is_err() {
+        let error = boxed_link.err().unwrap();
+        if error.kind() == ErrorKind::Unsupported || error.kind() == ErrorKind::PermissionDenied {
+            // filesystem without hard links (FAT, exFAT, some network and container mounts)
+            let boxed_publish = publish_with_lock_file(temporary_path.as_str(), path);
+            let _ = fs::remove_file(temporary_path.as_str());
+            if boxed_publish.is_err() {
+                return Err(boxed_publish.err().unwrap());
+            }
+            if !boxed_publish.unwrap() {
+                // another caller published the file first
+                return read_file(path);
+            }
+            return Ok(Vec::from(content));
         }
-        let passphrase = boxed_read.unwrap();
-        Ok(passphrase)
-    } else {
-        let boxed_create = create_file(path);
-        if boxed_create.is_err() {
-            let message = boxed_create.err().unwrap();
-            return Err(message)
+
+        let _ = fs::remove_file(temporary_path.as_str());
+        if error.kind() == ErrorKind::AlreadyExists {
+            // another caller published the file first
+            return read_file(path);
         }
+        let message = format!("unable to create file: {}", path);
+        return Err(Error::io(message, error));
+    }
+    let _ = fs::remove_file(temporary_path.as_str());
+
+    let boxed_sync = sync_parent_directory(path);
+    if boxed_sync.is_err() {
+        return Err(boxed_sync.err().unwrap());
+    }
+
+    Ok(Vec::from(content))
+}
 
-        let boxed_write = write_file(path, content);
-        if boxed_write.is_err() {
-            let message = boxed_write.err().unwrap();
-            return Err(message)
+// publishes the temporary file by rename while holding an exclusively created lock file, every writer takes the lock
+// before checking for the target, so rename never replaces a published file and readers never see a partial one;
+// returns false if the target was published by another caller. Lock file left behind by a crashed writer
+// is reported rather than ignored, it has to be removed manually
+fn publish_with_lock_file(temporary_path: &str, path: &str) -> Result<bool, Error> {
+    let lock_path = [path, ".lock"].join("");
+    let deadline = Instant::now() + Duration::from_millis(LOCK_FILE_TIMEOUT_MILLIS);
+    loop {
+        if does_file_exist(path) {
+            return Ok(false);
         }
-        Ok(Vec::from(content))
+
+        let boxed_lock = write_new_file(lock_path.as_str(), &[]);
+        if boxed_lock.is_ok() {
+            break;
+        }
+        let error = boxed_lock.err().unwrap();
+        if !is_already_exists(&error) {
+            return Err(error);
+        }
+        if Instant::now() > deadline {
+            let message = format!("lock file exists, remove it if no other process is creating the file: {}", lock_path);
+            return Err(Error::io(message, io::Error::from(ErrorKind::TimedOut)));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let boxed_published = publish_locked(temporary_path, path);
+    let boxed_unlock = fs::remove_file(lock_path.as_str());
+    if boxed_published.is_err() {
+        return Err(boxed_published.err().unwrap());
+    }
+    if boxed_unlock.is_err() {
+        let message = format!("unable to remove lock file: {}", lock_path);
+        return Err(Error::io(message, boxed_unlock.err().unwrap()));
+    }
+    boxed_published
+}
+
+fn publish_locked(temporary_path: &str, path: &str) -> Result<bool, Error> {
+    if does_file_exist(path) {
+        return Ok(false);
+    }
+
+    let boxed_rename = fs::rename(temporary_path, path);
+    if boxed_rename.is_err() {
+        let message = format!("unable to create file: {}", path);
+        return Err(Error::io(message, boxed_rename.err().unwrap()));
+    }
+
+    let boxed_sync = sync_parent_directory(path);
+    if boxed_sync.is_err() {
+        return Err(boxed_sync.err().unwrap());
+    }
+    Ok(true)
+}
+
+fn is_already_exists(error: &Error) -> bool {
+    match error {
+        Error::Io { source, .. } => source.kind() == ErrorKind::AlreadyExists,
+        _ => false,
+    }
+}
+
+// creates file readable and writable by the owner only, fails if the file already exists
+fn write_new_file(path: &str, file_content: &[u8]) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let boxed_open = options.open(path);
+    if boxed_open.is_err() {
+        let message = format!("unable to create file: {}", path);
+        return Err(Error::io(message, boxed_open.err().unwrap()))
+    }
+    let mut file = boxed_open.unwrap();
+
+    let boxed_write = file.write_all(file_content);
+    if boxed_write.is_err() {
+        let message = format!("unable to write to file: {}", path);
+        return Err(Error::io(message, boxed_write.err().unwrap()))
     }
+
+    let boxed_sync = file.sync_all();
+    if boxed_sync.is_err() {
+        let message = format!("unable to flush file to disk: {}", path);
+        return Err(Error::io(message, boxed_sync.err().unwrap()))
+    }
+    Ok(())
 }
 
-fn create_file(path: &str) -> Result<File, String>  {
-    let boxed_file = File::create(path);
+// makes the new directory entry durable, only supported on unix
+fn sync_parent_directory(path: &str) -> Result<(), Error> {
+    #[cfg(unix)]
+    {
+        let directory = Path::new(path).parent().filter(|directory| !directory.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let boxed_directory = File::open(directory);
+        if boxed_directory.is_err() {
+            let message = format!("unable to open directory: {}", directory.display());
+            return Err(Error::io(message, boxed_directory.err().unwrap()))
+        }
 
-    if boxed_file.is_err() {
-        let message = format!("unable to create file: {}", boxed_file.err().unwrap());
-        return Err(message)
+        let boxed_sync = boxed_directory.unwrap().sync_all();
+        if boxed_sync.is_err() {
+            let message = format!("unable to flush directory to disk: {}", directory.display());
+            return Err(Error::io(message, boxed_sync.err().unwrap()))
+        }
     }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
 
-    let file = boxed_file.unwrap();
-    Ok(file)
+// creates missing directories of the path accessible by the owner only (0700 on unix),
+// existing directories are left as is
+fn create_directory_tree(path: &Path) -> Result<(), Error> {
+    for ancestor in path.ancestors() {
+        if ancestor.as_os_str().is_empty() || !ancestor.exists() {
+            continue;
+        }
+        if !ancestor.is_dir() {
+            return Err(Error::not_a_directory(ancestor));
+        }
+        if ancestor == path {
+            return Ok(());
+        }
+        break;
+    }
+
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+
+    let boxed_create = builder.create(path);
+    if boxed_create.is_err() {
+        let message = format!("unable to create directory: {}", path.display());
+        return Err(Error::io(message, boxed_create.err().unwrap()))
+    }
+    Ok(())
 }
 
 fn does_file_exist(path: &str) -> bool {
@@ -94,7 +283,7 @@ fn does_file_exist(path: &str) -> bool {
     file_exists
 }
 
-fn read_file(path: &str) -> Result<Vec<u8>, String> {
+fn read_file(path: &str) -> Result<Vec<u8>, Error> {
     let mut file_contents : Vec<u8> = vec![];
     let boxed_open = OpenOptions::new()
         .read(true)
@@ -103,33 +292,48 @@ fn read_file(path: &str) -> Result<Vec<u8>, String> {
         .truncate(false)
         .open(path);
     if boxed_open.is_err() {
-        let message = format!("unable to read from file: {}", boxed_open.err().unwrap());
-        return Err(message)
+        let message = format!("unable to read from file: {}", path);
+        return Err(Error::io(message, boxed_open.err().unwrap()))
     }
 
     let mut file = boxed_open.unwrap();
 
     let boxed_read = file.read_to_end(&mut file_contents);
     if boxed_read.is_err() {
-        let message = format!("unable to read from file: {}", boxed_read.err().unwrap());
-        return Err(message)
+        let message = format!("unable to read from file: {}", path);
+        return Err(Error::io(message, boxed_read.err().unwrap()))
     }
 
     Ok(file_contents)
 }
 
-fn write_file(path: &str, file_content: &[u8]) -> Result<(), String> {
-    let mut file = OpenOptions::new()
-        .read(false)
-        .write(true)
-        .create(false)
-        .truncate(false)
-        .open(path)
-        .unwrap();
-    let boxed_write = file.write_all(file_content);
-    if boxed_write.is_err() {
-        let message = format!("unable to write to file: {}", boxed_write.err().unwrap());
-        return Err(message)
+fn encode_key_file(magic: &[u8], algorithm_id: u8, key: &[u8]) -> Secret<Vec<u8>> {
+    let mut key_file = Vec::with_capacity(magic.len() + 2 + key.len());
+    key_file.extend_from_slice(magic);
+    key_file.push(KEY_FILE_VERSION);
+    key_file.push(algorithm_id);
+    key_file.extend_from_slice(key);
+    Secret::new(key_file)
+}
+
+// kind names the key in error messages, the key itself is validated by the caller
+fn decode_key_file<A>(magic: &[u8], kind: &str, key_file: &[u8], from_id: fn(u8) -> Result<A, Error>) -> Result<(A, Secret<Vec<u8>>), Error> {
+    let header_length = magic.len() + 2;
+    if key_file.len() < header_length || !key_file.starts_with(magic) {
+        let message = format!("unrecognized {} key file format", kind);
+        return Err(Error::format(message))
     }
-    Ok(())
-}
\ No newline at end of file
+
+    let version = key_file[magic.len()];
+    if version != KEY_FILE_VERSION {
+        let message = format!("unsupported {} key file version: {}", kind, version);
+        return Err(Error::format(message))
+    }
+
+    let boxed_algorithm = from_id(key_file[magic.len() + 1]);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+
+    Ok((boxed_algorithm.unwrap(), Secret::new(key_file[header_length..].to_vec())))
+}
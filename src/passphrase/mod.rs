@@ -1,10 +1,36 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-use sha256::digest;
+use aes_gcm::aead::rand_core::{OsRng, RngCore};
+use crate::Error;
 
 #[cfg(test)]
 mod tests;
 
-/// Generates 256-bit long passphrase
+const HEX: &[u8] = b"0123456789abcdef";
+const BASE64_URL: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Set of characters passphrase is built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    /// lowercase hexadecimal digits, 4 bits of entropy per character
+    Hex,
+    /// url and filename safe base64 alphabet (RFC 4648), 6 bits of entropy per character
+    Base64Url,
+    /// latin letters in both cases and digits, ~5.95 bits of entropy per character
+    Alphanumeric,
+}
+
+impl Alphabet {
+    /// Returns characters of the alphabet
+    pub fn characters(&self) -> &'static [u8] {
+        match self {
+            Alphabet::Hex => HEX,
+            Alphabet::Base64Url => BASE64_URL,
+            Alphabet::Alphanumeric => ALPHANUMERIC,
+        }
+    }
+}
+
+/// Generates 256-bit long passphrase, encoded as 64 hexadecimal characters
 ///
 /// # Examples
 ///
@@ -19,15 +45,73 @@ mod tests;
 ///     }
 ///
 /// ```
-pub fn generate_passphrase() -> Result<String, String> {
-    let now = SystemTime::now();
-    let boxed_time_in_nanos = now.duration_since(UNIX_EPOCH);
-    if boxed_time_in_nanos.is_err() {
-        let message = format!("unable to get system time: {}", boxed_time_in_nanos.err().unwrap());
-        return Err(message)
+pub fn generate_passphrase() -> Result<String, Error> {
+    generate_passphrase_with(64, Alphabet::Hex)
+}
+
+/// Generates passphrase of the given length using characters from the given alphabet
+///
+/// Each character is drawn uniformly from the alphabet using operating system's
+/// cryptographically secure random number generator
+///
+/// # Examples
+///
+/// ```
+///
+///     use crypto_ext::passphrase::{Alphabet, generate_passphrase_with};
+///
+///     #[test]
+///     fn passphrase() {
+///         let passphrase = generate_passphrase_with(32, Alphabet::Alphanumeric).unwrap();
+///         assert_eq!(passphrase.len(), 32);
+///     }
+///
+/// ```
+pub fn generate_passphrase_with(length: usize, alphabet: Alphabet) -> Result<String, Error> {
+    let characters = alphabet.characters();
+    // largest multiple of alphabet size not exceeding 256, bytes above are rejected to avoid modulo bias
+    let limit = 256 - (256 % characters.len());
+
+    let mut passphrase = String::with_capacity(length);
+    while passphrase.len() < length {
+        let boxed_bytes = generate_bytes(length - passphrase.len());
+        if boxed_bytes.is_err() {
+            return Err(boxed_bytes.err().unwrap());
+        }
+        let bytes = boxed_bytes.unwrap();
+
+        for byte in bytes {
+            let byte = byte as usize;
+            if byte < limit {
+                passphrase.push(characters[byte % characters.len()] as char);
+            }
+        }
+    }
+
+    Ok(passphrase)
+}
+
+/// Generates byte array of the given length filled by operating system's cryptographically secure random number generator
+///
+/// # Examples
+///
+/// ```
+///
+///     use crypto_ext::passphrase::generate_bytes;
+///
+///     #[test]
+///     fn bytes() {
+///         let bytes = generate_bytes(32).unwrap();
+///         assert_eq!(bytes.len(), 32);
+///     }
+///
+/// ```
+pub fn generate_bytes(length: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes: Vec<u8> = vec![0; length];
+    let boxed_fill = OsRng.try_fill_bytes(&mut bytes);
+    if boxed_fill.is_err() {
+        let error = Error::crypto_from("unable to generate random bytes", boxed_fill.err().unwrap());
+        return Err(error)
     }
-    let time_in_nanos = boxed_time_in_nanos.unwrap().as_nanos();
-    let hex_time_in_millis = format!("{time_in_nanos:X}");
-    let sha_timestamp = digest(hex_time_in_millis);
-    Ok(sha_timestamp)
-}
\ No newline at end of file
+    Ok(bytes)
+}
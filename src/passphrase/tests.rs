@@ -1,7 +1,78 @@
-use crate::passphrase::generate_passphrase;
+use std::collections::{HashMap, HashSet};
+use crate::passphrase::{Alphabet, generate_bytes, generate_passphrase, generate_passphrase_with};
 
 #[test]
 fn passphrase() {
     let passphrase = generate_passphrase().unwrap();
     assert_eq!(passphrase.len(), 64);
-}
\ No newline at end of file
+    assert!(passphrase.bytes().all(|character| Alphabet::Hex.characters().contains(&character)));
+}
+
+#[test]
+fn passphrase_alphabets() {
+    let alphabets = [Alphabet::Hex, Alphabet::Base64Url, Alphabet::Alphanumeric];
+    for alphabet in alphabets {
+        for length in [0, 1, 17, 64, 255] {
+            let passphrase = generate_passphrase_with(length, alphabet).unwrap();
+            assert_eq!(passphrase.len(), length);
+            assert!(passphrase.bytes().all(|character| alphabet.characters().contains(&character)));
+        }
+    }
+}
+
+#[test]
+fn passphrase_uniqueness() {
+    let mut generated = HashSet::new();
+    for _ in 0..10_000 {
+        let passphrase = generate_passphrase().unwrap();
+        assert!(generated.insert(passphrase));
+    }
+
+    let mut generated = HashSet::new();
+    for _ in 0..10_000 {
+        let bytes = generate_bytes(16).unwrap();
+        assert!(generated.insert(bytes));
+    }
+}
+
+#[test]
+fn passphrase_distribution() {
+    let alphabets = [Alphabet::Hex, Alphabet::Base64Url, Alphabet::Alphanumeric];
+    for alphabet in alphabets {
+        let characters = alphabet.characters();
+        let sample_size = characters.len() * 2_000;
+        let passphrase = generate_passphrase_with(sample_size, alphabet).unwrap();
+
+        let mut occurrences: HashMap<u8, usize> = HashMap::new();
+        for character in passphrase.bytes() {
+            *occurrences.entry(character).or_insert(0) += 1;
+        }
+        assert_eq!(occurrences.len(), characters.len());
+
+        // chi-squared statistic, for up to 63 degrees of freedom 150 is far beyond the 99.999th percentile
+        let expected = (sample_size / characters.len()) as f64;
+        let chi_squared: f64 = occurrences
+            .values()
+            .map(|observed| (*observed as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_squared < 150.0, "{:?} chi-squared {}", alphabet, chi_squared);
+    }
+}
+
+#[test]
+fn bytes_distribution() {
+    let bytes = generate_bytes(256 * 2_000).unwrap();
+
+    let mut occurrences = [0usize; 256];
+    for byte in bytes {
+        occurrences[byte as usize] += 1;
+    }
+
+    // chi-squared statistic, for 255 degrees of freedom 400 is far beyond the 99.999th percentile
+    let expected = 2_000.0;
+    let chi_squared: f64 = occurrences
+        .iter()
+        .map(|observed| (*observed as f64 - expected).powi(2) / expected)
+        .sum();
+    assert!(chi_squared < 400.0, "chi-squared {}", chi_squared);
+}
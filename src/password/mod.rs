@@ -0,0 +1,209 @@
+use argon2::Argon2;
+use password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use pbkdf2::Pbkdf2;
+use scrypt::Scrypt;
+use crate::Error;
+use crate::kdf::{Kdf, KdfLimits, SALT_LENGTH};
+use crate::passphrase::generate_bytes;
+
+#[cfg(test)]
+mod tests;
+
+// length of the hash stored in the PHC string in bytes
+const OUTPUT_LENGTH: usize = 32;
+
+/// Hashes password for storage with Argon2id and default cost parameters, see [hash_password_with]
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::password::{hash_password, verify_password};
+///
+///     #[test]
+///     fn password_hashing() {
+///         // store the hash, for example "$argon2id$v=19$m=19456,t=2,p=1$..."
+///         let hash = hash_password("correct horse battery staple").unwrap();
+///         assert!(hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+///
+///         assert!(verify_password("correct horse battery staple", hash.as_str()).is_ok());
+///         assert!(verify_password("wrong password", hash.as_str()).is_err());
+///     }
+/// ```
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    hash_password_with(password, &Kdf::default())
+}
+
+/// Hashes password for storage with the given function, returns [PHC string](https://github.com/P-H-C/phc-string-format/blob/master/phc-sf-spec.md)
+/// holding the function, its cost parameters, random salt and the hash
+///
+/// Strings are `$argon2id$v=19$m=..,t=..,p=..$salt$hash`, `$scrypt$ln=..,r=..,p=..$salt$hash`
+/// and `$pbkdf2-sha256$i=..,l=32$salt$hash`, compatible with other implementations of the format.
+pub fn hash_password_with(password: &str, kdf: &Kdf) -> Result<String, Error> {
+    let boxed_validate = kdf.validate();
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let boxed_salt = generate_bytes(SALT_LENGTH);
+    if boxed_salt.is_err() {
+        return Err(boxed_salt.err().unwrap());
+    }
+    let boxed_salt = SaltString::encode_b64(boxed_salt.unwrap().as_slice());
+    if boxed_salt.is_err() {
+        return Err(Error::crypto_from("unable to encode salt", boxed_salt.err().unwrap()))
+    }
+    let salt = boxed_salt.unwrap();
+
+    let password = password.as_bytes();
+    let boxed_hash = match *kdf {
+        Kdf::Argon2id { memory_cost, time_cost, parallelism } => {
+            let boxed_params = argon2::Params::new(memory_cost, time_cost, parallelism, Some(OUTPUT_LENGTH));
+            if boxed_params.is_err() {
+                return Err(Error::format_from("invalid Argon2id parameters", boxed_params.err().unwrap()))
+            }
+            let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, boxed_params.unwrap());
+            argon2.hash_password(password, &salt)
+        }
+        Kdf::Scrypt { log_n, block_size, parallelism } => {
+            let boxed_params = scrypt::Params::new(log_n, block_size, parallelism, OUTPUT_LENGTH);
+            if boxed_params.is_err() {
+                return Err(Error::format_from("invalid scrypt parameters", boxed_params.err().unwrap()))
+            }
+            Scrypt.hash_password_customized(password, None, None, boxed_params.unwrap(), &salt)
+        }
+        Kdf::Pbkdf2HmacSha256 { iterations } => {
+            let params = pbkdf2::Params { rounds: iterations, output_length: OUTPUT_LENGTH };
+            Pbkdf2.hash_password_customized(password, Some(pbkdf2::Algorithm::Pbkdf2Sha256.ident()), None, params, &salt)
+        }
+    };
+    if boxed_hash.is_err() {
+        return Err(Error::crypto_from("unable to hash password", boxed_hash.err().unwrap()))
+    }
+    Ok(boxed_hash.unwrap().to_string())
+}
+
+/// Verifies password against the PHC string returned by [hash_password] or [hash_password_with],
+/// hashes are compared in constant time
+///
+/// Returns `Error::Authentication` if the password does not match, `Error::Format` if the string is malformed
+/// and `Error::UnsupportedAlgorithm` if it holds function other than `argon2id`, `scrypt` or `pbkdf2-sha256`.
+/// Cost parameters of the hash are checked against default [KdfLimits] before hashing, see [verify_password_with_limits].
+pub fn verify_password(password: &str, hash: &str) -> Result<(), Error> {
+    verify_password_with_limits(password, hash, &KdfLimits::default())
+}
+
+/// Verifies password against the PHC string, cost parameters of the hash are checked against the given limits
+pub fn verify_password_with_limits(password: &str, hash: &str, limits: &KdfLimits) -> Result<(), Error> {
+    let boxed_hash = PasswordHash::new(hash);
+    if boxed_hash.is_err() {
+        return Err(Error::format_from("unable to parse password hash", boxed_hash.err().unwrap()))
+    }
+    let hash = boxed_hash.unwrap();
+    if hash.salt.is_none() || hash.hash.is_none() {
+        return Err(Error::format("password hash has no salt or hash value"))
+    }
+
+    // cost parameters are checked before hashing, as hash of untrusted origin may require excessive memory
+    let boxed_kdf = kdf_from_hash(&hash);
+    if boxed_kdf.is_err() {
+        return Err(boxed_kdf.err().unwrap());
+    }
+    let kdf = boxed_kdf.unwrap();
+    let boxed_check = limits.check(&kdf);
+    if boxed_check.is_err() {
+        return Err(boxed_check.err().unwrap());
+    }
+    let boxed_validate = kdf.validate();
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    let password = password.as_bytes();
+    let boxed_verify = match hash.algorithm.as_str() {
+        "argon2id" => Argon2::default().verify_password(password, &hash),
+        "scrypt" => Scrypt.verify_password(password, &hash),
+        _ => Pbkdf2.verify_password(password, &hash),
+    };
+    if boxed_verify.is_err() {
+        let error = boxed_verify.err().unwrap();
+        if error == password_hash::Error::Password {
+            return Err(Error::authentication_from("password does not match", error))
+        }
+        return Err(Error::format_from("unable to verify password hash", error))
+    }
+    Ok(())
+}
+
+/// Checks if the hash was created with function or cost parameters other than the given ones
+///
+/// Stored hashes are upgraded on login, when the password is known: verify it, and if the hash is outdated
+/// hash the password again and replace the stored hash.
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::kdf::Kdf;
+///     use crypto_ext::password::{hash_password, hash_password_with, needs_rehash, verify_password};
+///
+///     #[test]
+///     fn rehash_on_login() {
+///         let mut stored_hash = hash_password_with("password", &Kdf::pbkdf2_hmac_sha256()).unwrap();
+///
+///         // on login
+///         assert!(verify_password("password", stored_hash.as_str()).is_ok());
+///         if needs_rehash(stored_hash.as_str(), &Kdf::default()).unwrap() {
+///             stored_hash = hash_password("password").unwrap();
+///         }
+///
+///         assert!(stored_hash.starts_with("$argon2id$"));
+///         assert!(!needs_rehash(stored_hash.as_str(), &Kdf::default()).unwrap());
+///     }
+/// ```
+pub fn needs_rehash(hash: &str, kdf: &Kdf) -> Result<bool, Error> {
+    let boxed_hash = PasswordHash::new(hash);
+    if boxed_hash.is_err() {
+        return Err(Error::format_from("unable to parse password hash", boxed_hash.err().unwrap()))
+    }
+    let hash = boxed_hash.unwrap();
+
+    let boxed_kdf = kdf_from_hash(&hash);
+    if boxed_kdf.is_err() {
+        return Err(boxed_kdf.err().unwrap());
+    }
+    Ok(boxed_kdf.unwrap() != *kdf)
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+fn kdf_from_hash(hash: &PasswordHash) -> Result<Kdf, Error> {
+    match hash.algorithm.as_str() {
+        "argon2id" => {
+            let boxed_params = argon2::Params::try_from(hash);
+            if boxed_params.is_err() {
+                return Err(Error::format_from("invalid Argon2id parameters", boxed_params.err().unwrap()))
+            }
+            let params = boxed_params.unwrap();
+            Ok(Kdf::Argon2id { memory_cost: params.m_cost(), time_cost: params.t_cost(), parallelism: params.p_cost() })
+        }
+        "scrypt" => {
+            let boxed_params = scrypt::Params::try_from(hash);
+            if boxed_params.is_err() {
+                return Err(Error::format_from("invalid scrypt parameters", boxed_params.err().unwrap()))
+            }
+            let params = boxed_params.unwrap();
+            Ok(Kdf::Scrypt { log_n: params.log_n(), block_size: params.r(), parallelism: params.p() })
+        }
+        "pbkdf2-sha256" => {
+            let boxed_params = pbkdf2::Params::try_from(hash);
+            if boxed_params.is_err() {
+                return Err(Error::format_from("invalid PBKDF2 parameters", boxed_params.err().unwrap()))
+            }
+            Ok(Kdf::Pbkdf2HmacSha256 { iterations: boxed_params.unwrap().rounds })
+        }
+        algorithm => {
+            let message = format!("unsupported password hashing algorithm: {}", algorithm);
+            Err(Error::unsupported_algorithm(message))
+        }
+    }
+}
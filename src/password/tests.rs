@@ -0,0 +1,165 @@
+use crate::Error;
+use crate::kdf::{Kdf, KdfLimits};
+use crate::password::{hash_password, hash_password_with, needs_rehash, verify_password, verify_password_with_limits};
+
+// cheap parameters, cost does not matter for correctness
+const FAST_KDFS: [Kdf; 3] = [
+    Kdf::Argon2id { memory_cost: 64, time_cost: 1, parallelism: 1 },
+    Kdf::Scrypt { log_n: 4, block_size: 8, parallelism: 1 },
+    Kdf::Pbkdf2HmacSha256 { iterations: 10 },
+];
+
+// reference vectors of the kdf module encoded as PHC strings
+const ARGON2ID_HASH: &str = "$argon2id$v=19$m=65536,t=2,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc";
+const SCRYPT_HASH: &str = "$scrypt$ln=10,r=8,p=16$TmFDbA$/bq+HJ00cgB4VucZDQHp/nxq18vII3gw53N2Y0s3MWIurzDZLiKjiG/xCSedmDDaxyevuUqD7m2DYMvfoswGQA";
+const PBKDF2_HASH: &str = "$pbkdf2-sha256$i=1,l=64$c2FsdA$VawEblbjCJ/sFpHCJUS2BflBhSFt3gRl5oudV8INrLxJypzM8Xm2RZkWZLOdd+8xfHG4RbHjC9UJESBB06GXgw";
+
+#[test]
+fn reference_hashes() {
+    let hashes = [(ARGON2ID_HASH, "password"), (SCRYPT_HASH, "password"), (PBKDF2_HASH, "passwd")];
+
+    for (hash, password) in hashes {
+        assert!(verify_password(password, hash).is_ok(), "{}", hash);
+
+        let error = verify_password("wrong password", hash).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }), "{}", hash);
+    }
+}
+
+#[test]
+fn hash_and_verify() {
+    let prefixes = ["$argon2id$v=19$m=64,t=1,p=1$", "$scrypt$ln=4,r=8,p=1$", "$pbkdf2-sha256$i=10,l=32$"];
+
+    for (kdf, prefix) in FAST_KDFS.iter().zip(prefixes) {
+        let hash = hash_password_with("password", kdf).unwrap();
+        assert!(hash.starts_with(prefix), "{}", hash);
+        assert!(verify_password("password", hash.as_str()).is_ok());
+
+        let error = verify_password("Password", hash.as_str()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+        let error = verify_password("", hash.as_str()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        // salt is random, the same password hashes differently every time
+        assert_ne!(hash, hash_password_with("password", kdf).unwrap());
+    }
+
+    let hash = hash_password("").unwrap();
+    assert!(hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+    assert!(verify_password("", hash.as_str()).is_ok());
+}
+
+#[test]
+fn tampered_hash_is_rejected() {
+    let hash = hash_password_with("password", &FAST_KDFS[0]).unwrap();
+
+    // other cost parameters, first character of the salt or of the hash, salt is 22 and hash is 43 characters long
+    let replace_character = |index: usize| {
+        let character = if &hash[index..index + 1] == "A" { "B" } else { "A" };
+        [&hash[..index], character, &hash[index + 1..]].concat()
+    };
+    let tampered = [
+        hash.replace("t=1", "t=2"),
+        hash.replace("m=64", "m=65"),
+        replace_character(hash.len() - 43 - 1 - 22),
+        replace_character(hash.len() - 43),
+    ];
+    for tampered in tampered {
+        assert_ne!(tampered, hash);
+        let error = verify_password("password", tampered.as_str()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }), "{}", tampered);
+    }
+}
+
+#[test]
+fn invalid_hashes_are_rejected() {
+    let malformed = [
+        "",
+        "password",
+        "$argon2id$v=19$m=64,t=1,p=1$c29tZXNhbHQ",
+        "$argon2id$v=19$m=64,t=1,p=1$c29tZXNhbHQ$!!",
+        "$argon2id$v=19$m=64,t=0,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        "$scrypt$ln=10,r=8,x=16$TmFDbA$/bq+HJ00cgB4VucZDQHp/nxq18vII3gw53N2Y0s3MWI",
+        // would take 8 GiB of memory
+        "$argon2id$v=19$m=8388608,t=1,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        "$scrypt$ln=24,r=8,p=1$TmFDbA$/bq+HJ00cgB4VucZDQHp/nxq18vII3gw53N2Y0s3MWI",
+        "$pbkdf2-sha256$i=0,l=64$c2FsdA$VawEblbjCJ/sFpHCJUS2BflBhSFt3gRl5oudV8INrLxJypzM8Xm2RZkWZLOdd+8xfHG4RbHjC9UJESBB06GXgw",
+        // above the default limits
+        "$argon2id$v=19$m=1048576,t=1,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        "$scrypt$ln=20,r=8,p=1$TmFDbA$/bq+HJ00cgB4VucZDQHp/nxq18vII3gw53N2Y0s3MWI",
+        "$pbkdf2-sha256$i=5000000,l=64$c2FsdA$VawEblbjCJ/sFpHCJUS2BflBhSFt3gRl5oudV8INrLxJypzM8Xm2RZkWZLOdd+8xfHG4RbHjC9UJESBB06GXgw",
+        // would take days
+        "$argon2id$v=19$m=65536,t=4294967295,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        "$scrypt$ln=17,r=8,p=1000$TmFDbA$/bq+HJ00cgB4VucZDQHp/nxq18vII3gw53N2Y0s3MWI",
+        "$pbkdf2-sha256$i=4294967295,l=64$c2FsdA$VawEblbjCJ/sFpHCJUS2BflBhSFt3gRl5oudV8INrLxJypzM8Xm2RZkWZLOdd+8xfHG4RbHjC9UJESBB06GXgw",
+    ];
+    for hash in malformed {
+        let error = verify_password("password", hash).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }), "{}", hash);
+    }
+
+    let unsupported = [
+        "$argon2i$v=19$m=65536,t=2,p=1$c29tZXNhbHQ$wWKIMhR9lyDFvRz9YTZweHKfbftvj+qf+YFY4NeBbtA",
+        "$pbkdf2-sha512$i=1,l=64$c2FsdA$VawEblbjCJ/sFpHCJUS2BflBhSFt3gRl5oudV8INrLxJypzM8Xm2RZkWZLOdd+8xfHG4RbHjC9UJESBB06GXgw",
+    ];
+    for hash in unsupported {
+        let error = verify_password("password", hash).err().unwrap();
+        assert!(matches!(error, Error::UnsupportedAlgorithm { .. }), "{}", hash);
+
+        let error = needs_rehash(hash, &Kdf::default()).err().unwrap();
+        assert!(matches!(error, Error::UnsupportedAlgorithm { .. }), "{}", hash);
+    }
+
+    let invalid_kdfs = [
+        Kdf::Argon2id { memory_cost: 64, time_cost: 0, parallelism: 1 },
+        Kdf::Argon2id { memory_cost: 8 * 1024 * 1024, time_cost: 1, parallelism: 1 },
+        Kdf::Scrypt { log_n: 4, block_size: 0, parallelism: 1 },
+        Kdf::Pbkdf2HmacSha256 { iterations: 0 },
+        Kdf::Pbkdf2HmacSha256 { iterations: u32::MAX },
+    ];
+    for kdf in invalid_kdfs {
+        let error = hash_password_with("password", &kdf).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }), "{:?}", kdf);
+    }
+}
+
+#[test]
+fn outdated_hashes_need_rehash() {
+    assert!(!needs_rehash(ARGON2ID_HASH, &Kdf::Argon2id { memory_cost: 65536, time_cost: 2, parallelism: 1 }).unwrap());
+    assert!(!needs_rehash(SCRYPT_HASH, &Kdf::Scrypt { log_n: 10, block_size: 8, parallelism: 16 }).unwrap());
+    assert!(!needs_rehash(PBKDF2_HASH, &Kdf::Pbkdf2HmacSha256 { iterations: 1 }).unwrap());
+
+    for hash in [ARGON2ID_HASH, SCRYPT_HASH, PBKDF2_HASH] {
+        assert!(needs_rehash(hash, &Kdf::default()).unwrap());
+    }
+
+    let kdf = FAST_KDFS[0];
+    let hash = hash_password_with("password", &kdf).unwrap();
+    assert!(!needs_rehash(hash.as_str(), &kdf).unwrap());
+    assert!(needs_rehash(hash.as_str(), &Kdf::Argon2id { memory_cost: 128, time_cost: 1, parallelism: 1 }).unwrap());
+    assert!(needs_rehash(hash.as_str(), &Kdf::Argon2id { memory_cost: 64, time_cost: 2, parallelism: 1 }).unwrap());
+    assert!(needs_rehash(hash.as_str(), &FAST_KDFS[1]).unwrap());
+
+    let error = needs_rehash("password", &kdf).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn verification_cost_is_limited() {
+    for kdf in FAST_KDFS {
+        let hash = hash_password_with("password", &kdf).unwrap();
+
+        let limits = KdfLimits { max_memory_cost: 64 * 1024, max_time_cost: 64 * 1024, max_pbkdf2_iterations: 10 };
+        assert!(verify_password_with_limits("password", hash.as_str(), &limits).is_ok(), "{:?}", kdf);
+
+        let limits = KdfLimits { max_memory_cost: 1024, max_time_cost: 1024, max_pbkdf2_iterations: 9 };
+        let error = verify_password_with_limits("password", hash.as_str(), &limits).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }), "{:?}", kdf);
+    }
+
+    // hashes above the default limits are accepted with raised limits
+    let hash = hash_password_with("password", &Kdf::Pbkdf2HmacSha256 { iterations: 2_000_001 }).unwrap();
+    assert!(matches!(verify_password("password", hash.as_str()).err().unwrap(), Error::Format { .. }));
+    let limits = KdfLimits { max_pbkdf2_iterations: 2_000_001, ..KdfLimits::default() };
+    assert!(verify_password_with_limits("password", hash.as_str(), &limits).is_ok());
+}
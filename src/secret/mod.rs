@@ -0,0 +1,70 @@
+use std::fmt;
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+#[cfg(test)]
+mod tests;
+
+/// Secret holds key material, passphrase or another sensitive value and wipes it from memory on drop
+///
+/// Value is accessible via [Deref], `Debug` output never includes it
+///
+/// # Examples
+///
+/// ```
+///
+///     use crypto_ext::secret::Secret;
+///
+///     #[test]
+///     fn secret() {
+///         let passphrase = Secret::new("passphrase".to_string());
+///         assert_eq!(passphrase.as_str(), "passphrase");
+///         assert_eq!(format!("{:?}", passphrase), "Secret([REDACTED])");
+///     }
+///
+/// ```
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    /// Takes ownership of the value, it is wiped once the Secret is dropped
+    pub fn new(value: T) -> Secret<T> {
+        Secret(value)
+    }
+
+    /// Returns reference to the value
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Secret<T> {
+        Secret::new(value)
+    }
+}
+
+impl<T: Zeroize + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Secret<T> {
+        Secret(self.0.clone())
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
@@ -1,133 +1,211 @@
+use std::collections::BTreeMap;
 use aes_gcm::aead::{generic_array::GenericArray, Aead, KeyInit, Payload};
-use aes_gcm::Aes128Gcm;
-use crate::{get_path_relative_to_working_directory, get_static_filepath, read_file, read_or_create_and_write};
-use crate::passphrase::generate_passphrase;
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use crate::Error;
+use crate::key_store::{FileSystemKeyStore, KeyStore};
+use crate::passphrase::generate_bytes;
+use crate::secret::Secret;
 
 #[cfg(test)]
 mod tests;
 
-/// EncryptionParameters is basically the key and nonce
-pub struct EncryptionParameters {
-    pub key: String,
-    pub nonce: String,
-}
-
-/// DecryptionParameters is basically the key and nonce
-pub struct DecryptionParameters {
-    pub key: String,
-    pub nonce: String,
+// name of the key in the key store, file name for the path based functions
+const KEY_NAME: &str = ".aes_key";
+// magic of the key file, format version, algorithm identifier and key follow it
+// files consisting of the bare 16 bytes long key were written by earlier versions and hold AES-128-GCM keys
+const KEY_FILE_MAGIC: &[u8] = b"CXSK";
+const LEGACY_KEY_LENGTH: usize = 16;
+// keyring output layout: magic, format version, key identifier as big endian u32, output of encrypt
+const KEYRING_MAGIC: &[u8] = b"CXKR";
+const KEYRING_VERSION: u8 = 1;
+const KEYRING_HEADER_LENGTH: usize = 9;
+
+/// Authenticated encryption algorithm used to encrypt and decrypt data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// AES-GCM with 128 bits long key
+    Aes128Gcm,
+    /// AES-GCM with 256 bits long key
+    #[default]
+    Aes256Gcm,
+    /// ChaCha20-Poly1305 as defined in RFC 8439
+    ChaCha20Poly1305,
+    /// ChaCha20-Poly1305 with extended 192 bits long nonce
+    XChaCha20Poly1305,
 }
 
-/// Will read or create EncryptionParameters and DecryptionParameters at the given location which is relative to the working directory
-pub fn setup(path_to_encryption_parameters: Option<&str>) -> Result<(EncryptionParameters, DecryptionParameters), String> {
-    let passphrase_64_bytes = generate_passphrase().unwrap();
-    // key is 16 bytes long
-    let aes_key = passphrase_64_bytes[48..64].to_string();
-    // nonce is 12 bytes long
-    let aes_nonce = passphrase_64_bytes[36..48].to_string();
-
+impl Algorithm {
+    /// Returns length of the key in bytes
+    pub fn key_length(&self) -> usize {
+        match self {
+            Algorithm::Aes128Gcm => 16,
+            Algorithm::Aes256Gcm => 32,
+            Algorithm::ChaCha20Poly1305 => 32,
+            Algorithm::XChaCha20Poly1305 => 32,
+        }
+    }
 
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".aes_key");
-    let boxed_aes_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_aes_key_path.is_err() {
-        return Err(boxed_aes_key_path.err().unwrap());
+    /// Returns length of the nonce in bytes, nonce is prepended to each encrypted message
+    pub fn nonce_length(&self) -> usize {
+        match self {
+            Algorithm::Aes128Gcm => 12,
+            Algorithm::Aes256Gcm => 12,
+            Algorithm::ChaCha20Poly1305 => 12,
+            Algorithm::XChaCha20Poly1305 => 24,
+        }
     }
-    let aes_key_path = boxed_aes_key_path.unwrap();
 
-    let boxed_aes_key = read_or_create_and_write(aes_key_path.as_str(), aes_key.as_bytes());
-    if boxed_aes_key.is_err() {
-        let message = boxed_aes_key.err().unwrap();
-        return Err(message)
+    /// Returns length of the authentication tag in bytes, tag is appended to each encrypted message
+    pub fn tag_length(&self) -> usize {
+        16
     }
 
-    let boxed_aes_key = String::from_utf8(boxed_aes_key.unwrap());
-    let aes_key = boxed_aes_key.unwrap();
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Algorithm::Aes128Gcm => 1,
+            Algorithm::Aes256Gcm => 2,
+            Algorithm::ChaCha20Poly1305 => 3,
+            Algorithm::XChaCha20Poly1305 => 4,
+        }
+    }
 
+    pub(crate) fn from_id(id: u8) -> Result<Algorithm, Error> {
+        match id {
+            1 => Ok(Algorithm::Aes128Gcm),
+            2 => Ok(Algorithm::Aes256Gcm),
+            3 => Ok(Algorithm::ChaCha20Poly1305),
+            4 => Ok(Algorithm::XChaCha20Poly1305),
+            _ => Err(Error::unsupported_algorithm(format!("unsupported symmetric encryption algorithm identifier: {}", id))),
+        }
+    }
+}
 
+/// EncryptionParameters is basically the algorithm and key, key is wiped from memory on drop
+#[derive(Debug, Clone)]
+pub struct EncryptionParameters {
+    pub algorithm: Algorithm,
+    pub key: Secret<Vec<u8>>,
+}
 
+/// DecryptionParameters is basically the algorithm and key, key is wiped from memory on drop
+#[derive(Debug, Clone)]
+pub struct DecryptionParameters {
+    pub algorithm: Algorithm,
+    pub key: Secret<Vec<u8>>,
+}
 
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".aes_nonce");
-    let boxed_aes_nonce_path = get_static_filepath(relative_path.as_str());
-    if boxed_aes_nonce_path.is_err() {
-        return Err(boxed_aes_nonce_path.err().unwrap());
+/// Will read or create EncryptionParameters and DecryptionParameters at the given location which is relative to the working directory
+///
+/// New key is created for the default algorithm, AES-256-GCM. Existing key is loaded regardless of its algorithm
+pub fn setup(path_to_encryption_parameters: Option<&str>) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
     }
-    let aes_nonce_path = boxed_aes_nonce_path.unwrap();
+    let store = boxed_store.unwrap();
 
-    let boxed_aes_nonce = read_or_create_and_write(aes_nonce_path.as_str(), aes_nonce.as_bytes());
-    if boxed_aes_nonce.is_err() {
-        let message = boxed_aes_nonce.err().unwrap();
-        return Err(message)
+    let boxed_key = get_or_create_key(&store, KEY_NAME, Algorithm::default());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
     }
+    let (algorithm, aes_key) = boxed_key.unwrap();
 
-    let boxed_aes_nonce = String::from_utf8(boxed_aes_nonce.unwrap());
-    let aes_nonce = boxed_aes_nonce.unwrap();
-
-    let encryption_params = EncryptionParameters { key: aes_key.to_string(), nonce: aes_nonce.to_string() };
-    let decryption_params = DecryptionParameters { key: aes_key.to_string(), nonce: aes_nonce.to_string() };
+    let encryption_params = EncryptionParameters { algorithm, key: aes_key.clone() };
+    let decryption_params = DecryptionParameters { algorithm, key: aes_key };
 
     Ok((encryption_params, decryption_params))
 }
 
-/// Returns EncryptionParameters stored at the given location which is relative to the working directory
-pub fn get_encryption_params(path_to_encryption_parameters: Option<&str>) -> Result<EncryptionParameters, String> {
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".aes_key");
-    let boxed_public_key_path = get_static_filepath(relative_path.as_str());
-    if boxed_public_key_path.is_err() {
-        return Err(boxed_public_key_path.err().unwrap());
+/// Will read or create EncryptionParameters and DecryptionParameters for the given algorithm at the given location which is relative to the working directory
+///
+/// Returns an error if key stored at the location belongs to another algorithm
+pub fn setup_with_algorithm(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
     }
-    let public_key_path = boxed_public_key_path.unwrap();
+    setup_with_store(&boxed_store.unwrap(), algorithm)
+}
 
+/// Will read or create EncryptionParameters and DecryptionParameters for the given algorithm in the given key store
+///
+/// Returns an error if key stored in the key store belongs to another algorithm
+pub fn setup_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<(EncryptionParameters, DecryptionParameters), Error> {
+    let boxed_key = get_or_create_key(store, KEY_NAME, algorithm);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (stored_algorithm, aes_key) = boxed_key.unwrap();
 
-    let boxed_public_key = read_file(public_key_path.as_str());
-    if boxed_public_key.is_err() {
-        let message = boxed_public_key.err().unwrap();
-        return Err(message)
+    if stored_algorithm != algorithm {
+        let message = format!("stored key is for {:?} algorithm, requested {:?}", stored_algorithm, algorithm);
+        return Err(Error::key_mismatch(message))
     }
-    let boxed_public_key = String::from_utf8(boxed_public_key.unwrap());
-    let aes_key = boxed_public_key.unwrap();
 
+    let encryption_params = EncryptionParameters { algorithm, key: aes_key.clone() };
+    let decryption_params = DecryptionParameters { algorithm, key: aes_key };
 
-    let relative_path = get_path_relative_to_working_directory(path_to_encryption_parameters, ".aes_nonce");
-    let boxed_aes_nonce_path = get_static_filepath(relative_path.as_str());
-    if boxed_aes_nonce_path.is_err() {
-        return Err(boxed_aes_nonce_path.err().unwrap());
-    }
-    let aes_nonce_path = boxed_aes_nonce_path.unwrap();
+    Ok((encryption_params, decryption_params))
+}
 
+/// Returns EncryptionParameters stored at the given location which is relative to the working directory
+pub fn get_encryption_params(path_to_encryption_parameters: Option<&str>) -> Result<EncryptionParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_encryption_params_with_store(&boxed_store.unwrap())
+}
 
-    let boxed_aes_nonce = read_file(aes_nonce_path.as_str());
-    if boxed_aes_nonce.is_err() {
-        let message = boxed_aes_nonce.err().unwrap();
+/// Returns EncryptionParameters stored in the given key store
+pub fn get_encryption_params_with_store(store: &dyn KeyStore) -> Result<EncryptionParameters, Error> {
+    let boxed_key_file = store.read(KEY_NAME);
+    if boxed_key_file.is_err() {
+        let message = boxed_key_file.err().unwrap();
         return Err(message)
     }
-    let boxed_aes_nonce = String::from_utf8(boxed_aes_nonce.unwrap());
-    let aes_nonce = boxed_aes_nonce.unwrap();
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    let boxed_key = decode_key_file(key_file.as_slice());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, aes_key) = boxed_key.unwrap();
 
     let encryption_params = EncryptionParameters {
-        key: aes_key.to_string(),
-        nonce: aes_nonce.to_string(),
+        algorithm,
+        key: aes_key,
     };
 
     Ok(encryption_params)
 }
 
 /// Returns DecryptionParameters stored at the given location which is relative to the working directory
-pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Result<DecryptionParameters, String> {
-    // in symmetric encryption same key and nonce used for encryption and decryption
-    let boxed_encryption_params = get_encryption_params(path_to_encryption_parameters);
+pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Result<DecryptionParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_decryption_params_with_store(&boxed_store.unwrap())
+}
+
+/// Returns DecryptionParameters stored in the given key store
+pub fn get_decryption_params_with_store(store: &dyn KeyStore) -> Result<DecryptionParameters, Error> {
+    // in symmetric encryption same key used for encryption and decryption
+    let boxed_encryption_params = get_encryption_params_with_store(store);
     if boxed_encryption_params.is_err() {
-        let message = boxed_encryption_params.err().unwrap().to_string();
-        return Err(message)
+        return Err(boxed_encryption_params.err().unwrap())
     }
     let encryption_params = boxed_encryption_params.unwrap();
     let decryption_params = DecryptionParameters {
-        key: encryption_params.key.to_string(),
-        nonce: encryption_params.nonce.to_string()
+        algorithm: encryption_params.algorithm,
+        key: encryption_params.key,
     };
     Ok(decryption_params)
 }
 
-/// Encrypts given byte array
+/// Encrypts given byte array, random nonce is generated for each call and prepended to the encrypted data
 ///
 /// # Examples
 ///
@@ -140,8 +218,7 @@ pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Res
 ///         let associated_data = "some unencrypted data that needs to be sent along the encrypted data and won't be changed during transmission by a hacker".as_bytes();
 ///
 ///         // path needs to be accessible by user with write permission for initial setup
-///         // ideally for each encryption you need to setup unique folder for AES key and nonce
-///         // do not reuse same setup with Encryption and Decryption parameters for multiple encryptions
+///         // same setup can be reused for multiple encryptions, nonce is generated for each message
 ///         let params_path = "/test/encryption_parameters/";
 ///
 ///
@@ -156,29 +233,16 @@ pub fn get_decryption_params(path_to_encryption_parameters: Option<&str>) -> Res
 ///     }
 ///
 /// ```
-pub fn encrypt(params: EncryptionParameters, data_to_encrypt: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, String> {
-    let payload = Payload {
-        msg: data_to_encrypt,
-        aad: associated_data,
-    };
-
-    let key = GenericArray::from_slice(params.key.as_bytes());
-    let nonce = GenericArray::from_slice(params.nonce.as_bytes());
-
-    let cipher = Aes128Gcm::new(key);
-    let boxed_cipher_text = cipher.encrypt(nonce, payload);
-    if boxed_cipher_text.is_err() {
-        let message = boxed_cipher_text.err().unwrap().to_string();
-        return Err(message)
+pub fn encrypt(params: EncryptionParameters, data_to_encrypt: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_encryptor = Encryptor::new(&params);
+    if boxed_encryptor.is_err() {
+        return Err(boxed_encryptor.err().unwrap());
     }
-
-    let cipher_text = boxed_cipher_text.unwrap();
-
-    Ok(cipher_text)
+    boxed_encryptor.unwrap().encrypt(data_to_encrypt, associated_data)
 }
 
 
-/// Decrypts given byte array
+/// Decrypts given byte array, nonce is read from the beginning of the encrypted data
 ///
 /// # Examples
 ///
@@ -192,8 +256,7 @@ pub fn encrypt(params: EncryptionParameters, data_to_encrypt: &[u8], associated_
 ///         let associated_data = "some unencrypted data that needs to be sent along the encrypted data and won't be changed during transmission by a hacker".as_bytes();
 ///
 ///         // path needs to be accessible by user with write permission for initial setup
-///         // ideally for each encryption you need to setup unique folder for AES key and nonce
-///         // do not reuse same setup with Encryption and Decryption parameters for multiple encryptions
+///         // same setup can be reused for multiple encryptions, nonce is generated for each message
 ///         let params_path = "/test/encryption_parameters/";
 ///
 ///
@@ -209,23 +272,537 @@ pub fn encrypt(params: EncryptionParameters, data_to_encrypt: &[u8], associated_
 ///
 ///     }
 /// ```
-pub fn decrypt(params: DecryptionParameters, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, String> {
-    let payload = Payload {
-        msg: encrypted_data,
-        aad: associated_data,
-    };
+pub fn decrypt(params: DecryptionParameters, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_decryptor = Decryptor::new(&params);
+    if boxed_decryptor.is_err() {
+        return Err(boxed_decryptor.err().unwrap());
+    }
+    boxed_decryptor.unwrap().decrypt(encrypted_data, associated_data)
+}
 
-    let key = GenericArray::from_slice(params.key.as_bytes());
-    let nonce = GenericArray::from_slice(params.nonce.as_bytes());
+/// Keyring holds versioned keys identified by key ID, one of them is primary
+///
+/// Data is encrypted with the primary key and tagged with its key ID, so it is still decrypted
+/// after the primary key is rotated, as long as the old key is in the keyring
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::symmetric::encryption::{rotate_keyring, setup_keyring, Algorithm, Keyring};
+///
+///     #[test]
+///     fn key_rotation() {
+///         // path needs to be accessible by user with write permission for initial setup
+///         let params_path = "/test/keyring_parameters/";
+///         let keyring = setup_keyring(Some(params_path)).unwrap();
+///         let encrypted = keyring.encrypt("some data".as_bytes(), &[]).unwrap();
+///
+///         // new primary key is created, previous keys stay in the keyring
+///         let keyring = rotate_keyring(Some(params_path), Algorithm::Aes256Gcm).unwrap();
+///         assert_eq!(keyring.decrypt(encrypted.as_slice(), &[]).unwrap(), "some data".as_bytes());
+///
+///         // data is migrated to the primary key
+///         let reencrypted = keyring.reencrypt(encrypted.as_slice(), &[]).unwrap();
+///         assert_eq!(Keyring::key_id_of(reencrypted.as_slice()).unwrap(), keyring.primary_key_id());
+///     }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    primary_key_id: u32,
+    keys: BTreeMap<u32, EncryptionParameters>,
+}
+
+impl Keyring {
+    /// Creates keyring holding single key, which is primary
+    pub fn new(key_id: u32, params: EncryptionParameters) -> Result<Keyring, Error> {
+        let boxed_validate = validate_key(params.algorithm, params.key.as_slice());
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        let mut keys = BTreeMap::new();
+        keys.insert(key_id, params);
+        Ok(Keyring { primary_key_id: key_id, keys })
+    }
+
+    /// Returns key ID of the primary key, data is encrypted with it
+    pub fn primary_key_id(&self) -> u32 {
+        self.primary_key_id
+    }
+
+    /// Returns key IDs of all keys in ascending order
+    pub fn key_ids(&self) -> Vec<u32> {
+        self.keys.keys().copied().collect()
+    }
+
+    /// Adds key under the given key ID, key ID can not be reused
+    pub fn add_key(&mut self, key_id: u32, params: EncryptionParameters) -> Result<(), Error> {
+        if self.keys.contains_key(&key_id) {
+            let message = format!("keyring already holds key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+
+        let boxed_validate = validate_key(params.algorithm, params.key.as_slice());
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        self.keys.insert(key_id, params);
+        Ok(())
+    }
+
+    /// Makes the key with given key ID primary
+    pub fn set_primary(&mut self, key_id: u32) -> Result<(), Error> {
+        if !self.keys.contains_key(&key_id) {
+            let message = format!("keyring does not hold key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+
+        self.primary_key_id = key_id;
+        Ok(())
+    }
+
+    /// Removes key with the given key ID, data encrypted with it can not be decrypted anymore. Primary key can not be removed
+    pub fn remove_key(&mut self, key_id: u32) -> Result<(), Error> {
+        if key_id == self.primary_key_id {
+            let message = format!("key with ID {} is primary and can not be removed", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+
+        if self.keys.remove(&key_id).is_none() {
+            let message = format!("keyring does not hold key with ID {}", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+        Ok(())
+    }
+
+    /// Returns key ID the encrypted data is tagged with, for example to find data not yet migrated to the primary key
+    pub fn key_id_of(encrypted_data: &[u8]) -> Result<u32, Error> {
+        if encrypted_data.len() < KEYRING_HEADER_LENGTH || !encrypted_data.starts_with(KEYRING_MAGIC) {
+            return Err(Error::format("encrypted data is not tagged with key ID"))
+        }
+
+        let version = encrypted_data[KEYRING_MAGIC.len()];
+        if version != KEYRING_VERSION {
+            let message = format!("unsupported keyring format version: {}", version);
+            return Err(Error::format(message))
+        }
+
+        let key_id = [encrypted_data[5], encrypted_data[6], encrypted_data[7], encrypted_data[8]];
+        Ok(u32::from_be_bytes(key_id))
+    }
+
+    /// Encrypts given byte array with the primary key, output starts with the key ID
+    pub fn encrypt(&self, data_to_encrypt: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let params = &self.keys[&self.primary_key_id];
+
+        let mut encrypted_data = Vec::with_capacity(KEYRING_HEADER_LENGTH + params.algorithm.nonce_length() + data_to_encrypt.len() + params.algorithm.tag_length());
+        encrypted_data.extend_from_slice(KEYRING_MAGIC);
+        encrypted_data.push(KEYRING_VERSION);
+        encrypted_data.extend_from_slice(&self.primary_key_id.to_be_bytes());
+
+        // key ID is authenticated along the associated data
+        let keyring_associated_data = [encrypted_data.as_slice(), associated_data].concat();
+
+        let boxed_encrypted_data = encrypt(params.clone(), data_to_encrypt, keyring_associated_data.as_slice());
+        if boxed_encrypted_data.is_err() {
+            return Err(boxed_encrypted_data.err().unwrap());
+        }
+
+        encrypted_data.extend_from_slice(boxed_encrypted_data.unwrap().as_slice());
+        Ok(encrypted_data)
+    }
+
+    /// Decrypts given byte array with the key it is tagged with
+    pub fn decrypt(&self, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_key_id = Keyring::key_id_of(encrypted_data);
+        if boxed_key_id.is_err() {
+            return Err(boxed_key_id.err().unwrap());
+        }
+        let key_id = boxed_key_id.unwrap();
+
+        let boxed_params = self.keys.get(&key_id);
+        if boxed_params.is_none() {
+            let message = format!("data is encrypted with key ID {}, which is not in the keyring", key_id);
+            return Err(Error::key_mismatch(message))
+        }
+        let params = boxed_params.unwrap();
+
+        let (header, encrypted_data) = encrypted_data.split_at(KEYRING_HEADER_LENGTH);
+        let keyring_associated_data = [header, associated_data].concat();
+
+        let decryption_params = DecryptionParameters { algorithm: params.algorithm, key: params.key.clone() };
+        decrypt(decryption_params, encrypted_data, keyring_associated_data.as_slice())
+    }
+
+    /// Decrypts given byte array and encrypts it again with the primary key
+    pub fn reencrypt(&self, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_data = self.decrypt(encrypted_data, associated_data);
+        if boxed_data.is_err() {
+            return Err(boxed_data.err().unwrap());
+        }
+        let data = Secret::new(boxed_data.unwrap());
+
+        self.encrypt(data.as_slice(), associated_data)
+    }
+
+    /// Decrypts given byte array encrypted by [encrypt], which carries no key ID, and encrypts it again with the primary key
+    pub fn reencrypt_from(&self, params: DecryptionParameters, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_data = decrypt(params, encrypted_data, associated_data);
+        if boxed_data.is_err() {
+            return Err(boxed_data.err().unwrap());
+        }
+        let data = Secret::new(boxed_data.unwrap());
+
+        self.encrypt(data.as_slice(), associated_data)
+    }
+}
+
+/// Will read or create Keyring at the given location which is relative to the working directory
+///
+/// Key with ID 1 is the key created by [setup], so data encrypted before can be migrated with [Keyring::reencrypt_from],
+/// keys created by rotation are stored next to it with the key ID as a suffix. The newest key is primary
+pub fn setup_keyring(path_to_encryption_parameters: Option<&str>) -> Result<Keyring, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    setup_keyring_with_store(&boxed_store.unwrap(), Algorithm::default())
+}
 
-    let cipher = Aes128Gcm::new(key);
-    let boxed_decrypted_data = cipher.decrypt(nonce, payload);
-    if boxed_decrypted_data.is_err() {
-        let message = boxed_decrypted_data.err().unwrap().to_string();
+/// Will read or create Keyring in the given key store, first key is created for the given algorithm
+///
+/// Existing keys are loaded regardless of their algorithm
+pub fn setup_keyring_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<Keyring, Error> {
+    let boxed_key = get_or_create_key(store, keyring_key_name(1).as_str(), algorithm);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    get_keyring_with_store(store)
+}
+
+/// Returns Keyring stored at the given location which is relative to the working directory
+pub fn get_keyring(path_to_encryption_parameters: Option<&str>) -> Result<Keyring, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_keyring_with_store(&boxed_store.unwrap())
+}
+
+/// Returns Keyring stored in the given key store, the newest key is primary
+pub fn get_keyring_with_store(store: &dyn KeyStore) -> Result<Keyring, Error> {
+    let boxed_params = read_keyring_key(store, 1);
+    if boxed_params.is_err() {
+        return Err(boxed_params.err().unwrap());
+    }
+
+    let boxed_keyring = Keyring::new(1, boxed_params.unwrap());
+    if boxed_keyring.is_err() {
+        return Err(boxed_keyring.err().unwrap());
+    }
+    let mut keyring = boxed_keyring.unwrap();
+
+    let mut key_id: u32 = 2;
+    loop {
+        let boxed_exists = store.contains(keyring_key_name(key_id).as_str());
+        if boxed_exists.is_err() {
+            return Err(boxed_exists.err().unwrap());
+        }
+        if !boxed_exists.unwrap() {
+            break;
+        }
+
+        let boxed_params = read_keyring_key(store, key_id);
+        if boxed_params.is_err() {
+            return Err(boxed_params.err().unwrap());
+        }
+
+        let boxed_add = keyring.add_key(key_id, boxed_params.unwrap());
+        if boxed_add.is_err() {
+            return Err(boxed_add.err().unwrap());
+        }
+        keyring.primary_key_id = key_id;
+        key_id += 1;
+    }
+
+    Ok(keyring)
+}
+
+/// Creates new primary key for the given algorithm in the Keyring stored at the given location which is relative to the working directory
+pub fn rotate_keyring(path_to_encryption_parameters: Option<&str>, algorithm: Algorithm) -> Result<Keyring, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_encryption_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    rotate_keyring_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Creates new primary key for the given algorithm in the Keyring stored in the given key store
+///
+/// Concurrent rotations converge on the same new key
+pub fn rotate_keyring_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<Keyring, Error> {
+    let boxed_keyring = get_keyring_with_store(store);
+    if boxed_keyring.is_err() {
+        return Err(boxed_keyring.err().unwrap());
+    }
+    let keyring = boxed_keyring.unwrap();
+
+    let boxed_key_id = keyring.primary_key_id.checked_add(1);
+    if boxed_key_id.is_none() {
+        return Err(Error::key_mismatch("keyring has no key ID left for a new key"))
+    }
+    let key_id = boxed_key_id.unwrap();
+
+    let boxed_key = get_or_create_key(store, keyring_key_name(key_id).as_str(), algorithm);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+
+    get_keyring_with_store(store)
+}
+
+/// Encryptor keeps the cipher initialized with the key, so it can encrypt any number of messages
+///
+/// It is `Send` and `Sync`, single instance can be shared between threads, random nonce is generated for each message
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::key_store::MemoryKeyStore;
+///     use crypto_ext::symmetric::encryption::{setup_with_store, Algorithm, Decryptor, Encryptor};
+///
+///     #[test]
+///     fn encryptor() {
+///         let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new(), Algorithm::Aes256Gcm).unwrap();
+///         let encryptor = Encryptor::new(&encryption_params).unwrap();
+///         let decryptor = Decryptor::new(&decryption_params).unwrap();
+///
+///         for message in ["first message", "second message"] {
+///             let encrypted = encryptor.encrypt(message.as_bytes(), &[]).unwrap();
+///             assert_eq!(decryptor.decrypt(encrypted.as_slice(), &[]).unwrap(), message.as_bytes());
+///         }
+///     }
+/// ```
+pub struct Encryptor {
+    cipher: Cipher,
+}
+
+impl Encryptor {
+    /// Initializes cipher with the key of the given parameters
+    pub fn new(params: &EncryptionParameters) -> Result<Encryptor, Error> {
+        let boxed_cipher = Cipher::new(params.algorithm, params.key.as_slice());
+        if boxed_cipher.is_err() {
+            return Err(boxed_cipher.err().unwrap());
+        }
+        Ok(Encryptor { cipher: boxed_cipher.unwrap() })
+    }
+
+    /// Returns algorithm of the cipher
+    pub fn algorithm(&self) -> Algorithm {
+        self.cipher.algorithm()
+    }
+
+    /// Encrypts given byte array the same way as [encrypt]
+    pub fn encrypt(&self, data_to_encrypt: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let boxed_nonce = generate_bytes(self.algorithm().nonce_length());
+        if boxed_nonce.is_err() {
+            return Err(boxed_nonce.err().unwrap());
+        }
+        let nonce = boxed_nonce.unwrap();
+
+        let payload = Payload {
+            msg: data_to_encrypt,
+            aad: associated_data,
+        };
+
+        let boxed_cipher_text = self.cipher.encrypt(nonce.as_slice(), payload);
+        if boxed_cipher_text.is_err() {
+            return Err(boxed_cipher_text.err().unwrap());
+        }
+
+        let cipher_text = boxed_cipher_text.unwrap();
+
+        let mut encrypted_data = nonce;
+        encrypted_data.extend_from_slice(cipher_text.as_slice());
+
+        Ok(encrypted_data)
+    }
+}
+
+/// Decryptor keeps the cipher initialized with the key, so it can decrypt any number of messages
+///
+/// It is `Send` and `Sync`, single instance can be shared between threads
+pub struct Decryptor {
+    cipher: Cipher,
+}
+
+impl Decryptor {
+    /// Initializes cipher with the key of the given parameters
+    pub fn new(params: &DecryptionParameters) -> Result<Decryptor, Error> {
+        let boxed_cipher = Cipher::new(params.algorithm, params.key.as_slice());
+        if boxed_cipher.is_err() {
+            return Err(boxed_cipher.err().unwrap());
+        }
+        Ok(Decryptor { cipher: boxed_cipher.unwrap() })
+    }
+
+    /// Returns algorithm of the cipher
+    pub fn algorithm(&self) -> Algorithm {
+        self.cipher.algorithm()
+    }
+
+    /// Decrypts given byte array the same way as [decrypt]
+    pub fn decrypt(&self, encrypted_data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let algorithm = self.algorithm();
+        let nonce_length = algorithm.nonce_length();
+        if encrypted_data.len() < nonce_length + algorithm.tag_length() {
+            let message = format!("encrypted data is too short: {} bytes, expected at least {} bytes of nonce and tag", encrypted_data.len(), nonce_length + algorithm.tag_length());
+            return Err(Error::format(message))
+        }
+        let (nonce, cipher_text) = encrypted_data.split_at(nonce_length);
+
+        let payload = Payload {
+            msg: cipher_text,
+            aad: associated_data,
+        };
+
+        self.cipher.decrypt(nonce, payload)
+    }
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+fn get_or_create_key(store: &dyn KeyStore, name: &str, algorithm: Algorithm) -> Result<(Algorithm, Secret<Vec<u8>>), Error> {
+    let boxed_key = generate_bytes(algorithm.key_length());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let key = Secret::new(boxed_key.unwrap());
+
+    let key_file = encode_key_file(algorithm, key.as_slice());
+    let boxed_key_file = store.read_or_create(name, key_file.as_slice());
+    if boxed_key_file.is_err() {
+        let message = boxed_key_file.err().unwrap();
         return Err(message)
     }
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    decode_key_file(key_file.as_slice())
+}
+
+// key with ID 1 is the one created by setup, so existing key becomes the first key of the keyring
+fn keyring_key_name(key_id: u32) -> String {
+    if key_id == 1 {
+        return KEY_NAME.to_string();
+    }
+    format!("{}.{}", KEY_NAME, key_id)
+}
+
+fn read_keyring_key(store: &dyn KeyStore, key_id: u32) -> Result<EncryptionParameters, Error> {
+    let boxed_key_file = store.read(keyring_key_name(key_id).as_str());
+    if boxed_key_file.is_err() {
+        return Err(boxed_key_file.err().unwrap());
+    }
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    let boxed_key = decode_key_file(key_file.as_slice());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, key) = boxed_key.unwrap();
+
+    Ok(EncryptionParameters { algorithm, key })
+}
+
+fn encode_key_file(algorithm: Algorithm, key: &[u8]) -> Secret<Vec<u8>> {
+    crate::encode_key_file(KEY_FILE_MAGIC, algorithm.id(), key)
+}
+
+fn decode_key_file(key_file: &[u8]) -> Result<(Algorithm, Secret<Vec<u8>>), Error> {
+    if key_file.len() == LEGACY_KEY_LENGTH {
+        return Ok((Algorithm::Aes128Gcm, Secret::new(key_file.to_vec())));
+    }
+
+    let boxed_key = crate::decode_key_file(KEY_FILE_MAGIC, "symmetric", key_file, Algorithm::from_id);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, key) = boxed_key.unwrap();
+
+    let boxed_validate = validate_key(algorithm, key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    Ok((algorithm, key))
+}
+
+fn validate_key(algorithm: Algorithm, key: &[u8]) -> Result<(), Error> {
+    if key.len() != algorithm.key_length() {
+        let message = format!("invalid {:?} key length: {} bytes, expected {} bytes", algorithm, key.len(), algorithm.key_length());
+        return Err(Error::key_mismatch(message))
+    }
+    Ok(())
+}
+
+// ciphers wipe their expanded keys on drop, AES ones are boxed as their key schedules take most of the space
+pub(crate) enum Cipher {
+    Aes128Gcm(Box<Aes128Gcm>),
+    Aes256Gcm(Box<Aes256Gcm>),
+    ChaCha20Poly1305(ChaCha20Poly1305),
+    XChaCha20Poly1305(XChaCha20Poly1305),
+}
+
+impl Cipher {
+    pub(crate) fn new(algorithm: Algorithm, key: &[u8]) -> Result<Cipher, Error> {
+        let boxed_validate = validate_key(algorithm, key);
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        let cipher = match algorithm {
+            Algorithm::Aes128Gcm => Cipher::Aes128Gcm(Box::new(Aes128Gcm::new(GenericArray::from_slice(key)))),
+            Algorithm::Aes256Gcm => Cipher::Aes256Gcm(Box::new(Aes256Gcm::new(GenericArray::from_slice(key)))),
+            Algorithm::ChaCha20Poly1305 => Cipher::ChaCha20Poly1305(ChaCha20Poly1305::new(GenericArray::from_slice(key))),
+            Algorithm::XChaCha20Poly1305 => Cipher::XChaCha20Poly1305(XChaCha20Poly1305::new(GenericArray::from_slice(key))),
+        };
+        Ok(cipher)
+    }
+
+    pub(crate) fn algorithm(&self) -> Algorithm {
+        match self {
+            Cipher::Aes128Gcm(_) => Algorithm::Aes128Gcm,
+            Cipher::Aes256Gcm(_) => Algorithm::Aes256Gcm,
+            Cipher::ChaCha20Poly1305(_) => Algorithm::ChaCha20Poly1305,
+            Cipher::XChaCha20Poly1305(_) => Algorithm::XChaCha20Poly1305,
+        }
+    }
 
-    let decrypted_data = boxed_decrypted_data.unwrap();
+    pub(crate) fn encrypt(&self, nonce: &[u8], payload: Payload) -> Result<Vec<u8>, Error> {
+        let boxed_cipher_text = match self {
+            Cipher::Aes128Gcm(cipher) => cipher.encrypt(GenericArray::from_slice(nonce), payload),
+            Cipher::Aes256Gcm(cipher) => cipher.encrypt(GenericArray::from_slice(nonce), payload),
+            Cipher::ChaCha20Poly1305(cipher) => cipher.encrypt(GenericArray::from_slice(nonce), payload),
+            Cipher::XChaCha20Poly1305(cipher) => cipher.encrypt(GenericArray::from_slice(nonce), payload),
+        };
+        if boxed_cipher_text.is_err() {
+            return Err(Error::crypto_from("unable to encrypt data", boxed_cipher_text.err().unwrap()))
+        }
+        Ok(boxed_cipher_text.unwrap())
+    }
 
-    Ok(decrypted_data)
+    pub(crate) fn decrypt(&self, nonce: &[u8], payload: Payload) -> Result<Vec<u8>, Error> {
+        let boxed_decrypted_data = match self {
+            Cipher::Aes128Gcm(cipher) => cipher.decrypt(GenericArray::from_slice(nonce), payload),
+            Cipher::Aes256Gcm(cipher) => cipher.decrypt(GenericArray::from_slice(nonce), payload),
+            Cipher::ChaCha20Poly1305(cipher) => cipher.decrypt(GenericArray::from_slice(nonce), payload),
+            Cipher::XChaCha20Poly1305(cipher) => cipher.decrypt(GenericArray::from_slice(nonce), payload),
+        };
+        if boxed_decrypted_data.is_err() {
+            return Err(Error::authentication_from("unable to decrypt data, wrong key or tampered data", boxed_decrypted_data.err().unwrap()))
+        }
+        Ok(boxed_decrypted_data.unwrap())
+    }
 }
@@ -1,4 +1,11 @@
-use crate::symmetric::encryption::{decrypt, encrypt, get_decryption_params, get_encryption_params, setup};
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use crate::Error;
+use crate::passphrase::generate_bytes;
+use crate::key_store::MemoryKeyStore;
+use crate::symmetric::encryption::{Algorithm, decode_key_file, decrypt, DecryptionParameters, Decryptor, encode_key_file, encrypt, EncryptionParameters, Encryptor, get_decryption_params, get_encryption_params, get_keyring, get_keyring_with_store, Keyring, rotate_keyring, rotate_keyring_with_store, setup, setup_keyring, setup_keyring_with_store, setup_with_store};
 
 #[test]
 fn encryption() {
@@ -6,8 +13,7 @@ fn encryption() {
     let associated_data = "some unencrypted data that needs to be sent along the encrypted data and won't be changed during transmission by a hacker".as_bytes();
 
     // path needs to be accessible by user with write permission for initial setup
-    // ideally for each encryption you need to setup unique folder for AES key and nonce
-    // do not reuse same setup with Encryption and Decryption parameters for multiple encryptions
+    // same setup can be reused for multiple encryptions, nonce is generated for each message
     let params_path = "/test/encryption_parameters/";
 
 
@@ -28,8 +34,7 @@ fn decryption() {
     let associated_data = "some unencrypted data that needs to be sent along the encrypted data and won't be changed during transmission by a hacker".as_bytes();
 
     // path needs to be accessible by user with write permission for initial setup
-    // ideally for each encryption you need to setup unique folder for AES key and nonce
-    // do not reuse same setup with Encryption and Decryption parameters for multiple encryptions
+    // same setup can be reused for multiple encryptions, nonce is generated for each message
     let params_path = "/test/encryption_parameters/";
 
 
@@ -43,4 +48,342 @@ fn decryption() {
 
     assert_eq!(data, decrypted);
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn nonce_is_unique_per_message() {
+    let data = "some data to encrypt".as_bytes();
+    let associated_data = "some associated data".as_bytes();
+    let key = generate_bytes(16).unwrap();
+
+    let mut nonces = HashSet::new();
+    let mut encrypted_messages = vec![];
+    for _ in 0..1_000 {
+        let encryption_params = EncryptionParameters { algorithm: Algorithm::Aes128Gcm, key: key.to_vec().into() };
+        let encrypted_data = encrypt(encryption_params, data, associated_data).unwrap();
+        assert_eq!(encrypted_data.len(), 12 + data.len() + 16);
+        assert!(nonces.insert(encrypted_data[..12].to_vec()));
+        encrypted_messages.push(encrypted_data);
+    }
+
+    for encrypted_data in encrypted_messages {
+        let decryption_params = DecryptionParameters { algorithm: Algorithm::Aes128Gcm, key: key.to_vec().into() };
+        let decrypted = decrypt(decryption_params, encrypted_data.as_slice(), associated_data).unwrap();
+        assert_eq!(data, decrypted);
+    }
+}
+
+#[test]
+fn tampered_data_is_rejected() {
+    let data = "some data to encrypt".as_bytes();
+    let associated_data = "some associated data".as_bytes();
+    let key = generate_bytes(16).unwrap();
+
+    let encryption_params = EncryptionParameters { algorithm: Algorithm::Aes128Gcm, key: key.to_vec().into() };
+    let encrypted_data = encrypt(encryption_params, data, associated_data).unwrap();
+
+    for index in 0..encrypted_data.len() {
+        let mut tampered = encrypted_data.to_vec();
+        tampered[index] ^= 0x01;
+        let decryption_params = DecryptionParameters { algorithm: Algorithm::Aes128Gcm, key: key.to_vec().into() };
+        assert!(decrypt(decryption_params, tampered.as_slice(), associated_data).is_err());
+    }
+
+    let decryption_params = DecryptionParameters { algorithm: Algorithm::Aes128Gcm, key: key.to_vec().into() };
+    let error = decrypt(decryption_params, encrypted_data.as_slice(), "other associated data".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    let decryption_params = DecryptionParameters { algorithm: Algorithm::Aes128Gcm, key: key.to_vec().into() };
+    let error = decrypt(decryption_params, &encrypted_data[..11], associated_data).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn invalid_key_length_is_rejected() {
+    let encryption_params = EncryptionParameters { algorithm: Algorithm::Aes128Gcm, key: vec![0; 15].into() };
+    let error = encrypt(encryption_params, "data".as_bytes(), "".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let decryption_params = DecryptionParameters { algorithm: Algorithm::Aes128Gcm, key: vec![0; 17].into() };
+    let error = decrypt(decryption_params, vec![0; 64].as_slice(), "".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+}
+
+#[test]
+fn all_algorithms() {
+    let data = "some data to encrypt".as_bytes();
+    let associated_data = "some associated data".as_bytes();
+    let algorithms = [Algorithm::Aes128Gcm, Algorithm::Aes256Gcm, Algorithm::ChaCha20Poly1305, Algorithm::XChaCha20Poly1305];
+
+    for algorithm in algorithms {
+        let key = generate_bytes(algorithm.key_length()).unwrap();
+
+        let encryption_params = EncryptionParameters { algorithm, key: key.to_vec().into() };
+        let encrypted_data = encrypt(encryption_params, data, associated_data).unwrap();
+        assert_eq!(encrypted_data.len(), algorithm.nonce_length() + data.len() + algorithm.tag_length());
+
+        let decryption_params = DecryptionParameters { algorithm, key: key.to_vec().into() };
+        let decrypted = decrypt(decryption_params, encrypted_data.as_slice(), associated_data).unwrap();
+        assert_eq!(data, decrypted);
+
+        for other_algorithm in algorithms {
+            if other_algorithm == algorithm || other_algorithm.key_length() != algorithm.key_length() {
+                continue;
+            }
+            let decryption_params = DecryptionParameters { algorithm: other_algorithm, key: key.to_vec().into() };
+            assert!(decrypt(decryption_params, encrypted_data.as_slice(), associated_data).is_err());
+        }
+    }
+}
+
+#[test]
+fn key_file_format() {
+    let algorithms = [Algorithm::Aes128Gcm, Algorithm::Aes256Gcm, Algorithm::ChaCha20Poly1305, Algorithm::XChaCha20Poly1305];
+    for algorithm in algorithms {
+        let key = generate_bytes(algorithm.key_length()).unwrap();
+        let key_file = encode_key_file(algorithm, key.as_slice());
+        let (decoded_algorithm, decoded_key) = decode_key_file(key_file.as_slice()).unwrap();
+        assert_eq!(algorithm, decoded_algorithm);
+        assert_eq!(key, *decoded_key);
+    }
+
+    // key files written by earlier versions contain bare AES-128 key
+    let legacy_key = "0123456789abcdef".as_bytes();
+    let (algorithm, key) = decode_key_file(legacy_key).unwrap();
+    assert_eq!(Algorithm::Aes128Gcm, algorithm);
+    assert_eq!(legacy_key, key.as_slice());
+
+    let mut truncated = encode_key_file(Algorithm::Aes256Gcm, vec![0; 32].as_slice()).to_vec();
+    truncated.pop();
+    assert!(decode_key_file(truncated.as_slice()).is_err());
+
+    let mut unknown_algorithm = encode_key_file(Algorithm::Aes256Gcm, vec![0; 32].as_slice()).to_vec();
+    unknown_algorithm[5] = 0xFF;
+    let error = decode_key_file(unknown_algorithm.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+
+    let error = decode_key_file("not a key".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn missing_key_is_io_error() {
+    let error = get_encryption_params(Some("/test/missing_encryption_parameters/")).err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+}
+
+#[test]
+fn concurrent_setup_converges() {
+    let path_to_params = "/test/encryption_parameters_concurrent_symmetric/";
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| thread::spawn(move || setup(Some(path_to_params)).unwrap().0.key.to_vec()))
+        .collect();
+    let keys: Vec<Vec<u8>> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+    assert!(keys.iter().all(|key| *key == keys[0]));
+    assert_eq!(get_encryption_params(Some(path_to_params)).unwrap().key.to_vec(), keys[0]);
+
+    fs::remove_dir_all("test/encryption_parameters_concurrent_symmetric").unwrap();
+}
+
+#[test]
+fn setup_creates_directory() {
+    let path_to_params = "/test/encryption_parameters_created/nested/";
+    let _ = fs::remove_dir_all("test/encryption_parameters_created");
+
+    // nothing is created by reading
+    assert!(matches!(get_encryption_params(Some(path_to_params)).err().unwrap(), Error::Io { .. }));
+    assert!(!std::path::Path::new("test/encryption_parameters_created").exists());
+
+    let (encryption_params, _) = setup(Some(path_to_params)).unwrap();
+    assert_eq!(get_encryption_params(Some(path_to_params)).unwrap().key.to_vec(), encryption_params.key.to_vec());
+
+    // file in place of the directory
+    fs::write("test/encryption_parameters_created/file", b"").unwrap();
+    let error = setup(Some("/test/encryption_parameters_created/file/")).err().unwrap();
+    assert!(matches!(error, Error::NotADirectory { .. }));
+
+    fs::remove_dir_all("test/encryption_parameters_created").unwrap();
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn encryptor_and_decryptor_are_shared_between_threads() {
+    assert_send_sync::<Encryptor>();
+    assert_send_sync::<Decryptor>();
+
+    let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new(), Algorithm::XChaCha20Poly1305).unwrap();
+    let encryptor = Arc::new(Encryptor::new(&encryption_params).unwrap());
+    let decryptor = Arc::new(Decryptor::new(&decryption_params).unwrap());
+    assert_eq!(encryptor.algorithm(), Algorithm::XChaCha20Poly1305);
+
+    let handles: Vec<_> = (0..4u8)
+        .map(|index| {
+            let encryptor = Arc::clone(&encryptor);
+            let decryptor = Arc::clone(&decryptor);
+            thread::spawn(move || {
+                for length in 0..64 {
+                    let data = vec![index; length];
+                    let encrypted_data = encryptor.encrypt(data.as_slice(), &[index]).unwrap();
+                    assert_eq!(decryptor.decrypt(encrypted_data.as_slice(), &[index]).unwrap(), data);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // contexts and functions produce interchangeable output
+    let encrypted_data = encryptor.encrypt("some data".as_bytes(), &[]).unwrap();
+    assert_eq!(decrypt(decryption_params.clone(), encrypted_data.as_slice(), &[]).unwrap(), "some data".as_bytes());
+    let encrypted_data = encrypt(encryption_params, "some data".as_bytes(), &[]).unwrap();
+    assert_eq!(decryptor.decrypt(encrypted_data.as_slice(), &[]).unwrap(), "some data".as_bytes());
+
+    let error = decryptor.decrypt(&encrypted_data[..20], &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let error = Encryptor::new(&EncryptionParameters { algorithm: Algorithm::Aes256Gcm, key: vec![0; 16].into() }).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+}
+
+#[test]
+fn keyring_rotation() {
+    let store = MemoryKeyStore::new();
+    let keyring = setup_keyring_with_store(&store, Algorithm::Aes128Gcm).unwrap();
+    assert_eq!(keyring.key_ids(), vec![1]);
+    assert_eq!(keyring.primary_key_id(), 1);
+
+    let encrypted_data = keyring.encrypt("some data".as_bytes(), "associated data".as_bytes()).unwrap();
+    assert_eq!(Keyring::key_id_of(encrypted_data.as_slice()).unwrap(), 1);
+
+    // each rotation adds new primary key, keys may differ in algorithm
+    rotate_keyring_with_store(&store, Algorithm::ChaCha20Poly1305).unwrap();
+    let keyring = rotate_keyring_with_store(&store, Algorithm::XChaCha20Poly1305).unwrap();
+    assert_eq!(keyring.key_ids(), vec![1, 2, 3]);
+    assert_eq!(keyring.primary_key_id(), 3);
+    assert_eq!(keyring.decrypt(encrypted_data.as_slice(), "associated data".as_bytes()).unwrap(), "some data".as_bytes());
+
+    let reencrypted_data = keyring.reencrypt(encrypted_data.as_slice(), "associated data".as_bytes()).unwrap();
+    assert_eq!(Keyring::key_id_of(reencrypted_data.as_slice()).unwrap(), 3);
+    assert_eq!(keyring.decrypt(reencrypted_data.as_slice(), "associated data".as_bytes()).unwrap(), "some data".as_bytes());
+
+    // setup and get read the same keyring, rotation is not repeated
+    let loaded_keyring = setup_keyring_with_store(&store, Algorithm::Aes256Gcm).unwrap();
+    assert_eq!(loaded_keyring.key_ids(), vec![1, 2, 3]);
+    let loaded_keyring = get_keyring_with_store(&store).unwrap();
+    assert_eq!(loaded_keyring.primary_key_id(), 3);
+    assert_eq!(loaded_keyring.decrypt(reencrypted_data.as_slice(), "associated data".as_bytes()).unwrap(), "some data".as_bytes());
+
+    // key dropped from the keyring no longer decrypts data tagged with it
+    let mut keyring = keyring;
+    keyring.remove_key(1).unwrap();
+    let error = keyring.decrypt(encrypted_data.as_slice(), "associated data".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let error = get_keyring_with_store(&MemoryKeyStore::new()).err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+}
+
+#[test]
+fn keyring_tampering() {
+    let keyring = setup_keyring_with_store(&MemoryKeyStore::new(), Algorithm::Aes256Gcm).unwrap();
+    let mut keyring = keyring;
+    keyring.add_key(7, EncryptionParameters { algorithm: Algorithm::Aes256Gcm, key: vec![7; 32].into() }).unwrap();
+
+    let encrypted_data = keyring.encrypt("some data".as_bytes(), &[]).unwrap();
+
+    // key ID is authenticated, pointing data to another key fails
+    let mut tampered_data = encrypted_data.clone();
+    tampered_data[8] = 7;
+    let error = keyring.decrypt(tampered_data.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    tampered_data[8] = 9;
+    let error = keyring.decrypt(tampered_data.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let error = keyring.decrypt(encrypted_data.as_slice(), "associated data".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    let error = Keyring::key_id_of(&encrypted_data[..8]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let mut unsupported_version = encrypted_data.clone();
+    unsupported_version[4] = 2;
+    let error = keyring.decrypt(unsupported_version.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    // output of encrypt carries no key ID
+    let params = EncryptionParameters { algorithm: Algorithm::Aes256Gcm, key: vec![7; 32].into() };
+    let untagged_data = encrypt(params, "some data".as_bytes(), &[]).unwrap();
+    let error = keyring.decrypt(untagged_data.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn keyring_management() {
+    let params = EncryptionParameters { algorithm: Algorithm::Aes128Gcm, key: vec![1; 16].into() };
+    let mut keyring = Keyring::new(10, params.clone()).unwrap();
+
+    let error = keyring.add_key(10, params.clone()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = keyring.add_key(11, EncryptionParameters { algorithm: Algorithm::Aes256Gcm, key: vec![1; 16].into() }).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = Keyring::new(1, EncryptionParameters { algorithm: Algorithm::ChaCha20Poly1305, key: vec![1; 31].into() }).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    keyring.add_key(5, EncryptionParameters { algorithm: Algorithm::ChaCha20Poly1305, key: vec![5; 32].into() }).unwrap();
+    assert_eq!(keyring.key_ids(), vec![5, 10]);
+    assert_eq!(keyring.primary_key_id(), 10);
+
+    let error = keyring.set_primary(6).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    keyring.set_primary(5).unwrap();
+    let encrypted_data = keyring.encrypt("some data".as_bytes(), &[]).unwrap();
+    assert_eq!(Keyring::key_id_of(encrypted_data.as_slice()).unwrap(), 5);
+
+    let error = keyring.remove_key(5).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    let error = keyring.remove_key(6).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+    keyring.remove_key(10).unwrap();
+    assert_eq!(keyring.key_ids(), vec![5]);
+
+    // data encrypted before keyring was introduced is migrated with its parameters
+    let untagged_data = encrypt(params.clone(), "legacy data".as_bytes(), &[1]).unwrap();
+    let decryption_params = DecryptionParameters { algorithm: params.algorithm, key: params.key };
+    let reencrypted_data = keyring.reencrypt_from(decryption_params, untagged_data.as_slice(), &[1]).unwrap();
+    assert_eq!(Keyring::key_id_of(reencrypted_data.as_slice()).unwrap(), 5);
+    assert_eq!(keyring.decrypt(reencrypted_data.as_slice(), &[1]).unwrap(), "legacy data".as_bytes());
+}
+
+#[test]
+fn keyring_starts_with_existing_key() {
+    let params_path = "/test/keyring_existing_key/";
+    let _ = fs::remove_dir_all("test/keyring_existing_key");
+
+    let (encryption_params, decryption_params) = setup(Some(params_path)).unwrap();
+    let untagged_data = encrypt(encryption_params.clone(), "some data".as_bytes(), &[]).unwrap();
+
+    let keyring = setup_keyring(Some(params_path)).unwrap();
+    assert_eq!(keyring.key_ids(), vec![1]);
+    let tagged_data = keyring.encrypt("some data".as_bytes(), &[]).unwrap();
+    let decrypted_data = decrypt(decryption_params.clone(), &tagged_data[9..], &tagged_data[..9]).unwrap();
+    assert_eq!(decrypted_data, "some data".as_bytes());
+
+    let keyring = rotate_keyring(Some(params_path), Algorithm::XChaCha20Poly1305).unwrap();
+    assert_eq!(keyring.key_ids(), vec![1, 2]);
+    assert!(fs::metadata("test/keyring_existing_key/.aes_key.2").is_ok());
+    let reencrypted_data = keyring.reencrypt_from(decryption_params, untagged_data.as_slice(), &[]).unwrap();
+
+    // parameters of setup still refer to the first key
+    let keyring = get_keyring(Some(params_path)).unwrap();
+    assert_eq!(keyring.primary_key_id(), 2);
+    assert_eq!(keyring.decrypt(reencrypted_data.as_slice(), &[]).unwrap(), "some data".as_bytes());
+    assert_eq!(get_encryption_params(Some(params_path)).unwrap().key.as_slice(), encryption_params.key.as_slice());
+
+    fs::remove_dir_all("test/keyring_existing_key").unwrap();
+}
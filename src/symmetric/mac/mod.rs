@@ -0,0 +1,356 @@
+use std::io;
+use std::io::Write;
+use hmac::{Hmac, Mac as _};
+use sha2::{Sha256, Sha384, Sha512};
+use crate::Error;
+use crate::key_store::{FileSystemKeyStore, KeyStore};
+use crate::passphrase::generate_bytes;
+use crate::secret::Secret;
+
+#[cfg(test)]
+mod tests;
+
+// name of the key in the key store, file name for the path based functions
+const KEY_NAME: &str = ".hmac_key";
+// magic of the key file, format version, algorithm identifier and key follow it
+const KEY_FILE_MAGIC: &[u8] = b"CXMK";
+
+/// Message authentication algorithm used to authenticate data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// HMAC with SHA-256 as defined in RFC 2104
+    #[default]
+    HmacSha256,
+    /// HMAC with SHA-384 as defined in RFC 2104
+    HmacSha384,
+    /// HMAC with SHA-512 as defined in RFC 2104
+    HmacSha512,
+}
+
+impl Algorithm {
+    /// Returns length of the generated key in bytes, same as length of the tag
+    pub fn key_length(&self) -> usize {
+        self.tag_length()
+    }
+
+    /// Returns length of the authentication tag in bytes
+    pub fn tag_length(&self) -> usize {
+        match self {
+            Algorithm::HmacSha256 => 32,
+            Algorithm::HmacSha384 => 48,
+            Algorithm::HmacSha512 => 64,
+        }
+    }
+
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Algorithm::HmacSha256 => 1,
+            Algorithm::HmacSha384 => 2,
+            Algorithm::HmacSha512 => 3,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Algorithm, Error> {
+        match id {
+            1 => Ok(Algorithm::HmacSha256),
+            2 => Ok(Algorithm::HmacSha384),
+            3 => Ok(Algorithm::HmacSha512),
+            _ => Err(Error::unsupported_algorithm(format!("unsupported message authentication algorithm identifier: {}", id))),
+        }
+    }
+}
+
+/// MacParameters is basically the algorithm and key, same key computes and verifies tags, it is wiped from memory on drop
+#[derive(Debug, Clone)]
+pub struct MacParameters {
+    pub algorithm: Algorithm,
+    pub key: Secret<Vec<u8>>,
+}
+
+/// Will read or create MacParameters at the given location which is relative to the working directory
+///
+/// New key is created for the default algorithm, HMAC-SHA256. Existing key is loaded regardless of its algorithm
+pub fn setup(path_to_mac_parameters: Option<&str>) -> Result<MacParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_mac_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+
+    let boxed_key = get_or_create_key(&boxed_store.unwrap(), Algorithm::default());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, key) = boxed_key.unwrap();
+
+    Ok(MacParameters { algorithm, key })
+}
+
+/// Will read or create MacParameters for the given algorithm at the given location which is relative to the working directory
+///
+/// Returns an error if key stored at the location belongs to another algorithm
+pub fn setup_with_algorithm(path_to_mac_parameters: Option<&str>, algorithm: Algorithm) -> Result<MacParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_mac_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    setup_with_store(&boxed_store.unwrap(), algorithm)
+}
+
+/// Will read or create MacParameters for the given algorithm in the given key store
+///
+/// Returns an error if key stored in the key store belongs to another algorithm
+pub fn setup_with_store(store: &dyn KeyStore, algorithm: Algorithm) -> Result<MacParameters, Error> {
+    let boxed_key = get_or_create_key(store, algorithm);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (stored_algorithm, key) = boxed_key.unwrap();
+
+    if stored_algorithm != algorithm {
+        let message = format!("stored key is for {:?} algorithm, requested {:?}", stored_algorithm, algorithm);
+        return Err(Error::key_mismatch(message))
+    }
+
+    Ok(MacParameters { algorithm, key })
+}
+
+/// Returns MacParameters stored at the given location which is relative to the working directory
+pub fn get_mac_params(path_to_mac_parameters: Option<&str>) -> Result<MacParameters, Error> {
+    let boxed_store = FileSystemKeyStore::relative_to_working_directory(path_to_mac_parameters);
+    if boxed_store.is_err() {
+        return Err(boxed_store.err().unwrap());
+    }
+    get_mac_params_with_store(&boxed_store.unwrap())
+}
+
+/// Returns MacParameters stored in the given key store
+pub fn get_mac_params_with_store(store: &dyn KeyStore) -> Result<MacParameters, Error> {
+    let boxed_key_file = store.read(KEY_NAME);
+    if boxed_key_file.is_err() {
+        return Err(boxed_key_file.err().unwrap());
+    }
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    let boxed_key = decode_key_file(key_file.as_slice());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, key) = boxed_key.unwrap();
+
+    Ok(MacParameters { algorithm, key })
+}
+
+/// Computes authentication tag of the given byte array, tag is sent along the data
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::symmetric::mac::{mac, setup, verify_mac};
+///
+///     #[test]
+///     fn webhook_signature() {
+///         let payload = "{\"event\":\"payment.succeeded\"}".as_bytes();
+///
+///         // path needs to be accessible by user with write permission for initial setup
+///         // the key is shared by the sender and the receiver
+///         let params = setup(Some("/test/mac_parameters/")).unwrap();
+///
+///         let tag = mac(params.clone(), payload).unwrap();
+///
+///         assert!(verify_mac(params.clone(), payload, tag.as_slice()).is_ok());
+///         assert!(verify_mac(params, "{\"event\":\"payment.failed\"}".as_bytes(), tag.as_slice()).is_err());
+///     }
+/// ```
+pub fn mac(params: MacParameters, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let boxed_mac = Mac::new(&params);
+    if boxed_mac.is_err() {
+        return Err(boxed_mac.err().unwrap());
+    }
+    let mut mac = boxed_mac.unwrap();
+    mac.update(data);
+    Ok(mac.finalize())
+}
+
+/// Verifies authentication tag of the given byte array, tags are compared in constant time
+///
+/// Returns `Error::Authentication` if the tag does not match the data
+pub fn verify_mac(params: MacParameters, data: &[u8], tag: &[u8]) -> Result<(), Error> {
+    let boxed_mac = Mac::new(&params);
+    if boxed_mac.is_err() {
+        return Err(boxed_mac.err().unwrap());
+    }
+    let mut mac = boxed_mac.unwrap();
+    mac.update(data);
+    mac.verify(tag)
+}
+
+/// Mac computes authentication tag of the data given in parts, for data that is not available at once
+///
+/// It implements [Write], so [std::io::copy] can feed it from any reader. Key is processed once by [Mac::new],
+/// to authenticate many messages with the same key clone the initialized instance.
+///
+/// # Examples
+///
+/// ```
+///     use crypto_ext::key_store::MemoryKeyStore;
+///     use crypto_ext::symmetric::mac::{mac, setup_with_store, Algorithm, Mac};
+///
+///     #[test]
+///     fn incremental() {
+///         let params = setup_with_store(&MemoryKeyStore::new(), Algorithm::HmacSha512).unwrap();
+///         let initialized = Mac::new(&params).unwrap();
+///
+///         let mut request = initialized.clone();
+///         request.update("POST /orders\n".as_bytes());
+///         request.update("{\"amount\":42}".as_bytes());
+///         let tag = request.finalize();
+///
+///         assert_eq!(tag, mac(params, "POST /orders\n{\"amount\":42}".as_bytes()).unwrap());
+///     }
+/// ```
+#[derive(Clone)]
+pub struct Mac {
+    state: State,
+}
+
+impl Mac {
+    /// Initializes HMAC with the key of the given parameters
+    pub fn new(params: &MacParameters) -> Result<Mac, Error> {
+        let boxed_validate = validate_key(params.algorithm, params.key.as_slice());
+        if boxed_validate.is_err() {
+            return Err(boxed_validate.err().unwrap());
+        }
+
+        let key = params.key.as_slice();
+        let state = match params.algorithm {
+            Algorithm::HmacSha256 => {
+                let boxed_hmac = Hmac::<Sha256>::new_from_slice(key);
+                if boxed_hmac.is_err() {
+                    return Err(Error::key_mismatch_from("unable to initialize HMAC-SHA-256 with the given key", boxed_hmac.err().unwrap()));
+                }
+                State::HmacSha256(boxed_hmac.unwrap())
+            }
+            Algorithm::HmacSha384 => {
+                let boxed_hmac = Hmac::<Sha384>::new_from_slice(key);
+                if boxed_hmac.is_err() {
+                    return Err(Error::key_mismatch_from("unable to initialize HMAC-SHA-384 with the given key", boxed_hmac.err().unwrap()));
+                }
+                State::HmacSha384(boxed_hmac.unwrap())
+            }
+            Algorithm::HmacSha512 => {
+                let boxed_hmac = Hmac::<Sha512>::new_from_slice(key);
+                if boxed_hmac.is_err() {
+                    return Err(Error::key_mismatch_from("unable to initialize HMAC-SHA-512 with the given key", boxed_hmac.err().unwrap()));
+                }
+                State::HmacSha512(boxed_hmac.unwrap())
+            }
+        };
+        Ok(Mac { state })
+    }
+
+    /// Returns algorithm of the HMAC
+    pub fn algorithm(&self) -> Algorithm {
+        match self.state {
+            State::HmacSha256(_) => Algorithm::HmacSha256,
+            State::HmacSha384(_) => Algorithm::HmacSha384,
+            State::HmacSha512(_) => Algorithm::HmacSha512,
+        }
+    }
+
+    /// Processes next part of the data
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            State::HmacSha256(mac) => mac.update(data),
+            State::HmacSha384(mac) => mac.update(data),
+            State::HmacSha512(mac) => mac.update(data),
+        }
+    }
+
+    /// Returns authentication tag of all the data processed so far
+    pub fn finalize(self) -> Vec<u8> {
+        match self.state {
+            State::HmacSha256(mac) => mac.finalize().into_bytes().to_vec(),
+            State::HmacSha384(mac) => mac.finalize().into_bytes().to_vec(),
+            State::HmacSha512(mac) => mac.finalize().into_bytes().to_vec(),
+        }
+    }
+
+    /// Verifies authentication tag of all the data processed so far the same way as [verify_mac]
+    pub fn verify(self, tag: &[u8]) -> Result<(), Error> {
+        let boxed_verify = match self.state {
+            State::HmacSha256(mac) => mac.verify_slice(tag),
+            State::HmacSha384(mac) => mac.verify_slice(tag),
+            State::HmacSha512(mac) => mac.verify_slice(tag),
+        };
+        if boxed_verify.is_err() {
+            return Err(Error::authentication_from("authentication tag does not match the data", boxed_verify.err().unwrap()))
+        }
+        Ok(())
+    }
+}
+
+impl Write for Mac {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.update(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+fn get_or_create_key(store: &dyn KeyStore, algorithm: Algorithm) -> Result<(Algorithm, Secret<Vec<u8>>), Error> {
+    let boxed_key = generate_bytes(algorithm.key_length());
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let key = Secret::new(boxed_key.unwrap());
+
+    let key_file = encode_key_file(algorithm, key.as_slice());
+    let boxed_key_file = store.read_or_create(KEY_NAME, key_file.as_slice());
+    if boxed_key_file.is_err() {
+        return Err(boxed_key_file.err().unwrap());
+    }
+    let key_file = Secret::new(boxed_key_file.unwrap());
+
+    decode_key_file(key_file.as_slice())
+}
+
+fn encode_key_file(algorithm: Algorithm, key: &[u8]) -> Secret<Vec<u8>> {
+    crate::encode_key_file(KEY_FILE_MAGIC, algorithm.id(), key)
+}
+
+fn decode_key_file(key_file: &[u8]) -> Result<(Algorithm, Secret<Vec<u8>>), Error> {
+    let boxed_key = crate::decode_key_file(KEY_FILE_MAGIC, "message authentication", key_file, Algorithm::from_id);
+    if boxed_key.is_err() {
+        return Err(boxed_key.err().unwrap());
+    }
+    let (algorithm, key) = boxed_key.unwrap();
+
+    let boxed_validate = validate_key(algorithm, key.as_slice());
+    if boxed_validate.is_err() {
+        return Err(boxed_validate.err().unwrap());
+    }
+
+    Ok((algorithm, key))
+}
+
+// keys of any length are accepted as RFC 2104 allows, generated keys are as long as the tag
+fn validate_key(algorithm: Algorithm, key: &[u8]) -> Result<(), Error> {
+    if key.is_empty() {
+        let message = format!("{:?} key is empty", algorithm);
+        return Err(Error::key_mismatch(message))
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+enum State {
+    HmacSha256(Hmac<Sha256>),
+    HmacSha384(Hmac<Sha384>),
+    HmacSha512(Hmac<Sha512>),
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use std::io;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::thread;
+use crate::Error;
+use crate::key_store::MemoryKeyStore;
+use crate::passphrase::generate_bytes;
+use crate::symmetric::mac::{decode_key_file, encode_key_file, get_mac_params, mac, setup, setup_with_algorithm, setup_with_store, verify_mac, Algorithm, Mac, MacParameters};
+use crate::test_util::from_hex;
+
+const ALGORITHMS: [Algorithm; 3] = [Algorithm::HmacSha256, Algorithm::HmacSha384, Algorithm::HmacSha512];
+
+#[test]
+fn rfc4231_vectors() {
+    // test cases 1 to 7 of RFC 4231 section 4, key, data and tags for HMAC-SHA-256, HMAC-SHA-384 and HMAC-SHA-512
+    let large_key = vec![0xaa; 131];
+    let vectors: [(Vec<u8>, Vec<u8>, [&str; 3]); 7] = [
+        (vec![0x0b; 20], "Hi There".as_bytes().to_vec(), [
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            "afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59cfaea9ea9076ede7f4af152e8b2fa9cb6",
+            "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+        ]),
+        ("Jefe".as_bytes().to_vec(), "what do ya want for nothing?".as_bytes().to_vec(), [
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec3736322445e8e2240ca5e69e2c78b3239ecfab21649",
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+        ]),
+        (vec![0xaa; 20], vec![0xdd; 50], [
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            "88062608d3e6ad8a0aa2ace014c8a86f0aa635d947ac9febe83ef4e55966144b2a5ab39dc13814b94e3ab6e101a34f27",
+            "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb",
+        ]),
+        ((1..=25).collect(), vec![0xcd; 50], [
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            "3e8a69b7783c25851933ab6290af6ca77a9981480850009cc5577c6e1f573b4e6801dd23c4a7d679ccf8a386c674cffb",
+            "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3dba91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd",
+        ]),
+        // tags are truncated to 128 bits
+        (vec![0x0c; 20], "Test With Truncation".as_bytes().to_vec(), [
+            "a3b6167473100ee06e0c796c2955552b",
+            "3abf34c3503b2a23a46efc619baef897",
+            "415fad6271580a531d4179bc891d87a6",
+        ]),
+        (large_key.clone(), "Test Using Larger Than Block-Size Key - Hash Key First".as_bytes().to_vec(), [
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            "4ece084485813e9088d2c63a041bc5b44f9ef1012a2b588f3cd11f05033ac4c60c2ef6ab4030fe8296248df163f44952",
+            "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+        ]),
+        (large_key, "This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.".as_bytes().to_vec(), [
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            "6617178e941f020d351e2f254e8fd32c602420feb0b8fb9adccebb82461e99c5a678cc31e799176d3860e6110c46523e",
+            "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58",
+        ]),
+    ];
+
+    for (key, data, tags) in vectors {
+        for (algorithm, expected) in ALGORITHMS.iter().zip(tags) {
+            let params = MacParameters { algorithm: *algorithm, key: key.clone().into() };
+            let expected = from_hex(expected);
+
+            let tag = mac(params.clone(), data.as_slice()).unwrap();
+            assert_eq!(tag.len(), algorithm.tag_length());
+            assert_eq!(&tag[..expected.len()], expected.as_slice(), "{:?}", algorithm);
+
+            assert!(verify_mac(params, data.as_slice(), tag.as_slice()).is_ok());
+        }
+    }
+}
+
+#[test]
+fn setup_and_get_params() {
+    let path_to_params = "/test/mac_parameters_setup/";
+
+    let params = setup(Some(path_to_params)).unwrap();
+    assert_eq!(params.algorithm, Algorithm::HmacSha256);
+    assert_eq!(params.key.len(), 32);
+
+    let stored_params = get_mac_params(Some(path_to_params)).unwrap();
+    assert_eq!(stored_params.algorithm, params.algorithm);
+    assert_eq!(*stored_params.key, *params.key);
+
+    // stored key is reused
+    let tag = mac(params, "some data".as_bytes()).unwrap();
+    assert!(verify_mac(setup(Some(path_to_params)).unwrap(), "some data".as_bytes(), tag.as_slice()).is_ok());
+
+    let error = setup_with_algorithm(Some(path_to_params), Algorithm::HmacSha512).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    fs::remove_dir_all("test/mac_parameters_setup").unwrap();
+
+    for algorithm in ALGORITHMS {
+        let store = MemoryKeyStore::new();
+        let params = setup_with_store(&store, algorithm).unwrap();
+        assert_eq!(params.algorithm, algorithm);
+        assert_eq!(params.key.len(), algorithm.key_length());
+        assert_eq!(*setup_with_store(&store, algorithm).unwrap().key, *params.key);
+    }
+}
+
+#[test]
+fn missing_key_is_io_error() {
+    let error = get_mac_params(Some("/test/missing_mac_parameters/")).err().unwrap();
+    assert!(matches!(error, Error::Io { .. }));
+}
+
+#[test]
+fn key_file_format() {
+    for algorithm in ALGORITHMS {
+        let key = generate_bytes(algorithm.key_length()).unwrap();
+        let key_file = encode_key_file(algorithm, key.as_slice());
+        assert!(key_file.starts_with("CXMK".as_bytes()));
+
+        let (decoded_algorithm, decoded_key) = decode_key_file(key_file.as_slice()).unwrap();
+        assert_eq!(algorithm, decoded_algorithm);
+        assert_eq!(key, *decoded_key);
+    }
+
+    let empty_key = encode_key_file(Algorithm::HmacSha256, &[]);
+    let error = decode_key_file(empty_key.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let mut unknown_algorithm = encode_key_file(Algorithm::HmacSha256, vec![0; 32].as_slice()).to_vec();
+    unknown_algorithm[5] = 0xFF;
+    let error = decode_key_file(unknown_algorithm.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedAlgorithm { .. }));
+
+    let mut unknown_version = encode_key_file(Algorithm::HmacSha256, vec![0; 32].as_slice()).to_vec();
+    unknown_version[4] = 2;
+    let error = decode_key_file(unknown_version.as_slice()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let error = decode_key_file("not a key".as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+}
+
+#[test]
+fn tampered_data_is_rejected() {
+    let data = "some data to authenticate".as_bytes();
+
+    for algorithm in ALGORITHMS {
+        let params = setup_with_store(&MemoryKeyStore::new(), algorithm).unwrap();
+        let tag = mac(params.clone(), data).unwrap();
+
+        let error = verify_mac(params.clone(), "some data to authenticatE".as_bytes(), tag.as_slice()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        let mut tampered_tag = tag.clone();
+        tampered_tag[0] ^= 1;
+        let error = verify_mac(params.clone(), data, tampered_tag.as_slice()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        // truncated tags are not accepted
+        for length in [0, 16, tag.len() - 1] {
+            let error = verify_mac(params.clone(), data, &tag[..length]).err().unwrap();
+            assert!(matches!(error, Error::Authentication { .. }));
+        }
+        let error = verify_mac(params.clone(), data, [tag.as_slice(), &[0]].concat().as_slice()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+
+        let other_params = setup_with_store(&MemoryKeyStore::new(), algorithm).unwrap();
+        let error = verify_mac(other_params, data, tag.as_slice()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+    }
+
+    let empty_key = MacParameters { algorithm: Algorithm::HmacSha256, key: vec![].into() };
+    let error = mac(empty_key, data).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+}
+
+#[test]
+fn incremental_updates() {
+    let data = generate_bytes(10_000).unwrap();
+
+    for algorithm in ALGORITHMS {
+        let params = setup_with_store(&MemoryKeyStore::new(), algorithm).unwrap();
+        let expected = mac(params.clone(), data.as_slice()).unwrap();
+
+        let initialized = Mac::new(&params).unwrap();
+        assert_eq!(initialized.algorithm(), algorithm);
+
+        for part_length in [1, 64, 127, 128, 129, 10_000] {
+            let mut mac = initialized.clone();
+            for part in data.chunks(part_length) {
+                mac.update(part);
+            }
+            assert_eq!(mac.finalize(), expected, "{:?} in parts of {} bytes", algorithm, part_length);
+        }
+
+        let mut mac = initialized.clone();
+        io::copy(&mut Cursor::new(&data), &mut mac).unwrap();
+        assert!(mac.verify(expected.as_slice()).is_ok());
+
+        let mut mac = initialized.clone();
+        mac.update(&data[1..]);
+        let error = mac.verify(expected.as_slice()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+    }
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn mac_is_shared_between_threads() {
+    assert_send_sync::<Mac>();
+    assert_send_sync::<MacParameters>();
+
+    let params = setup_with_store(&MemoryKeyStore::new(), Algorithm::HmacSha384).unwrap();
+    let initialized = Arc::new(Mac::new(&params).unwrap());
+
+    let handles: Vec<_> = (0..4u8)
+        .map(|index| {
+            let initialized = Arc::clone(&initialized);
+            let params = params.clone();
+            thread::spawn(move || {
+                for length in 0..64 {
+                    let data = vec![index; length];
+                    let mut mac = (*initialized).clone();
+                    mac.update(data.as_slice());
+                    assert!(verify_mac(params.clone(), data.as_slice(), mac.finalize().as_slice()).is_ok());
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
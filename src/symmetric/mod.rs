@@ -1 +1,3 @@
-pub mod encryption;
\ No newline at end of file
+pub mod encryption;
+pub mod stream;
+pub mod mac;
@@ -0,0 +1,336 @@
+use std::io::{ErrorKind, Read, Write};
+use aes_gcm::aead::Payload;
+use crate::Error;
+use crate::passphrase::generate_bytes;
+use crate::symmetric::encryption::{Algorithm, Cipher, DecryptionParameters, EncryptionParameters};
+
+#[cfg(test)]
+mod tests;
+
+/// Length of the plaintext chunk used by [encrypt_stream], 64 KiB
+pub const DEFAULT_CHUNK_LENGTH: usize = 64 * 1024;
+/// Maximum length of the plaintext chunk, bounds memory used to decrypt a stream, 16 MiB
+pub const MAX_CHUNK_LENGTH: usize = 16 * 1024 * 1024;
+
+// stream layout: magic, format version, algorithm identifier, chunk length, nonce prefix, encrypted chunks
+// header is authenticated as associated data of every chunk
+const STREAM_MAGIC: &[u8] = b"CXSS";
+const STREAM_VERSION: u8 = 1;
+const STREAM_HEADER_LENGTH: usize = 10;
+// counter and last chunk flag take 5 bytes of the nonce
+const NONCE_OVERHEAD: usize = 5;
+
+/// Encrypts everything read from the reader in 64 KiB chunks and writes encrypted stream to the writer,
+/// returns number of bytes read
+///
+/// Memory use does not depend on the length of the data. Every chunk is authenticated, so
+/// [decrypt_stream] detects modified, reordered, duplicated and removed chunks as well as truncated stream.
+///
+/// # Examples
+///
+/// ```
+///     use std::io::Cursor;
+///     use crypto_ext::key_store::MemoryKeyStore;
+///     use crypto_ext::symmetric::encryption::{setup_with_store, Algorithm};
+///     use crypto_ext::symmetric::stream::{decrypt_stream, encrypt_stream};
+///
+///     #[test]
+///     fn stream_encryption() {
+///         let data = vec![7; 1024 * 1024];
+///         let associated_data = "backup of 2024-01-01".as_bytes();
+///         let (encryption_params, decryption_params) = setup_with_store(&MemoryKeyStore::new(), Algorithm::Aes256Gcm).unwrap();
+///
+///         // any Read and Write implementation such as File is accepted
+///         let mut encrypted = vec![];
+///         encrypt_stream(encryption_params, Cursor::new(&data), &mut encrypted, associated_data).unwrap();
+///
+///         let mut decrypted = vec![];
+///         decrypt_stream(decryption_params, Cursor::new(&encrypted), &mut decrypted, associated_data).unwrap();
+///
+///         assert_eq!(data, decrypted);
+///     }
+/// ```
+pub fn encrypt_stream<R: Read, W: Write>(params: EncryptionParameters, reader: R, writer: W, associated_data: &[u8]) -> Result<u64, Error> {
+    encrypt_stream_with_chunk_length(params, DEFAULT_CHUNK_LENGTH, reader, writer, associated_data)
+}
+
+/// Encrypts everything read from the reader in chunks of the given length, up to [MAX_CHUNK_LENGTH],
+/// and writes encrypted stream to the writer, returns number of bytes read
+///
+/// Chunk length is recorded in the stream, [decrypt_stream] does not need it.
+pub fn encrypt_stream_with_chunk_length<R: Read, W: Write>(params: EncryptionParameters, chunk_length: usize, reader: R, writer: W, associated_data: &[u8]) -> Result<u64, Error> {
+    let algorithm = params.algorithm;
+    let boxed_cipher = Cipher::new(algorithm, params.key.as_slice());
+    if boxed_cipher.is_err() {
+        return Err(boxed_cipher.err().unwrap());
+    }
+    let cipher = boxed_cipher.unwrap();
+
+    if chunk_length == 0 || chunk_length > MAX_CHUNK_LENGTH {
+        let message = format!("invalid chunk length: {} bytes, expected from 1 to {} bytes", chunk_length, MAX_CHUNK_LENGTH);
+        return Err(Error::format(message))
+    }
+
+    let boxed_nonce_prefix = generate_bytes(algorithm.nonce_length() - NONCE_OVERHEAD);
+    if boxed_nonce_prefix.is_err() {
+        return Err(boxed_nonce_prefix.err().unwrap());
+    }
+    let nonce_prefix = boxed_nonce_prefix.unwrap();
+
+    let mut header = Vec::with_capacity(STREAM_HEADER_LENGTH + nonce_prefix.len());
+    header.extend_from_slice(STREAM_MAGIC);
+    header.push(STREAM_VERSION);
+    header.push(algorithm.id());
+    header.extend_from_slice(&(chunk_length as u32).to_be_bytes());
+    header.extend_from_slice(nonce_prefix.as_slice());
+
+    let stream = Stream::new(cipher, header, associated_data, chunk_length);
+    encrypt_chunks(stream, reader, writer)
+}
+
+/// Decrypts stream created by [encrypt_stream] read from the reader and writes decrypted data to the writer,
+/// returns number of bytes written
+///
+/// Each chunk is written only after it is authenticated, still if an error is returned data written so far
+/// is incomplete and has to be discarded, for example stream may be truncated or its chunks reordered.
+pub fn decrypt_stream<R: Read, W: Write>(params: DecryptionParameters, mut reader: R, writer: W, associated_data: &[u8]) -> Result<u64, Error> {
+    let algorithm = params.algorithm;
+    let boxed_cipher = Cipher::new(algorithm, params.key.as_slice());
+    if boxed_cipher.is_err() {
+        return Err(boxed_cipher.err().unwrap());
+    }
+    let cipher = boxed_cipher.unwrap();
+
+    let mut header = vec![0; STREAM_HEADER_LENGTH];
+    let boxed_read = read_chunk(&mut reader, &mut header, STREAM_HEADER_LENGTH);
+    if boxed_read.is_err() {
+        return Err(Error::io("unable to read encrypted stream header", boxed_read.err().unwrap()))
+    }
+    if header.len() < STREAM_HEADER_LENGTH || !header.starts_with(STREAM_MAGIC) {
+        return Err(Error::format("unrecognized encrypted stream format"))
+    }
+
+    let version = header[STREAM_MAGIC.len()];
+    if version != STREAM_VERSION {
+        let message = format!("unsupported encrypted stream version: {}", version);
+        return Err(Error::format(message))
+    }
+
+    let boxed_algorithm = Algorithm::from_id(header[STREAM_MAGIC.len() + 1]);
+    if boxed_algorithm.is_err() {
+        return Err(boxed_algorithm.err().unwrap());
+    }
+    let stream_algorithm = boxed_algorithm.unwrap();
+    if stream_algorithm != algorithm {
+        let message = format!("stream is encrypted with {:?}, decryption parameters are for {:?}", stream_algorithm, algorithm);
+        return Err(Error::key_mismatch(message))
+    }
+
+    let chunk_length = u32::from_be_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    if chunk_length == 0 || chunk_length > MAX_CHUNK_LENGTH {
+        let message = format!("invalid chunk length: {} bytes, expected from 1 to {} bytes", chunk_length, MAX_CHUNK_LENGTH);
+        return Err(Error::format(message))
+    }
+
+    let nonce_prefix_length = algorithm.nonce_length() - NONCE_OVERHEAD;
+    let mut nonce_prefix = vec![0; nonce_prefix_length];
+    let boxed_read = read_chunk(&mut reader, &mut nonce_prefix, nonce_prefix_length);
+    if boxed_read.is_err() {
+        return Err(Error::io("unable to read encrypted stream header", boxed_read.err().unwrap()))
+    }
+    if nonce_prefix.len() < nonce_prefix_length {
+        return Err(Error::format("encrypted stream is too short, header is incomplete"))
+    }
+    header.extend_from_slice(nonce_prefix.as_slice());
+
+    let stream = Stream::new(cipher, header, associated_data, chunk_length);
+    decrypt_chunks(stream, reader, writer)
+}
+
+
+// below are functions not exposed as an api, used for inner implementation
+
+// STREAM BE32 construction: chunk nonce is the prefix from the header, big endian counter of the chunk and last chunk flag
+struct Stream {
+    cipher: Cipher,
+    header: Vec<u8>,
+    associated_data: Vec<u8>,
+    chunk_length: usize,
+    position: u32,
+}
+
+impl Stream {
+    fn new(cipher: Cipher, header: Vec<u8>, associated_data: &[u8], chunk_length: usize) -> Stream {
+        let associated_data = [header.as_slice(), associated_data].concat();
+        Stream { cipher, header, associated_data, chunk_length, position: 0 }
+    }
+
+    fn encrypted_chunk_length(&self) -> usize {
+        self.chunk_length + self.cipher.algorithm().tag_length()
+    }
+
+    fn nonce(&self, is_last: bool) -> Vec<u8> {
+        let mut nonce = self.header[STREAM_HEADER_LENGTH..].to_vec();
+        nonce.extend_from_slice(&self.position.to_be_bytes());
+        nonce.push(is_last as u8);
+        nonce
+    }
+
+    // last chunk may take the final counter value, the others may not as the counter would wrap around
+    fn advance(&mut self, is_last: bool) -> Result<(), Error> {
+        if is_last {
+            return Ok(())
+        }
+        let boxed_position = self.position.checked_add(1);
+        if boxed_position.is_none() {
+            let message = format!("stream is too long, it exceeds {} chunks of {} bytes", u32::MAX, self.chunk_length);
+            return Err(Error::format(message))
+        }
+        self.position = boxed_position.unwrap();
+        Ok(())
+    }
+
+    fn encrypt_chunk(&mut self, chunk: &[u8], is_last: bool) -> Result<Vec<u8>, Error> {
+        let payload = Payload { msg: chunk, aad: self.associated_data.as_slice() };
+        let boxed_encrypted_chunk = self.cipher.encrypt(self.nonce(is_last).as_slice(), payload);
+        if boxed_encrypted_chunk.is_err() {
+            return Err(boxed_encrypted_chunk.err().unwrap());
+        }
+
+        let boxed_advance = self.advance(is_last);
+        if boxed_advance.is_err() {
+            return Err(boxed_advance.err().unwrap());
+        }
+        Ok(boxed_encrypted_chunk.unwrap())
+    }
+
+    fn decrypt_chunk(&mut self, encrypted_chunk: &[u8], is_last: bool) -> Result<Vec<u8>, Error> {
+        let payload = Payload { msg: encrypted_chunk, aad: self.associated_data.as_slice() };
+        let boxed_chunk = self.cipher.decrypt(self.nonce(is_last).as_slice(), payload);
+        if boxed_chunk.is_err() {
+            // removing trailing chunks leaves the stream without the chunk encrypted as the last one
+            let message = if is_last {
+                "unable to decrypt last chunk, wrong key, tampered or truncated stream"
+            } else {
+                "unable to decrypt chunk, wrong key, tampered or reordered stream"
+            };
+            return Err(Error::authentication_from(message, boxed_chunk.err().unwrap()))
+        }
+
+        let boxed_advance = self.advance(is_last);
+        if boxed_advance.is_err() {
+            return Err(boxed_advance.err().unwrap());
+        }
+        Ok(boxed_chunk.unwrap())
+    }
+}
+
+fn encrypt_chunks<R: Read, W: Write>(mut stream: Stream, mut reader: R, mut writer: W) -> Result<u64, Error> {
+    let boxed_write = writer.write_all(stream.header.as_slice());
+    if boxed_write.is_err() {
+        return Err(Error::io("unable to write encrypted stream header", boxed_write.err().unwrap()))
+    }
+
+    let mut length: u64 = 0;
+    let mut chunk = Vec::with_capacity(stream.chunk_length);
+    let mut next_chunk = Vec::with_capacity(stream.chunk_length);
+    let boxed_read = read_chunk(&mut reader, &mut chunk, stream.chunk_length);
+    if boxed_read.is_err() {
+        return Err(Error::io("unable to read data to encrypt", boxed_read.err().unwrap()))
+    }
+
+    loop {
+        // chunk is the last one if the data ends within it or right after it
+        let mut is_last = chunk.len() < stream.chunk_length;
+        if !is_last {
+            let boxed_read = read_chunk(&mut reader, &mut next_chunk, stream.chunk_length);
+            if boxed_read.is_err() {
+                return Err(Error::io("unable to read data to encrypt", boxed_read.err().unwrap()))
+            }
+            is_last = next_chunk.is_empty();
+        }
+
+        let boxed_encrypted_chunk = stream.encrypt_chunk(chunk.as_slice(), is_last);
+        if boxed_encrypted_chunk.is_err() {
+            return Err(boxed_encrypted_chunk.err().unwrap());
+        }
+        let boxed_write = writer.write_all(boxed_encrypted_chunk.unwrap().as_slice());
+        if boxed_write.is_err() {
+            return Err(Error::io("unable to write encrypted chunk", boxed_write.err().unwrap()))
+        }
+        length += chunk.len() as u64;
+
+        if is_last {
+            break;
+        }
+        std::mem::swap(&mut chunk, &mut next_chunk);
+    }
+
+    let boxed_flush = writer.flush();
+    if boxed_flush.is_err() {
+        return Err(Error::io("unable to flush encrypted stream", boxed_flush.err().unwrap()))
+    }
+    Ok(length)
+}
+
+fn decrypt_chunks<R: Read, W: Write>(mut stream: Stream, mut reader: R, mut writer: W) -> Result<u64, Error> {
+    let encrypted_chunk_length = stream.encrypted_chunk_length();
+
+    let mut length: u64 = 0;
+    let mut encrypted_chunk = Vec::with_capacity(encrypted_chunk_length);
+    let mut next_encrypted_chunk = Vec::with_capacity(encrypted_chunk_length);
+    let boxed_read = read_chunk(&mut reader, &mut encrypted_chunk, encrypted_chunk_length);
+    if boxed_read.is_err() {
+        return Err(Error::io("unable to read encrypted chunk", boxed_read.err().unwrap()))
+    }
+
+    loop {
+        let mut is_last = encrypted_chunk.len() < encrypted_chunk_length;
+        if !is_last {
+            let boxed_read = read_chunk(&mut reader, &mut next_encrypted_chunk, encrypted_chunk_length);
+            if boxed_read.is_err() {
+                return Err(Error::io("unable to read encrypted chunk", boxed_read.err().unwrap()))
+            }
+            is_last = next_encrypted_chunk.is_empty();
+        }
+
+        let boxed_chunk = stream.decrypt_chunk(encrypted_chunk.as_slice(), is_last);
+        if boxed_chunk.is_err() {
+            return Err(boxed_chunk.err().unwrap());
+        }
+        let chunk = boxed_chunk.unwrap();
+        let boxed_write = writer.write_all(chunk.as_slice());
+        if boxed_write.is_err() {
+            return Err(Error::io("unable to write decrypted chunk", boxed_write.err().unwrap()))
+        }
+        length += chunk.len() as u64;
+
+        if is_last {
+            break;
+        }
+        std::mem::swap(&mut encrypted_chunk, &mut next_encrypted_chunk);
+    }
+
+    let boxed_flush = writer.flush();
+    if boxed_flush.is_err() {
+        return Err(Error::io("unable to flush decrypted stream", boxed_flush.err().unwrap()))
+    }
+    Ok(length)
+}
+
+// reads until the buffer holds the given number of bytes or the reader is exhausted
+fn read_chunk<R: Read>(reader: &mut R, buffer: &mut Vec<u8>, length: usize) -> std::io::Result<()> {
+    buffer.clear();
+    buffer.resize(length, 0);
+    let mut filled = 0;
+    while filled < length {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    buffer.truncate(filled);
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read};
+use crate::Error;
+use crate::key_store::MemoryKeyStore;
+use crate::passphrase::generate_bytes;
+use crate::symmetric::encryption::{setup, setup_with_store, Algorithm, DecryptionParameters, EncryptionParameters};
+use crate::symmetric::stream::{decrypt_stream, encrypt_stream, encrypt_stream_with_chunk_length, DEFAULT_CHUNK_LENGTH, MAX_CHUNK_LENGTH};
+
+const TAG_LENGTH: usize = 16;
+
+fn params(algorithm: Algorithm) -> (EncryptionParameters, DecryptionParameters) {
+    setup_with_store(&MemoryKeyStore::new(), algorithm).unwrap()
+}
+
+fn encrypt_with_chunk_length(params: &EncryptionParameters, chunk_length: usize, data: &[u8], associated_data: &[u8]) -> Vec<u8> {
+    let mut encrypted = vec![];
+    let length = encrypt_stream_with_chunk_length(params.clone(), chunk_length, Cursor::new(data), &mut encrypted, associated_data).unwrap();
+    assert_eq!(length, data.len() as u64);
+    encrypted
+}
+
+fn decrypt(params: &DecryptionParameters, encrypted: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decrypted = vec![];
+    let boxed_length = decrypt_stream(params.clone(), Cursor::new(encrypted), &mut decrypted, associated_data);
+    if boxed_length.is_err() {
+        return Err(boxed_length.err().unwrap());
+    }
+    assert_eq!(boxed_length.unwrap(), decrypted.len() as u64);
+    Ok(decrypted)
+}
+
+// reader returning at most 1000 bytes per call, as sockets and pipes do
+struct ShortReader<R: Read>(R);
+
+impl<R: Read> Read for ShortReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let length = buffer.len().min(1000);
+        self.0.read(&mut buffer[..length])
+    }
+}
+
+#[test]
+fn multi_megabyte_round_trip() {
+    let data = generate_bytes(5 * 1024 * 1024 + 123).unwrap();
+    let associated_data = "backup of 2024-01-01".as_bytes();
+
+    for algorithm in [Algorithm::Aes128Gcm, Algorithm::Aes256Gcm, Algorithm::ChaCha20Poly1305, Algorithm::XChaCha20Poly1305] {
+        let (encryption_params, decryption_params) = params(algorithm);
+
+        let mut encrypted = vec![];
+        let length = encrypt_stream(encryption_params, ShortReader(Cursor::new(&data)), &mut encrypted, associated_data).unwrap();
+        assert_eq!(length, data.len() as u64);
+
+        let chunks = data.len() / DEFAULT_CHUNK_LENGTH + 1;
+        let header_length = 10 + algorithm.nonce_length() - 5;
+        assert_eq!(encrypted.len(), header_length + data.len() + chunks * TAG_LENGTH);
+
+        let mut decrypted = vec![];
+        let length = decrypt_stream(decryption_params.clone(), ShortReader(Cursor::new(&encrypted)), &mut decrypted, associated_data).unwrap();
+        assert_eq!(length, data.len() as u64);
+        assert!(decrypted == data);
+
+        let error = decrypt(&decryption_params, encrypted.as_slice(), "other associated data".as_bytes()).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+    }
+}
+
+#[test]
+fn files_round_trip() {
+    let directory = "test/stream_files";
+    fs::create_dir_all(directory).unwrap();
+    let data = generate_bytes(3 * 1024 * 1024).unwrap();
+    fs::write([directory, "data"].join("/"), data.as_slice()).unwrap();
+
+    let (encryption_params, decryption_params) = setup(Some("/test/stream_files/")).unwrap();
+
+    let reader = BufReader::new(File::open([directory, "data"].join("/")).unwrap());
+    let writer = BufWriter::new(File::create([directory, "data.encrypted"].join("/")).unwrap());
+    encrypt_stream(encryption_params, reader, writer, &[]).unwrap();
+
+    let reader = BufReader::new(File::open([directory, "data.encrypted"].join("/")).unwrap());
+    let writer = BufWriter::new(File::create([directory, "data.decrypted"].join("/")).unwrap());
+    decrypt_stream(decryption_params, reader, writer, &[]).unwrap();
+
+    assert!(fs::read([directory, "data.decrypted"].join("/")).unwrap() == data);
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn chunk_boundaries() {
+    let (encryption_params, decryption_params) = params(Algorithm::Aes256Gcm);
+    let chunk_length = 100;
+
+    // empty data, data shorter than, equal to and multiple of the chunk length
+    for length in [0, 1, 99, 100, 101, 200, 250] {
+        let data = vec![7; length];
+        let encrypted = encrypt_with_chunk_length(&encryption_params, chunk_length, data.as_slice(), &[]);
+        let chunks = if length == 0 { 1 } else { (length + chunk_length - 1) / chunk_length };
+        assert_eq!(encrypted.len(), 17 + length + chunks * TAG_LENGTH);
+        assert_eq!(decrypt(&decryption_params, encrypted.as_slice(), &[]).unwrap(), data);
+    }
+
+    for chunk_length in [0, MAX_CHUNK_LENGTH + 1] {
+        let error = encrypt_stream_with_chunk_length(encryption_params.clone(), chunk_length, Cursor::new(&[]), vec![], &[]).err().unwrap();
+        assert!(matches!(error, Error::Format { .. }));
+    }
+}
+
+#[test]
+fn truncation_is_detected() {
+    let (encryption_params, decryption_params) = params(Algorithm::ChaCha20Poly1305);
+    let chunk_length = 100;
+    let encrypted_chunk_length = chunk_length + TAG_LENGTH;
+    let header_length = 17;
+    let data = generate_bytes(450).unwrap();
+    let encrypted = encrypt_with_chunk_length(&encryption_params, chunk_length, data.as_slice(), &[]);
+
+    // whole chunks removed from the end
+    for chunks in 0..5 {
+        let truncated = &encrypted[..header_length + chunks * encrypted_chunk_length];
+        let error = decrypt(&decryption_params, truncated, &[]).err().unwrap();
+        assert!(matches!(error, Error::Authentication { .. }));
+    }
+
+    // stream cut at any position
+    for length in (0..encrypted.len()).step_by(7) {
+        assert!(decrypt(&decryption_params, &encrypted[..length], &[]).is_err());
+    }
+
+    // appended data
+    let mut extended = encrypted.to_vec();
+    extended.extend_from_slice(&encrypted[header_length..header_length + encrypted_chunk_length]);
+    assert!(decrypt(&decryption_params, extended.as_slice(), &[]).is_err());
+}
+
+#[test]
+fn reordering_is_detected() {
+    let (encryption_params, decryption_params) = params(Algorithm::Aes128Gcm);
+    let chunk_length = 100;
+    let encrypted_chunk_length = chunk_length + TAG_LENGTH;
+    let header_length = 17;
+    let data = generate_bytes(450).unwrap();
+    let encrypted = encrypt_with_chunk_length(&encryption_params, chunk_length, data.as_slice(), &[]);
+
+    let (header, chunks) = encrypted.split_at(header_length);
+    let chunks: Vec<&[u8]> = chunks.chunks(encrypted_chunk_length).collect();
+    assert_eq!(chunks.len(), 5);
+
+    let swapped = [header, chunks[1], chunks[0], chunks[2], chunks[3], chunks[4]].concat();
+    let error = decrypt(&decryption_params, swapped.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+
+    let duplicated = [header, chunks[0], chunks[0], chunks[1], chunks[2], chunks[3], chunks[4]].concat();
+    assert!(decrypt(&decryption_params, duplicated.as_slice(), &[]).is_err());
+
+    let removed = [header, chunks[0], chunks[2], chunks[3], chunks[4]].concat();
+    assert!(decrypt(&decryption_params, removed.as_slice(), &[]).is_err());
+
+    // chunks of another stream encrypted by the same key
+    let other = encrypt_with_chunk_length(&encryption_params, chunk_length, data.as_slice(), &[]);
+    let mixed = [header, chunks[0], &other[header_length + encrypted_chunk_length..]].concat();
+    assert!(decrypt(&decryption_params, mixed.as_slice(), &[]).is_err());
+
+    let restored = [header, chunks[0], chunks[1], chunks[2], chunks[3], chunks[4]].concat();
+    assert_eq!(decrypt(&decryption_params, restored.as_slice(), &[]).unwrap(), data);
+}
+
+#[test]
+fn tampered_stream_is_rejected() {
+    let (encryption_params, decryption_params) = params(Algorithm::Aes256Gcm);
+    let data = generate_bytes(300).unwrap();
+    let encrypted = encrypt_with_chunk_length(&encryption_params, 64, data.as_slice(), &[]);
+
+    for index in 0..encrypted.len() {
+        let mut tampered = encrypted.to_vec();
+        tampered[index] ^= 0x01;
+        assert!(decrypt(&decryption_params, tampered.as_slice(), &[]).is_err());
+    }
+
+    let error = decrypt(&decryption_params, "not a stream".as_bytes(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Format { .. }));
+
+    let (_, other_decryption_params) = params(Algorithm::XChaCha20Poly1305);
+    let error = decrypt(&other_decryption_params, encrypted.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::KeyMismatch { .. }));
+
+    let (_, other_decryption_params) = params(Algorithm::Aes256Gcm);
+    let error = decrypt(&other_decryption_params, encrypted.as_slice(), &[]).err().unwrap();
+    assert!(matches!(error, Error::Authentication { .. }));
+}
+
+#[test]
+fn chunks_follow_stream_be32() {
+    use aead::stream::DecryptorBE32;
+    use aead::{generic_array::GenericArray, Payload};
+    use aes_gcm::Aes256Gcm;
+
+    let (encryption_params, _) = params(Algorithm::Aes256Gcm);
+    let chunk_length = 1000;
+    let data = generate_bytes(3500).unwrap();
+    let associated_data = "some associated data".as_bytes();
+    let encrypted = encrypt_with_chunk_length(&encryption_params, chunk_length, data.as_slice(), associated_data);
+
+    // header: magic, version, algorithm, chunk length and 7 bytes long nonce prefix
+    let (header, chunks) = encrypted.split_at(17);
+    assert_eq!(&header[..6], b"CXSS\x01\x02");
+    assert_eq!(&header[6..10], &(chunk_length as u32).to_be_bytes());
+
+    let key = GenericArray::from_slice(encryption_params.key.as_slice());
+    let mut decryptor = DecryptorBE32::<Aes256Gcm>::new(key, GenericArray::from_slice(&header[10..]));
+    let chunk_associated_data = [header, associated_data].concat();
+    let chunks: Vec<&[u8]> = chunks.chunks(chunk_length + TAG_LENGTH).collect();
+
+    let mut decrypted = vec![];
+    for chunk in &chunks[..chunks.len() - 1] {
+        let payload = Payload { msg: chunk, aad: chunk_associated_data.as_slice() };
+        decrypted.extend_from_slice(decryptor.decrypt_next(payload).unwrap().as_slice());
+    }
+    let payload = Payload { msg: chunks[chunks.len() - 1], aad: chunk_associated_data.as_slice() };
+    decrypted.extend_from_slice(decryptor.decrypt_last(payload).unwrap().as_slice());
+
+    assert_eq!(decrypted, data);
+}
@@ -0,0 +1,9 @@
+// helpers shared by the tests of several modules
+
+// decodes test vectors, panics on malformed input
+pub(crate) fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())
+        .collect()
+}